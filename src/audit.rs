@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use crate::dates;
+use crate::get_data_dir;
+use crate::lock;
+use crate::sha256;
+use crate::term;
+
+/// `prev` of the first entry
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    time: String,
+    command: String,
+    /// SHA-256 of the request body, so the log records what was sent without storing it
+    payload_hash: String,
+    result: String,
+    /// Hash of the previous entry, chaining the log so edits and deletions show up
+    prev: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Record {
+    #[serde(flatten)]
+    entry: Entry,
+    hash: String,
+}
+
+impl Entry {
+    fn hash(&self) -> String {
+        sha256::hex(serde_json::to_string(self).unwrap_or_default().as_bytes())
+    }
+}
+
+pub fn get_audit_path() -> PathBuf {
+    get_data_dir().join("audit.log")
+}
+
+/// The last line that isn't blank, read from the end a block at a time so
+/// a long log isn't read whole for every entry
+fn last_line(file: &mut (impl Read + Seek)) -> io::Result<String> {
+    const BLOCK: u64 = 4096;
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut tail = Vec::new();
+    let mut start = len;
+    while start > 0 {
+        let from = start.saturating_sub(BLOCK);
+        let mut block = vec![0; (start - from) as usize];
+        file.seek(SeekFrom::Start(from))?;
+        file.read_exact(&mut block)?;
+        block.extend_from_slice(&tail);
+        tail = block;
+        start = from;
+        let text = tail.trim_ascii_end();
+        // Once a newline comes before the last line, all of it has been read
+        if text.contains(&b'\n') {
+            break;
+        }
+    }
+    let text = String::from_utf8_lossy(tail.trim_ascii_end()).into_owned();
+    Ok(text.lines().last().unwrap_or_default().to_string())
+}
+
+fn last_hash(line: &str) -> String {
+    serde_json::from_str::<Record>(line)
+        .map(|record| record.hash)
+        .unwrap_or_else(|_| GENESIS.to_string())
+}
+
+fn chained(prev: String, command: &str, payload: &str, result: &str) -> Record {
+    let entry = Entry {
+        time: dates::format_utc(dates::now_unix()),
+        command: command.to_string(),
+        payload_hash: sha256::hex(payload.as_bytes()),
+        result: result.to_string(),
+        prev,
+    };
+    Record {
+        hash: entry.hash(),
+        entry,
+    }
+}
+
+fn append(command: &str, payload: &str, result: &str) -> io::Result<()> {
+    let path = get_audit_path();
+    // Reading the last hash and appending is one step, or two sends at once
+    // would both chain to the same entry
+    let _lock = lock::exclusive(&get_data_dir().join("audit.lock"))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)?;
+    let record = chained(last_hash(&last_line(&mut file)?), command, payload, result);
+    let line = serde_json::to_string(&record).map_err(io::Error::other)?;
+    writeln!(file, "{}", line)
+}
+
+/// Append an API mutation to the audit log. Failing to log is reported but
+/// never stops the mutation itself.
+pub fn record(command: &str, payload: &str, result: &str) {
+    if let Err(e) = append(command, payload, result) {
+        eprintln!("Warning: failed to write audit log: {}", e);
+    }
+}
+
+/// Check the log's hash chain, returning the entries up to the first
+/// problem, and the problem if there is one
+fn verify(content: &str) -> (Vec<Record>, Option<String>) {
+    let mut prev = GENESIS.to_string();
+    let mut records = Vec::new();
+
+    let problem = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .find_map(|(i, line)| {
+            let number = i + 1;
+            let record = match serde_json::from_str::<Record>(line) {
+                Ok(record) => record,
+                Err(_) => {
+                    return Some(format!(
+                        "Audit log damaged: line {} is not a valid entry",
+                        number
+                    ))
+                }
+            };
+            if record.entry.hash() != record.hash {
+                return Some(format!(
+                    "Audit log tampered: line {} has been modified",
+                    number
+                ));
+            }
+            if record.entry.prev != prev {
+                return Some(format!(
+                    "Audit log tampered: entries before line {} were removed or reordered",
+                    number
+                ));
+            }
+            prev = record.hash.clone();
+            records.push(record);
+            None
+        });
+    (records, problem)
+}
+
+/// Print the audit log and check its hash chain. Returns false if it was tampered with.
+pub fn show() -> bool {
+    let path = get_audit_path();
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    let (records, problem) = verify(&content);
+
+    if term::json() {
+        term::print_json(&serde_json::json!({
+            "path": path,
+            "intact": problem.is_none(),
+            "error": problem,
+            "entries": records,
+        }));
+        return problem.is_none();
+    }
+    for record in &records {
+        print_entry(&record.entry);
+    }
+    match problem {
+        Some(problem) => {
+            eprintln!("{}", problem);
+            false
+        }
+        None => {
+            println!(
+                "Audit log intact: {} entries ({})",
+                records.len(),
+                path.display()
+            );
+            true
+        }
+    }
+}
+
+fn print_entry(entry: &Entry) {
+    let result = match entry.result.strip_prefix("created ") {
+        Some(id) if entry.command == "create" => {
+            format!("created {}", term::hyperlink(id, &crate::task_url(id)))
+        }
+        _ => entry.result.clone(),
+    };
+    println!(
+        "{}  {:<8} {}  {}",
+        entry.time,
+        entry.command,
+        &entry.payload_hash[..entry.payload_hash.len().min(12)],
+        result
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn log(count: usize) -> Vec<String> {
+        let mut prev = GENESIS.to_string();
+        (0..count)
+            .map(|i| {
+                let record = chained(prev.clone(), "create", &i.to_string(), "created x");
+                prev = record.hash.clone();
+                serde_json::to_string(&record).unwrap()
+            })
+            .collect()
+    }
+
+    fn problem(lines: &[String]) -> Option<String> {
+        verify(&(lines.join("\n") + "\n")).1
+    }
+
+    #[test]
+    fn untouched_chain_verifies() {
+        let (records, problem) = verify(&(log(3).join("\n") + "\n"));
+        assert_eq!(records.len(), 3);
+        assert_eq!(problem, None);
+    }
+
+    #[test]
+    fn edited_entry_is_found() {
+        let mut lines = log(3);
+        lines[1] = lines[1].replace("created x", "created y");
+        let problem = problem(&lines).unwrap();
+        assert!(problem.contains("line 2 has been modified"), "{}", problem);
+    }
+
+    #[test]
+    fn deleted_entry_is_found() {
+        let mut lines = log(3);
+        lines.remove(0);
+        let problem = problem(&lines).unwrap();
+        assert!(problem.contains("before line 1"), "{}", problem);
+    }
+
+    #[test]
+    fn reordered_entries_are_found() {
+        let mut lines = log(3);
+        lines.swap(1, 2);
+        let problem = problem(&lines).unwrap();
+        assert!(problem.contains("before line 2"), "{}", problem);
+    }
+
+    #[test]
+    fn last_line_is_read_from_the_end() {
+        let lines = log(200);
+        let content = lines.join("\n") + "\n\n";
+        assert!(content.len() > 3 * 4096);
+        let last = last_line(&mut Cursor::new(content)).unwrap();
+        assert_eq!(last, lines[199]);
+        assert_eq!(last_hash(&last), verify(&lines.join("\n")).0[199].hash);
+
+        assert_eq!(last_line(&mut Cursor::new("")).unwrap(), "");
+        assert_eq!(last_line(&mut Cursor::new("only\n")).unwrap(), "only");
+        assert_eq!(last_hash(""), GENESIS);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::init::{self, Tty};
+use crate::{credentials, mock, profile, Api};
+
+/// `auth login`: ask for the active profile's key, check it and save it in
+/// the system's keyring
+pub fn login(connect: impl Fn(String) -> Api) -> Result<(), String> {
+    if mock::enabled() {
+        return Err("--mock needs no API key".into());
+    }
+    let keyring = credentials::keyring().ok_or(
+        "no keyring to keep the key in (on Linux, install secret-tool from libsecret); set GODSPEED_API, or run init to keep it in the config file",
+    )?;
+    let mut tty = Tty::open().ok_or("auth login asks for the key, so it needs a terminal")?;
+    let profile = profile::active();
+    let (key, _) = init::ask_key(&mut tty, profile, connect)?;
+    keyring.set(profile, &key)?;
+    tty.say(&format!(
+        "Saved the key for profile {} in {}.",
+        profile,
+        keyring.name()
+    ))
+}
+
+/// `auth logout`: remove the active profile's key from the keyring
+pub fn logout() -> Result<(), String> {
+    let profile = profile::active();
+    let Some(keyring) = credentials::keyring() else {
+        println!(
+            "There's no keyring, so no key to remove for profile {}",
+            profile
+        );
+        return Ok(());
+    };
+    if keyring.delete(profile)? {
+        println!(
+            "Removed the key for profile {} from {}",
+            profile,
+            keyring.name()
+        );
+    } else {
+        println!("Found no key for profile {} in {}", profile, keyring.name());
+    }
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::dates;
+use crate::lock::FileLock;
+use crate::queue;
+use crate::{get_data_dir, write_atomic};
+
+/// Local state worth rolling back. The audit log is left out on purpose:
+/// restoring it would rewrite history it exists to protect. `cache` is the
+/// old offline queue, restored from older snapshots and migrated when read.
+/// Each name cache goes with its `ETag`, so a restored cache is never taken
+/// for the server's current one.
+const FILES: [&str; 9] = [
+    "queue.jsonl",
+    "dead-letter.jsonl",
+    "cache",
+    "lists.toml",
+    "lists.etag",
+    "labels.toml",
+    "labels.etag",
+    "members.toml",
+    "members.etag",
+];
+
+const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// Take a snapshot when the newest one is more than a week old
+    pub weekly: bool,
+    /// Number of snapshots kept; older ones are deleted
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            weekly: true,
+            keep: 4,
+        }
+    }
+}
+
+fn get_snapshots_dir() -> PathBuf {
+    get_data_dir().join("snapshots")
+}
+
+/// Snapshot names, oldest first. Names start with a UTC timestamp, so they sort by age.
+pub fn list() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(get_snapshots_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// The flush and queue locks, so no run sends or rewrites the queue while
+/// it's copied or replaced. Waits for a flush in progress unless `wait` is
+/// false, when it's None instead.
+fn hold(wait: bool) -> io::Result<Option<(FileLock, FileLock)>> {
+    let Some(flushing) = queue::flush_lock(wait)? else {
+        return Ok(None);
+    };
+    Ok(Some((flushing, queue::lock()?)))
+}
+
+/// Copy the local state into a new snapshot, then delete the oldest beyond `keep`
+pub fn snapshot(reason: &str, keep: usize) -> io::Result<String> {
+    let _locks = hold(true)?;
+    take(reason, keep)
+}
+
+fn take(reason: &str, keep: usize) -> io::Result<String> {
+    let time = dates::format_utc(dates::now_unix()).replace(':', "");
+    let name = format!("{}-{}", time, reason);
+    let dir = get_snapshots_dir().join(&name);
+    fs::create_dir_all(&dir)?;
+    for file in FILES {
+        let source = get_data_dir().join(file);
+        if source.exists() {
+            fs::copy(&source, dir.join(file))?;
+        }
+    }
+
+    for old in pruned(&list(), keep) {
+        fs::remove_dir_all(get_snapshots_dir().join(old))?;
+    }
+    Ok(name)
+}
+
+/// The oldest of `names` beyond the newest `keep`, which is at least one
+fn pruned(names: &[String], keep: usize) -> &[String] {
+    &names[..names.len().saturating_sub(keep.max(1))]
+}
+
+/// Take the weekly snapshot if one is due. Problems are reported, never fatal.
+pub fn weekly(config: &BackupConfig) {
+    if !config.weekly {
+        return;
+    }
+    let newest = list()
+        .last()
+        .and_then(|name| fs::metadata(get_snapshots_dir().join(name)).ok())
+        .and_then(|meta| meta.modified().ok());
+    let due = newest.is_none_or(|time| {
+        SystemTime::now()
+            .duration_since(time)
+            .is_ok_and(|age| age >= WEEK)
+    });
+    if !due {
+        return;
+    }
+    // Not worth holding up a capture for; the next run takes it
+    let taken = hold(false).and_then(|locks| match locks {
+        Some(_locks) => take("weekly", config.keep).map(Some),
+        None => Ok(None),
+    });
+    if let Err(e) = taken {
+        eprintln!("Warning: failed to back up local data: {}", e);
+    }
+}
+
+/// Replace the local state with a snapshot's, after snapshotting the current
+/// state so the restore itself can be undone
+pub fn restore(name: &str, keep: usize) -> io::Result<String> {
+    let source = get_snapshots_dir().join(name);
+    if !valid_name(name) || !source.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No snapshot named {}", name),
+        ));
+    }
+    let _locks = hold(true)?;
+    // Keep one more, so the snapshot being restored survives the pruning
+    let undo = take("before-restore", keep + 1)?;
+
+    for file in FILES {
+        let from = source.join(file);
+        let to = get_data_dir().join(file);
+        if from.exists() {
+            write_atomic(&to, fs::read(&from)?)?;
+        } else if to.exists() {
+            // The file didn't exist yet when the snapshot was taken
+            fs::remove_file(&to)?;
+        }
+    }
+    Ok(undo)
+}
+
+/// A snapshot's own name, which can't reach outside the snapshots directory
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains("..") && !name.contains(['/', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn oldest_snapshots_beyond_keep_are_pruned() {
+        let all = names(&[
+            "2026-01-01T000000Z-weekly",
+            "2026-01-08T000000Z-weekly",
+            "2026-01-09T000000Z-before-clear",
+        ]);
+        assert_eq!(pruned(&all, 2), &all[..1]);
+        assert_eq!(pruned(&all, 3), &[] as &[String]);
+        assert_eq!(pruned(&all, 5), &[] as &[String]);
+        // The snapshot just taken is always kept
+        assert_eq!(pruned(&all, 0), &all[..2]);
+    }
+
+    #[test]
+    fn snapshot_names_stay_in_the_snapshots_directory() {
+        assert!(valid_name("2026-01-01T000000Z-weekly"));
+        for name in ["", "..", "../queue.jsonl", "a/b", "a\\b", "..\\x"] {
+            assert!(!valid_name(name), "{:?}", name);
+        }
+    }
+}
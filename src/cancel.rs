@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Exit status used when the user interrupts a command, as shells do for SIGINT
+pub const EXIT_CODE: i32 = 130;
+
+#[cfg(unix)]
+extern "C" fn handle_sigint(_: libc::c_int) {
+    // A second Ctrl-C means "stop now", even if that abandons the current step
+    if CANCELLED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(EXIT_CODE) };
+    }
+}
+
+/// Route Ctrl-C into a cancellation flag instead of killing the process outright,
+/// so file writes in progress always complete before we stop.
+#[cfg(unix)]
+pub fn install_handler() {
+    let handler = handle_sigint as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+    }
+}
+
+/// The same for a console's Ctrl-C and Ctrl-Break, which Windows delivers
+/// on a thread of its own
+#[cfg(windows)]
+pub fn install_handler() {
+    unsafe {
+        console::SetConsoleCtrlHandler(Some(console::handle_ctrl), 1);
+    }
+}
+
+/// Other platforms keep the default, where Ctrl-C stops the run at once
+#[cfg(not(any(unix, windows)))]
+pub fn install_handler() {}
+
+#[cfg(windows)]
+mod console {
+    use std::sync::atomic::Ordering;
+
+    use super::{CANCELLED, EXIT_CODE};
+
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn SetConsoleCtrlHandler(
+            handler: Option<unsafe extern "system" fn(u32) -> i32>,
+            add: i32,
+        ) -> i32;
+        fn ExitProcess(code: u32) -> !;
+    }
+
+    pub unsafe extern "system" fn handle_ctrl(kind: u32) -> i32 {
+        if kind != CTRL_C_EVENT && kind != CTRL_BREAK_EVENT {
+            // Closing the window or logging off still ends the process
+            return 0;
+        }
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            ExitProcess(EXIT_CODE as u32);
+        }
+        1
+    }
+}
+
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Run a blocking network call on a worker thread, returning early if Ctrl-C is pressed.
+/// The worker only does network I/O, so abandoning it can't leave files half-written.
+pub fn run<T, F>(f: F) -> Result<T, Box<dyn Error>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, Box<dyn Error + Send + Sync>> + Send + 'static,
+{
+    if is_cancelled() {
+        return Err("Cancelled".into());
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(f());
+    });
+
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(50)) {
+            Ok(result) => return result.map_err(|e| e as Box<dyn Error>),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if is_cancelled() {
+                    return Err("Cancelled".into());
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err("Request worker stopped unexpectedly".into());
+            }
+        }
+    }
+}
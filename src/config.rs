@@ -0,0 +1,355 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::backup::BackupConfig;
+use crate::daemon::DaemonConfig;
+use crate::dotenv::DotenvConfig;
+use crate::git::GitConfig;
+use crate::home_dir;
+use crate::logfile::LogConfig;
+use crate::matching::MatchingConfig;
+use crate::notify::NotificationConfig;
+use crate::profile::ProfileConfig;
+use crate::queue::QueueConfig;
+use crate::receipt::ReceiptConfig;
+use crate::term::OutputConfig;
+use crate::urls::UrlConfig;
+use crate::version::UpdatesConfig;
+use crate::watch::WatchConfig;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Applied when a task has no `@list`, `.label` or `:duration` of its own
+    pub default_list: Option<String>,
+    pub default_labels: Vec<String>,
+    /// Duration such as `15` or `30m`
+    pub default_duration: Option<String>,
+    /// Fail when a list, label or teammate can't be found, instead of ignoring it
+    pub strict: bool,
+    /// Create labels that don't exist instead of dropping them
+    pub create_labels: bool,
+    pub create_lists: CreateLists,
+    pub pick_list: PickList,
+    pub matching: MatchingConfig,
+    pub urls: UrlConfig,
+    /// Shortcuts for list, label and teammate names (`dw = "deep-work"`)
+    pub aliases: HashMap<String, String>,
+    /// Custom sigils and what they expand to (`"~" = ".{}"`)
+    pub sigils: HashMap<String, String>,
+    pub profiles: HashMap<String, ProfileConfig>,
+    pub api: ApiConfig,
+    pub cache: CacheConfig,
+    #[serde(deserialize_with = "crate::notify::enabled_or_table")]
+    pub notifications: NotificationConfig,
+    pub receipts: ReceiptConfig,
+    pub queue: QueueConfig,
+    pub daemon: DaemonConfig,
+    pub watch: WatchConfig,
+    pub git: GitConfig,
+    pub backups: BackupConfig,
+    pub output: OutputConfig,
+    pub updates: UpdatesConfig,
+    pub dotenv: DotenvConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Where requests go instead of the Godspeed API, such as a staging
+    /// server or a local mock; `GODSPEED_API_URL` takes precedence
+    pub base_url: Option<String>,
+    /// `http://`, `https://` or `socks5://` proxy for every request, over
+    /// `HTTPS_PROXY` and `ALL_PROXY`
+    pub proxy: Option<String>,
+    /// PEM file of extra root certificates, such as a TLS-intercepting
+    /// proxy's
+    pub ca_file: Option<String>,
+    /// PEM file of the only root certificates trusted for the API
+    pub pin_file: Option<String>,
+    /// Lowest TLS version accepted; only `1.2` can be set
+    pub min_tls: Option<String>,
+    /// Requests in flight at once when sending many tasks, such as an
+    /// import or the queue
+    pub concurrency: usize,
+    /// Seconds before a request gives up; `GODSPEED_TIMEOUT` and `--timeout`
+    /// take precedence
+    pub timeout_secs: Option<u64>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig {
+            base_url: None,
+            proxy: None,
+            ca_file: None,
+            pin_file: None,
+            min_tls: None,
+            concurrency: 5,
+            timeout_secs: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Caches of lists, labels and teammates older than this are refreshed
+    /// in the background after a capture; 0 never refreshes them
+    pub max_age_hours: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig { max_age_hours: 24 }
+    }
+}
+
+/// What to do when `@list` names a list that doesn't exist
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CreateLists {
+    /// Ask on the terminal, if there is one
+    #[default]
+    Ask,
+    Always,
+    Never,
+}
+
+/// When to open the list picker for a capture without `@list`
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PickList {
+    /// When there's no default list and the capture was typed on a terminal
+    #[default]
+    Missing,
+    /// Always, and labels too, as with `--pick`
+    Always,
+    Never,
+}
+
+/// `XDG_CONFIG_HOME`, or `%APPDATA%` on Windows, so the settings roam with
+/// the user, and `~/.config` elsewhere
+fn get_config_home() -> PathBuf {
+    if let Some(xdg) = env::var_os("XDG_CONFIG_HOME").filter(|xdg| !xdg.is_empty()) {
+        return PathBuf::from(xdg);
+    }
+    if cfg!(windows) {
+        if let Some(roaming) = env::var_os("APPDATA") {
+            return PathBuf::from(roaming);
+        }
+    }
+    home_dir().unwrap_or_default().join(".config")
+}
+
+pub fn get_config_dir() -> PathBuf {
+    get_config_home().join("godspeed-cli")
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_config_dir().join("config.toml")
+}
+
+/// Load the config file, falling back to defaults if it is missing.
+/// A file that can't be parsed is reported and otherwise ignored, so a typo
+/// never stops a capture.
+pub fn load() -> Config {
+    let path = get_config_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Config::default(),
+    };
+
+    match toml::from_str(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!(
+                "Warning: ignoring invalid config file {}: {}",
+                path.display(),
+                e
+            );
+            Config::default()
+        }
+    }
+}
+
+/// A key's value in the config file, such as `api.timeout_secs`, as JSON.
+/// None if the file doesn't set it, so its default applies.
+pub fn get(key: &str) -> Result<Option<serde_json::Value>, String> {
+    let path = get_config_path();
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    let table: toml::Table =
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut value = &toml::Value::Table(table);
+    for part in key.split('.') {
+        match value.get(part) {
+            Some(next) => value = next,
+            None => return Ok(None),
+        }
+    }
+    serde_json::to_value(value)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Set a key in the config file, keeping the rest of it, comments included.
+/// The value is read as TOML (`10`, `true`, `["a", "b"]`) and otherwise
+/// taken as text. Nothing is written if the config would no longer load.
+pub fn set(key: &str, value: &str) -> Result<(), String> {
+    save(&set_in(&read(), key, value)?)
+}
+
+fn set_in(content: &str, key: &str, value: &str) -> Result<String, String> {
+    let as_text = toml_edit::Value::from(value);
+    let candidates = match value.parse::<toml_edit::Value>() {
+        Ok(parsed) => vec![parsed, as_text],
+        Err(_) => vec![as_text],
+    };
+    let mut first_error = None;
+    for candidate in candidates {
+        // `1.2` reads as a number, but `min_tls` wants it as text
+        match change(content, key, |table, last| {
+            table.insert(last, toml_edit::Item::Value(candidate));
+        }) {
+            Ok(changed) => return Ok(changed),
+            Err(e) => {
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    Err(first_error.unwrap_or_default())
+}
+
+/// Remove a key from the config file, returning false if it wasn't set
+pub fn unset(key: &str) -> Result<bool, String> {
+    let (content, removed) = unset_in(&read(), key)?;
+    save(&content)?;
+    Ok(removed)
+}
+
+fn unset_in(content: &str, key: &str) -> Result<(String, bool), String> {
+    let mut removed = false;
+    let content = change(content, key, |table, last| {
+        removed = table.remove(last).is_some()
+    })?;
+    Ok((content, removed))
+}
+
+fn read() -> String {
+    fs::read_to_string(get_config_path()).unwrap_or_default()
+}
+
+fn save(content: &str) -> Result<(), String> {
+    fs::create_dir_all(get_config_dir()).map_err(|e| e.to_string())?;
+    crate::write_atomic(&get_config_path(), content).map_err(|e| e.to_string())
+}
+
+/// Apply `edit` to the table holding the last part of `key`, creating the
+/// sections on the way, and return the file as it would be once checked
+fn change(
+    content: &str,
+    key: &str,
+    edit: impl FnOnce(&mut toml_edit::Table, &str),
+) -> Result<String, String> {
+    let mut document = content
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| format!("{}: {}", get_config_path().display(), e))?;
+    let parts: Vec<&str> = key.split('.').collect();
+    let Some((last, sections)) = parts.split_last().filter(|(last, _)| !last.is_empty()) else {
+        return Err(format!("invalid key {:?}", key));
+    };
+    let mut table = document.as_table_mut();
+    for section in sections {
+        let item = table.entry(section).or_insert_with(|| {
+            let mut new = toml_edit::Table::new();
+            // `[profiles.work]` needs no `[profiles]` header of its own
+            new.set_implicit(true);
+            toml_edit::Item::Table(new)
+        });
+        table = item.as_table_mut().ok_or_else(|| {
+            format!(
+                "{} is not a section; change it with `godspeed-cli config edit`",
+                section
+            )
+        })?;
+    }
+    edit(table, last);
+
+    let content = document.to_string();
+    check(&content)?;
+    Ok(content)
+}
+
+/// Why `content` wouldn't load as the config, if it wouldn't
+pub fn check(content: &str) -> Result<(), String> {
+    toml::from_str::<Config>(content)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_adds_the_section_and_keeps_comments() {
+        let content = "# my settings\nstrict = true\n";
+        let changed = set_in(content, "api.timeout_secs", "10").unwrap();
+        assert_eq!(
+            changed,
+            "# my settings\nstrict = true\n\n[api]\ntimeout_secs = 10\n"
+        );
+        let config: Config = toml::from_str(&changed).unwrap();
+        assert_eq!(config.api.timeout_secs, Some(10));
+        assert!(config.strict);
+    }
+
+    #[test]
+    fn set_profile_key_needs_no_parent_header() {
+        let changed = set_in("", "profiles.work.api_key_env", "WORK_KEY").unwrap();
+        assert_eq!(changed, "[profiles.work]\napi_key_env = \"WORK_KEY\"\n");
+    }
+
+    #[test]
+    fn set_falls_back_to_text() {
+        let changed = set_in("", "api.min_tls", "1.2").unwrap();
+        assert_eq!(changed, "[api]\nmin_tls = \"1.2\"\n");
+        let changed = set_in("", "default_labels", r#"["a", "b"]"#).unwrap();
+        assert_eq!(changed, "default_labels = [\"a\", \"b\"]\n");
+    }
+
+    #[test]
+    fn set_refuses_what_would_not_load() {
+        assert!(set_in("", "strict", "maybe").is_err());
+        assert!(set_in("strict = 1", "api.timeout_secs", "10").is_err());
+        assert!(set_in("", "strict.deep", "true").is_err());
+        assert!(set_in("", "api.", "1").is_err());
+    }
+
+    #[test]
+    fn unset_undoes_set() {
+        let content = "strict = true\n";
+        let changed = set_in(content, "create_labels", "true").unwrap();
+        let (restored, removed) = unset_in(&changed, "create_labels").unwrap();
+        assert!(removed);
+        assert_eq!(restored, content);
+        let (unchanged, removed) = unset_in(content, "api.proxy").unwrap();
+        assert!(!removed);
+        assert_eq!(
+            toml::from_str::<Config>(&unchanged).unwrap().api.proxy,
+            None
+        );
+    }
+
+    #[test]
+    fn check_reports_bad_values() {
+        assert!(check("strict = true\n[api]\ntimeout_secs = 5\n").is_ok());
+        let error = check("[api]\ntimeout_secs = \"soon\"\n").unwrap_err();
+        assert!(error.contains("timeout_secs"), "{}", error);
+        assert!(check("strict = [").is_err());
+    }
+}
@@ -0,0 +1,362 @@
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::process::{Command, Stdio};
+
+/// The service the keys are saved under; each profile has its own key
+const SERVICE: &str = "godspeed-cli";
+
+/// Somewhere outside the config file to keep API keys, one per profile
+pub trait Keyring {
+    /// What it's called in messages, such as "Saved the key in ..."
+    fn name(&self) -> &'static str;
+    /// The profile's key, or None if it has none here
+    fn get(&self, profile: &str) -> Result<Option<String>, String>;
+    /// Save the profile's key, replacing any it had
+    fn set(&self, profile: &str, key: &str) -> Result<(), String>;
+    /// Remove the profile's key, returning whether it had one
+    fn delete(&self, profile: &str) -> Result<bool, String>;
+}
+
+/// This system's keyring: the Keychain on macOS, Credential Manager on
+/// Windows and the Secret Service (through `secret-tool`) elsewhere. None
+/// when there's none to use, so keys come from the environment or config.
+#[cfg(target_os = "macos")]
+pub fn keyring() -> Option<&'static dyn Keyring> {
+    Some(&Keychain)
+}
+
+#[cfg(windows)]
+pub fn keyring() -> Option<&'static dyn Keyring> {
+    Some(&credential_manager::CredentialManager)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn keyring() -> Option<&'static dyn Keyring> {
+    if crate::on_path("secret-tool") {
+        Some(&SecretService)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn keyring() -> Option<&'static dyn Keyring> {
+    None
+}
+
+/// The profile's key from the keyring, warning rather than failing when it
+/// can't be read, so another way of giving the key still works
+pub fn lookup(profile: &str) -> Option<String> {
+    let keyring = keyring()?;
+    keyring.get(profile).unwrap_or_else(|e| {
+        eprintln!("Warning: {}", e);
+        None
+    })
+}
+
+/// Run `program`, giving it `input` on stdin rather than as an argument,
+/// where `ps` would show it
+#[cfg(unix)]
+fn run_with_input(
+    program: &str,
+    args: &[&str],
+    input: &str,
+) -> Result<std::process::Output, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Can't run {}: {}", program, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(input.as_bytes())
+            .map_err(|e| format!("Can't talk to {}: {}", program, e))?;
+    }
+    child
+        .wait_with_output()
+        .map_err(|e| format!("Can't run {}: {}", program, e))
+}
+
+/// The login Keychain, through the `security` tool
+#[cfg(target_os = "macos")]
+struct Keychain;
+
+#[cfg(target_os = "macos")]
+impl Keychain {
+    /// `security`'s exit status when there's no such item
+    const NOT_FOUND: i32 = 44;
+
+    /// A word for `security -i`, which splits its input like a shell
+    fn quote(word: &str) -> String {
+        format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Keyring for Keychain {
+    fn name(&self) -> &'static str {
+        "the macOS Keychain"
+    }
+
+    fn get(&self, profile: &str) -> Result<Option<String>, String> {
+        let output = Command::new("security")
+            .args(["find-generic-password", "-s", SERVICE, "-a", profile, "-w"])
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| format!("Can't run security: {}", e))?;
+        match output.status.code() {
+            Some(0) => Ok(
+                Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+                    .filter(|key| !key.is_empty()),
+            ),
+            Some(Self::NOT_FOUND) => Ok(None),
+            _ => Err("the Keychain refused to give up the API key".to_string()),
+        }
+    }
+
+    fn set(&self, profile: &str, key: &str) -> Result<(), String> {
+        let command = format!(
+            "add-generic-password -U -s {} -a {} -l {} -w {}\n",
+            Self::quote(SERVICE),
+            Self::quote(profile),
+            Self::quote(&format!("{} API key ({})", SERVICE, profile)),
+            Self::quote(key)
+        );
+        let output = run_with_input("security", &["-i"], &command)?;
+        // Interactive mode doesn't always exit non-zero when a command fails
+        let errors = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() || !errors.trim().is_empty() {
+            return Err(format!(
+                "Can't save the key in the Keychain: {}",
+                errors.trim()
+            ));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, profile: &str) -> Result<bool, String> {
+        let output = Command::new("security")
+            .args(["delete-generic-password", "-s", SERVICE, "-a", profile])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| format!("Can't run security: {}", e))?;
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(Self::NOT_FOUND) => Ok(false),
+            _ => Err("Can't remove the key from the Keychain".to_string()),
+        }
+    }
+}
+
+/// GNOME Keyring, KWallet or another Secret Service, through `secret-tool`
+/// from libsecret
+#[cfg(all(unix, not(target_os = "macos")))]
+struct SecretService;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl SecretService {
+    fn attributes(profile: &str) -> [&str; 4] {
+        ["service", SERVICE, "profile", profile]
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+impl Keyring for SecretService {
+    fn name(&self) -> &'static str {
+        "the Secret Service keyring"
+    }
+
+    fn get(&self, profile: &str) -> Result<Option<String>, String> {
+        let output = Command::new("secret-tool")
+            .arg("lookup")
+            .args(Self::attributes(profile))
+            .output()
+            .map_err(|e| format!("Can't run secret-tool: {}", e))?;
+        let errors = String::from_utf8_lossy(&output.stderr);
+        match output.status.code() {
+            Some(0) => Ok(
+                Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+                    .filter(|key| !key.is_empty()),
+            ),
+            // Nothing stored: a failure with nothing to say
+            Some(1) if errors.trim().is_empty() => Ok(None),
+            _ => Err(format!(
+                "Can't read the API key from the keyring: {}",
+                errors.trim()
+            )),
+        }
+    }
+
+    fn set(&self, profile: &str, key: &str) -> Result<(), String> {
+        let label = format!("--label={} API key ({})", SERVICE, profile);
+        let mut args = vec!["store", label.as_str()];
+        args.extend(Self::attributes(profile));
+        let output = run_with_input("secret-tool", &args, key)?;
+        if !output.status.success() {
+            return Err(format!(
+                "Can't save the key in the keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, profile: &str) -> Result<bool, String> {
+        // `clear` succeeds whether or not there was a key
+        if self.get(profile)?.is_none() {
+            return Ok(false);
+        }
+        let output = Command::new("secret-tool")
+            .arg("clear")
+            .args(Self::attributes(profile))
+            .output()
+            .map_err(|e| format!("Can't run secret-tool: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Can't remove the key from the keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(true)
+    }
+}
+
+/// Windows Credential Manager, as generic credentials named
+/// `godspeed-cli:<profile>`
+#[cfg(windows)]
+mod credential_manager {
+    use std::ffi::c_void;
+    use std::io;
+    use std::ptr;
+
+    use super::{Keyring, SERVICE};
+
+    const CRED_TYPE_GENERIC: u32 = 1;
+    const CRED_PERSIST_LOCAL_MACHINE: u32 = 2;
+    const ERROR_NOT_FOUND: i32 = 1168;
+
+    #[repr(C)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    /// `CREDENTIALW`
+    #[repr(C)]
+    struct Credential {
+        flags: u32,
+        kind: u32,
+        target_name: *mut u16,
+        comment: *mut u16,
+        last_written: FileTime,
+        blob_size: u32,
+        blob: *mut u8,
+        persist: u32,
+        attribute_count: u32,
+        attributes: *mut c_void,
+        target_alias: *mut u16,
+        user_name: *mut u16,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn CredReadW(
+            target: *const u16,
+            kind: u32,
+            flags: u32,
+            credential: *mut *mut Credential,
+        ) -> i32;
+        fn CredWriteW(credential: *const Credential, flags: u32) -> i32;
+        fn CredDeleteW(target: *const u16, kind: u32, flags: u32) -> i32;
+        fn CredFree(buffer: *mut c_void);
+    }
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    fn target(profile: &str) -> Vec<u16> {
+        wide(&format!("{}:{}", SERVICE, profile))
+    }
+
+    pub struct CredentialManager;
+
+    impl Keyring for CredentialManager {
+        fn name(&self) -> &'static str {
+            "Windows Credential Manager"
+        }
+
+        fn get(&self, profile: &str) -> Result<Option<String>, String> {
+            let target = target(profile);
+            let mut credential = ptr::null_mut();
+            if unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &mut credential) } == 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() == Some(ERROR_NOT_FOUND) {
+                    return Ok(None);
+                }
+                return Err(format!(
+                    "Can't read the API key from Credential Manager: {}",
+                    e
+                ));
+            }
+            let key = unsafe {
+                let credential = &*credential;
+                if credential.blob.is_null() || credential.blob_size == 0 {
+                    String::new()
+                } else {
+                    let blob =
+                        std::slice::from_raw_parts(credential.blob, credential.blob_size as usize);
+                    String::from_utf8_lossy(blob).into_owned()
+                }
+            };
+            unsafe { CredFree(credential.cast()) };
+            Ok(Some(key).filter(|key| !key.is_empty()))
+        }
+
+        fn set(&self, profile: &str, key: &str) -> Result<(), String> {
+            let mut target = target(profile);
+            let mut user_name = wide(profile);
+            let mut blob = key.as_bytes().to_vec();
+            let credential = Credential {
+                flags: 0,
+                kind: CRED_TYPE_GENERIC,
+                target_name: target.as_mut_ptr(),
+                comment: ptr::null_mut(),
+                last_written: FileTime { low: 0, high: 0 },
+                blob_size: blob.len() as u32,
+                blob: blob.as_mut_ptr(),
+                persist: CRED_PERSIST_LOCAL_MACHINE,
+                attribute_count: 0,
+                attributes: ptr::null_mut(),
+                target_alias: ptr::null_mut(),
+                user_name: user_name.as_mut_ptr(),
+            };
+            if unsafe { CredWriteW(&credential, 0) } == 0 {
+                return Err(format!(
+                    "Can't save the key in Credential Manager: {}",
+                    io::Error::last_os_error()
+                ));
+            }
+            Ok(())
+        }
+
+        fn delete(&self, profile: &str) -> Result<bool, String> {
+            let target = target(profile);
+            if unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) } == 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() == Some(ERROR_NOT_FOUND) {
+                    return Ok(false);
+                }
+                return Err(format!(
+                    "Can't remove the key from Credential Manager: {}",
+                    e
+                ));
+            }
+            Ok(true)
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel;
+use crate::config::Config;
+use crate::credentials;
+use crate::dotenv;
+use crate::get_data_dir;
+use crate::online;
+use crate::profile;
+use crate::write_atomic;
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// How often `daemon` sends the queue while online
+    pub interval_secs: u64,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        DaemonConfig { interval_secs: 300 }
+    }
+}
+
+/// How often the network is checked, so a reconnect is noticed quickly
+const CHECK_EVERY: Duration = Duration::from_secs(5);
+
+const LABEL: &str = "com.godspeed-cli.daemon";
+
+/// Call `flush` every interval while there's a network, and straight away
+/// when the network comes back, until Ctrl-C or the service manager stops us.
+///
+/// Captures aren't handed to a running daemon; each stays its own process.
+/// It needs the create's answer to print the new task's id and link, and
+/// to report a list or label that doesn't resolve, and it already returns
+/// as soon as a task is sent or queued. The two share the queue through
+/// `queue.lock` and `flush.lock`, so they never send the same entry twice.
+pub fn run(config: &DaemonConfig, mut flush: impl FnMut()) {
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let mut was_online = false;
+    let mut last_flush: Option<Instant> = None;
+    while !cancel::is_cancelled() {
+        let online = online::has_network();
+        let due = last_flush.is_none_or(|at| at.elapsed() >= interval);
+        if online && (due || !was_online) {
+            flush();
+            last_flush = Some(Instant::now());
+        }
+        was_online = online;
+        thread::sleep(CHECK_EVERY);
+    }
+}
+
+fn get_plist_path() -> Result<PathBuf, String> {
+    let home = env::var("HOME").map_err(|_| "HOME is not set")?;
+    Ok(PathBuf::from(home)
+        .join("Library")
+        .join("LaunchAgents")
+        .join(format!("{}.plist", LABEL)))
+}
+
+/// Set for launchd's agents with `launchctl setenv`, rather than only this shell
+fn in_launchd_env(name: &str) -> bool {
+    Command::new("launchctl")
+        .args(["getenv", name])
+        .output()
+        .is_ok_and(|output| !String::from_utf8_lossy(&output.stdout).trim().is_empty())
+}
+
+/// Whether the agent, which starts without the shell's environment, can get
+/// the active profile's key: from the config, `api_key_cmd` or the keyring,
+/// or a variable launchd or the data directory's `.env` sets
+fn agent_has_key(config: &Config) -> bool {
+    let name = profile::active();
+    let profile = config.profiles.get(name);
+    let variable_reaches = |variable: &str| {
+        in_launchd_env(variable) || (config.dotenv.enabled && dotenv::data_dir_sets(variable))
+    };
+    profile.is_some_and(|profile| {
+        profile.api_key.is_some()
+            || profile.api_key_cmd.is_some()
+            || profile.api_key_env.as_deref().is_some_and(variable_reaches)
+    }) || (name == profile::DEFAULT && variable_reaches("GODSPEED_API"))
+        || credentials::lookup(name).is_some()
+}
+
+/// Register `daemon` as a launchd agent that starts at login, returning the
+/// agent's plist path. It's refused when the key is only in this shell's
+/// environment, since the agent would never see it.
+pub fn install(config: &Config) -> Result<PathBuf, String> {
+    if !cfg!(target_os = "macos") {
+        return Err(
+            "daemon install sets up a launchd agent, which needs macOS; run `godspeed-cli daemon` from your own service manager instead".into(),
+        );
+    }
+    if !agent_has_key(config) {
+        return Err(format!(
+            "The agent starts without this shell's environment, so it would have no API key for profile {}. Save it with `godspeed-cli auth login`, give the profile an api_key or api_key_cmd in the config, or use `launchctl setenv`.",
+            profile::active()
+        ));
+    }
+    let exe = env::current_exe().map_err(|e| format!("Can't find this program: {}", e))?;
+    let path = get_plist_path()?;
+    // The agent starts without the shell's environment, so keep the profile
+    let environment = match profile::selected() {
+        Some(profile) => format!(
+            "    <key>EnvironmentVariables</key>\n    <dict>\n        <key>GODSPEED_PROFILE</key>\n        <string>{}</string>\n    </dict>\n",
+            xml_escape(&profile)
+        ),
+        None => String::new(),
+    };
+    let plist = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>daemon</string>
+    </array>
+{}    <key>StandardErrorPath</key>
+    <string>{}</string>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>Crashed</key>
+        <true/>
+    </dict>
+</dict>
+</plist>
+"#,
+        LABEL,
+        xml_escape(&exe.to_string_lossy()),
+        environment,
+        xml_escape(&get_data_dir().join("daemon.log").to_string_lossy())
+    );
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    // Replacing an installed agent: unload the old one first
+    if path.exists() {
+        let _ = Command::new("launchctl").arg("unload").arg(&path).output();
+    }
+    write_atomic(&path, plist).map_err(|e| e.to_string())?;
+    let status = Command::new("launchctl")
+        .args(["load", "-w"])
+        .arg(&path)
+        .status()
+        .map_err(|e| format!("Failed to run launchctl: {}", e))?;
+    if !status.success() {
+        return Err(format!("launchctl load exited with {}", status));
+    }
+    Ok(path)
+}
+
+/// Stop and remove the launchd agent
+pub fn uninstall() -> Result<PathBuf, String> {
+    let path = get_plist_path()?;
+    if !path.exists() {
+        return Err("The daemon isn't installed".into());
+    }
+    let _ = Command::new("launchctl")
+        .args(["unload", "-w"])
+        .arg(&path)
+        .output();
+    fs::remove_file(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,604 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+const WEEKDAYS: [&str; 7] = [
+    "sunday",
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+];
+
+impl Date {
+    /// Days since 1970-01-01 (proleptic Gregorian calendar)
+    pub fn to_days(self) -> i64 {
+        let y = if self.month <= 2 {
+            self.year as i64 - 1
+        } else {
+            self.year as i64
+        };
+        let era = if y >= 0 { y } else { y - 399 } / 400;
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719468;
+        let era = if z >= 0 { z } else { z - 146096 } / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+
+    pub fn add_days(self, days: i64) -> Date {
+        Date::from_days(self.to_days() + days)
+    }
+
+    /// 0 = Sunday .. 6 = Saturday
+    pub fn weekday(self) -> u32 {
+        (self.to_days() + 4).rem_euclid(7) as u32
+    }
+
+    /// Capitalized weekday name, such as `Monday`
+    pub fn weekday_name(self) -> String {
+        let name = WEEKDAYS[self.weekday() as usize];
+        name[..1].to_uppercase() + &name[1..]
+    }
+}
+
+/// Seconds the local timezone is ahead of UTC at the given unix time
+#[cfg(unix)]
+pub fn local_offset_seconds(unix_secs: i64) -> i64 {
+    let time = unix_secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::localtime_r(&time, &mut tm) };
+    if result.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff as i64
+    }
+}
+
+/// The same from the system timezone's rules, so it's right for times on
+/// the other side of a daylight saving change
+#[cfg(windows)]
+pub fn local_offset_seconds(unix_secs: i64) -> i64 {
+    timezone::offset_seconds(unix_secs).unwrap_or(0)
+}
+
+/// Other platforms are taken to run on UTC
+#[cfg(not(any(unix, windows)))]
+pub fn local_offset_seconds(_unix_secs: i64) -> i64 {
+    0
+}
+
+#[cfg(windows)]
+mod timezone {
+    use std::ptr;
+
+    use super::Date;
+
+    /// `SYSTEMTIME`
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemTime {
+        year: u16,
+        month: u16,
+        weekday: u16,
+        day: u16,
+        hour: u16,
+        minute: u16,
+        second: u16,
+        milliseconds: u16,
+    }
+
+    impl SystemTime {
+        fn from_unix(unix_secs: i64) -> Self {
+            let date = Date::from_days(unix_secs.div_euclid(86400));
+            let secs = unix_secs.rem_euclid(86400);
+            SystemTime {
+                year: date.year as u16,
+                month: date.month as u16,
+                weekday: date.weekday() as u16,
+                day: date.day as u16,
+                hour: (secs / 3600) as u16,
+                minute: (secs % 3600 / 60) as u16,
+                second: (secs % 60) as u16,
+                milliseconds: 0,
+            }
+        }
+
+        fn to_unix(&self) -> i64 {
+            let date = Date {
+                year: self.year as i32,
+                month: self.month as u32,
+                day: self.day as u32,
+            };
+            date.to_days() * 86400
+                + self.hour as i64 * 3600
+                + self.minute as i64 * 60
+                + self.second as i64
+        }
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        /// With no timezone given, converts to the system's own
+        fn SystemTimeToTzSpecificLocalTime(
+            timezone: *const std::ffi::c_void,
+            universal: *const SystemTime,
+            local: *mut SystemTime,
+        ) -> i32;
+    }
+
+    pub fn offset_seconds(unix_secs: i64) -> Option<i64> {
+        // Windows has no times before 1601
+        const YEAR_1601: i64 = -11_644_473_600;
+        if unix_secs < YEAR_1601 {
+            return None;
+        }
+        let universal = SystemTime::from_unix(unix_secs);
+        let mut local = SystemTime::default();
+        if unsafe { SystemTimeToTzSpecificLocalTime(ptr::null(), &universal, &mut local) } == 0 {
+            return None;
+        }
+        Some(local.to_unix() - universal.to_unix())
+    }
+}
+
+pub fn now_unix() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// A unix time as an RFC 3339 UTC timestamp, such as `2024-06-03T09:30:00Z`
+pub fn format_utc(unix_secs: i64) -> String {
+    let date = Date::from_days(unix_secs.div_euclid(86400));
+    let secs = unix_secs.rem_euclid(86400);
+    format!(
+        "{}T{:02}:{:02}:{:02}Z",
+        date,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Parse an RFC 3339 UTC timestamp written by `format_utc` back to unix time
+pub fn parse_utc(s: &str) -> Option<i64> {
+    let (date, time) = s.strip_suffix('Z')?.split_once('T')?;
+    let date = parse_iso(date)?;
+    Some(date.to_days() * 86400 + parse_time(time)?)
+}
+
+/// Seconds into the day of a time such as `08:49:37`
+fn parse_time(time: &str) -> Option<i64> {
+    let mut parts = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (parts.next()??, parts.next()??, parts.next()??);
+    // 60 seconds is a leap second
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) || !(0..=60).contains(&seconds) {
+        return None;
+    }
+    Some(hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Parse an HTTP date, such as `Sun, 06 Nov 1994 08:49:37 GMT`, to unix time
+pub fn parse_http_date(s: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|m| month.eq_ignore_ascii_case(m))? as u32 + 1;
+    let date = Date {
+        year: year.parse().ok()?,
+        month,
+        day: day.parse().ok()?,
+    };
+    if Date::from_days(date.to_days()) != date {
+        return None;
+    }
+    Some(date.to_days() * 86400 + parse_time(time)?)
+}
+
+/// A unix time in the local timezone, such as `2024-06-03 09:30`
+pub fn format_local(unix_secs: i64) -> String {
+    let local = unix_secs + local_offset_seconds(unix_secs);
+    let date = Date::from_days(local.div_euclid(86400));
+    let secs = local.rem_euclid(86400);
+    format!("{} {:02}:{:02}", date, secs / 3600, secs % 3600 / 60)
+}
+
+/// Today's date in the local timezone
+pub fn today() -> Date {
+    let now = now_unix();
+    Date::from_days((now + local_offset_seconds(now)).div_euclid(86400))
+}
+
+/// Index of a weekday name or its abbreviation (0 = Sunday)
+pub fn parse_weekday(s: &str) -> Option<u32> {
+    let s = s.to_lowercase();
+    if s.len() < 2 {
+        return None;
+    }
+    WEEKDAYS
+        .iter()
+        .position(|name| name.starts_with(&s))
+        .map(|i| i as u32)
+}
+
+fn parse_iso(s: &str) -> Option<Date> {
+    let mut parts = s.splitn(3, '-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = parts.next()?.parse::<u32>().ok()?;
+    let day = parts.next()?.parse::<u32>().ok()?;
+    let date = Date { year, month, day };
+    // Reject dates like 2024-02-31 that don't survive a round trip
+    if (1..=12).contains(&month) && Date::from_days(date.to_days()) == date {
+        Some(date)
+    } else {
+        None
+    }
+}
+
+/// Parse a date token such as `today`, `tomorrow`, `fri`, `+3d`, `+2w` or `2024-06-01`
+pub fn parse_date(s: &str, today: Date) -> Option<Date> {
+    let lower = s.to_lowercase();
+    match lower.as_str() {
+        "today" | "tod" => return Some(today),
+        "tomorrow" | "tom" => return Some(today.add_days(1)),
+        _ => {}
+    }
+
+    if let Some(offset) = lower.strip_prefix('+') {
+        let (number, unit) = match offset.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+            Some((i, _)) => offset.split_at(i),
+            None => (offset, "d"),
+        };
+        let count = number.parse::<i64>().ok()?;
+        return match unit {
+            "d" => Some(today.add_days(count)),
+            "w" => Some(today.add_days(count * 7)),
+            _ => None,
+        };
+    }
+
+    if let Some(weekday) = parse_weekday(&lower) {
+        // The next occurrence, counting today
+        let ahead = (weekday + 7 - today.weekday()) % 7;
+        return Some(today.add_days(ahead as i64));
+    }
+
+    parse_iso(&lower)
+}
+
+const RRULE_DAYS: [&str; 7] = ["SU", "MO", "TU", "WE", "TH", "FR", "SA"];
+
+/// Translate a repeat token (without its `*`) such as `daily`, `weekdays` or
+/// `monthly:15` into an RFC 5545 recurrence rule
+pub fn parse_recurrence(spec: &str) -> Option<String> {
+    let lower = spec.to_lowercase();
+    let (freq, arg) = match lower.split_once(':') {
+        Some((freq, arg)) => (freq, Some(arg)),
+        None => (lower.as_str(), None),
+    };
+
+    match (freq, arg) {
+        ("daily", None) => Some("FREQ=DAILY".to_string()),
+        ("weekly", None) => Some("FREQ=WEEKLY".to_string()),
+        ("weekly", Some(days)) => parse_every(days),
+        ("weekdays", None) => Some("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".to_string()),
+        ("monthly", None) => Some("FREQ=MONTHLY".to_string()),
+        ("monthly", Some(day)) => {
+            let day = day.parse::<u32>().ok().filter(|d| (1..=31).contains(d))?;
+            Some(format!("FREQ=MONTHLY;BYMONTHDAY={}", day))
+        }
+        ("yearly" | "annually", None) => Some("FREQ=YEARLY".to_string()),
+        _ => None,
+    }
+}
+
+/// Translate the argument of `*every`: a weekday list (`mon,wed`) or an
+/// interval such as `2d`, `3w` or `6m`
+pub fn parse_every(spec: &str) -> Option<String> {
+    let lower = spec.to_lowercase();
+
+    if let Some((count, unit)) = lower
+        .char_indices()
+        .find(|(_, c)| !c.is_ascii_digit())
+        .map(|(i, _)| lower.split_at(i))
+        .filter(|(count, _)| !count.is_empty())
+    {
+        let interval = count.parse::<u32>().ok().filter(|n| *n > 0)?;
+        let freq = match unit {
+            "d" => "DAILY",
+            "w" => "WEEKLY",
+            "m" => "MONTHLY",
+            "y" => "YEARLY",
+            _ => return None,
+        };
+        return Some(format!("FREQ={};INTERVAL={}", freq, interval));
+    }
+
+    let days = lower
+        .split(',')
+        .map(|day| parse_weekday(day).map(|i| RRULE_DAYS[i as usize]))
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("FREQ=WEEKLY;BYDAY={}", days.join(",")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    /// A Monday
+    const TODAY: Date = Date {
+        year: 2024,
+        month: 6,
+        day: 3,
+    };
+
+    #[test]
+    fn days_round_trip() {
+        assert_eq!(date(1970, 1, 1).to_days(), 0);
+        for day in [-719_468, -1, 0, 59, 19_782, 2_932_896] {
+            assert_eq!(Date::from_days(day).to_days(), day);
+        }
+        assert_eq!(Date::from_days(19_877), TODAY);
+    }
+
+    #[test]
+    fn weekday_of_a_date() {
+        assert_eq!(date(1970, 1, 1).weekday(), 4);
+        assert_eq!(TODAY.weekday(), 1);
+        assert_eq!(TODAY.weekday_name(), "Monday");
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(parse_iso("2024-06-01"), Some(date(2024, 6, 1)));
+        assert_eq!(parse_iso("2024-2-29"), Some(date(2024, 2, 29)));
+    }
+
+    #[test]
+    fn iso_rejects_impossible_dates() {
+        assert_eq!(parse_iso("2023-02-29"), None);
+        assert_eq!(parse_iso("2024-02-31"), None);
+        assert_eq!(parse_iso("2024-13-01"), None);
+        assert_eq!(parse_iso("2024-00-10"), None);
+        assert_eq!(parse_iso("2024-06"), None);
+        assert_eq!(parse_iso("june 1"), None);
+    }
+
+    #[test]
+    fn weekday_names_and_abbreviations() {
+        assert_eq!(parse_weekday("sunday"), Some(0));
+        assert_eq!(parse_weekday("Fri"), Some(5));
+        assert_eq!(parse_weekday("tu"), Some(2));
+        assert_eq!(parse_weekday("th"), Some(4));
+    }
+
+    #[test]
+    fn weekday_rejects_short_or_unknown_names() {
+        assert_eq!(parse_weekday("t"), None);
+        assert_eq!(parse_weekday(""), None);
+        assert_eq!(parse_weekday("fridays"), None);
+        assert_eq!(parse_weekday("someday"), None);
+    }
+
+    #[test]
+    fn relative_keywords() {
+        assert_eq!(parse_date("today", TODAY), Some(TODAY));
+        assert_eq!(parse_date("TOD", TODAY), Some(TODAY));
+        assert_eq!(parse_date("tomorrow", TODAY), Some(date(2024, 6, 4)));
+        assert_eq!(parse_date("tom", TODAY), Some(date(2024, 6, 4)));
+    }
+
+    #[test]
+    fn relative_offsets() {
+        assert_eq!(parse_date("+3", TODAY), Some(date(2024, 6, 6)));
+        assert_eq!(parse_date("+3d", TODAY), Some(date(2024, 6, 6)));
+        assert_eq!(parse_date("+2w", TODAY), Some(date(2024, 6, 17)));
+        assert_eq!(parse_date("+30d", TODAY), Some(date(2024, 7, 3)));
+        assert_eq!(parse_date("+3m", TODAY), None);
+        assert_eq!(parse_date("+d", TODAY), None);
+    }
+
+    #[test]
+    fn weekday_means_the_next_one_counting_today() {
+        assert_eq!(parse_date("mon", TODAY), Some(TODAY));
+        assert_eq!(parse_date("fri", TODAY), Some(date(2024, 6, 7)));
+        assert_eq!(parse_date("sunday", TODAY), Some(date(2024, 6, 9)));
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(parse_date("2024-12-25", TODAY), Some(date(2024, 12, 25)));
+        assert_eq!(parse_date("someday", TODAY), None);
+    }
+
+    #[test]
+    fn utc_timestamps() {
+        assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_utc(1_717_407_000), "2024-06-03T09:30:00Z");
+        assert_eq!(format_utc(-1), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn utc_round_trip() {
+        for secs in [0, 1, 59, 86_399, 951_782_400, 1_717_407_000, -86_401] {
+            assert_eq!(parse_utc(&format_utc(secs)), Some(secs));
+        }
+    }
+
+    fn rule(rule: &str) -> Option<String> {
+        Some(rule.to_string())
+    }
+
+    #[test]
+    fn recurrence_frequencies() {
+        assert_eq!(parse_recurrence("daily"), rule("FREQ=DAILY"));
+        assert_eq!(parse_recurrence("Weekly"), rule("FREQ=WEEKLY"));
+        assert_eq!(parse_recurrence("monthly"), rule("FREQ=MONTHLY"));
+        assert_eq!(parse_recurrence("yearly"), rule("FREQ=YEARLY"));
+        assert_eq!(parse_recurrence("annually"), rule("FREQ=YEARLY"));
+    }
+
+    #[test]
+    fn recurrence_on_weekdays() {
+        assert_eq!(
+            parse_recurrence("weekdays"),
+            rule("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR")
+        );
+        assert_eq!(
+            parse_recurrence("weekly:mon,thu"),
+            rule("FREQ=WEEKLY;BYDAY=MO,TH")
+        );
+    }
+
+    #[test]
+    fn recurrence_on_a_day_of_the_month() {
+        assert_eq!(
+            parse_recurrence("monthly:15"),
+            rule("FREQ=MONTHLY;BYMONTHDAY=15")
+        );
+        assert_eq!(
+            parse_recurrence("monthly:31"),
+            rule("FREQ=MONTHLY;BYMONTHDAY=31")
+        );
+    }
+
+    #[test]
+    fn recurrence_rejects_malformed_specs() {
+        assert_eq!(parse_recurrence("hourly"), None);
+        assert_eq!(parse_recurrence("daily:2"), None);
+        assert_eq!(parse_recurrence("weekdays:mon"), None);
+        assert_eq!(parse_recurrence("monthly:0"), None);
+        assert_eq!(parse_recurrence("monthly:32"), None);
+        assert_eq!(parse_recurrence("monthly:mid"), None);
+        assert_eq!(parse_recurrence("weekly:funday"), None);
+        assert_eq!(parse_recurrence(""), None);
+    }
+
+    #[test]
+    fn every_interval() {
+        assert_eq!(parse_every("2d"), rule("FREQ=DAILY;INTERVAL=2"));
+        assert_eq!(parse_every("3W"), rule("FREQ=WEEKLY;INTERVAL=3"));
+        assert_eq!(parse_every("6m"), rule("FREQ=MONTHLY;INTERVAL=6"));
+        assert_eq!(parse_every("1y"), rule("FREQ=YEARLY;INTERVAL=1"));
+    }
+
+    #[test]
+    fn every_weekday_list() {
+        assert_eq!(parse_every("fri"), rule("FREQ=WEEKLY;BYDAY=FR"));
+        assert_eq!(
+            parse_every("mon,wed,Sunday"),
+            rule("FREQ=WEEKLY;BYDAY=MO,WE,SU")
+        );
+    }
+
+    #[test]
+    fn every_rejects_malformed_specs() {
+        assert_eq!(parse_every("0d"), None);
+        assert_eq!(parse_every("2x"), None);
+        assert_eq!(parse_every("2dd"), None);
+        assert_eq!(parse_every("d"), None);
+        assert_eq!(parse_every("mon,"), None);
+        assert_eq!(parse_every("mon,someday"), None);
+        assert_eq!(parse_every(""), None);
+    }
+
+    #[test]
+    fn utc_rejects_other_forms() {
+        assert_eq!(parse_utc("2024-06-03T09:30:00"), None);
+        assert_eq!(parse_utc("2024-06-03 09:30:00Z"), None);
+        assert_eq!(parse_utc("2024-06-03T09:30Z"), None);
+        assert_eq!(parse_utc("2024-06-03T24:00:00Z"), None);
+        assert_eq!(parse_utc("2024-06-03T09:60:00Z"), None);
+        assert_eq!(parse_utc("2024-02-30T09:30:00Z"), None);
+        assert_eq!(parse_utc(""), None);
+    }
+
+    #[test]
+    fn http_dates() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+        assert_eq!(
+            parse_http_date("Mon, 03 JUN 2024 09:30:00 GMT"),
+            Some(1_717_407_000)
+        );
+        // The weekday isn't checked, as servers get it wrong
+        assert_eq!(
+            parse_http_date("Fri, 03 Jun 2024 09:30:00 GMT"),
+            Some(1_717_407_000)
+        );
+    }
+
+    #[test]
+    fn http_date_matches_utc() {
+        for secs in [0, 784_111_777, 1_717_407_000] {
+            let utc = format_utc(secs);
+            let (date, time) = utc.trim_end_matches('Z').split_once('T').unwrap();
+            let date = parse_iso(date).unwrap();
+            const MONTHS: [&str; 12] = [
+                "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+            ];
+            let http = format!(
+                "{}, {:02} {} {} {} GMT",
+                &date.weekday_name()[..3],
+                date.day,
+                MONTHS[date.month as usize - 1],
+                date.year,
+                time
+            );
+            assert_eq!(parse_http_date(&http), Some(secs), "{}", http);
+        }
+    }
+
+    #[test]
+    fn http_date_rejects_other_forms() {
+        // RFC 850 and asctime dates, which HTTP/1.1 no longer sends
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun Nov  6 08:49:37 1994"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nix 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 31 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(parse_http_date("120"), None);
+        assert_eq!(parse_http_date(""), None);
+    }
+}
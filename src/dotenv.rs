@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::profile;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DotenvConfig {
+    /// Read the API key and profile from a `.env` file in the current
+    /// directory or the data directory
+    pub enabled: bool,
+}
+
+/// Taken from a `.env`, along with each profile's `api_key_env`. Nothing else
+/// is, `GODSPEED_API_URL` included, so a `.env` in someone else's project
+/// can't send your key elsewhere.
+const VARIABLES: [&str; 2] = ["GODSPEED_API", "GODSPEED_PROFILE"];
+
+/// Set the variables the environment doesn't already have from `.env`
+/// files: the current directory's first, then the one in the data
+/// directory. Call it before anything reads them.
+pub fn load(config: &Config) {
+    if !config.dotenv.enabled {
+        return;
+    }
+    let key_vars: Vec<&str> = config
+        .profiles
+        .values()
+        .filter_map(|profile| profile.api_key_env.as_deref())
+        .collect();
+    // The shared data directory, since the profile may come from the file
+    for path in [PathBuf::from(".env"), profile::get_base_dir().join(".env")] {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                eprintln!("Warning: failed to read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for (name, value) in parse(&content) {
+            let wanted = VARIABLES.contains(&name) || key_vars.contains(&name);
+            if wanted && env::var_os(name).is_none_or(|set| set.is_empty()) {
+                env::set_var(name, value);
+            }
+        }
+    }
+}
+
+/// Whether the data directory's `.env` gives `name` a value, which a process
+/// started without the shell's environment still reads
+pub fn data_dir_sets(name: &str) -> bool {
+    fs::read_to_string(profile::get_base_dir().join(".env")).is_ok_and(|content| {
+        parse(&content)
+            .iter()
+            .any(|(found, value)| *found == name && !value.is_empty())
+    })
+}
+
+/// `NAME=value` lines, allowing `export`, quotes and `#` comments
+fn parse(content: &str) -> Vec<(&str, String)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+                _ => value.split(" #").next().unwrap_or_default().trim_end(),
+            };
+            Some((name.trim(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_are_read() {
+        let content = "GODSPEED_API=abc\nexport GODSPEED_PROFILE=work\n  SPACED = value  \n";
+        assert_eq!(
+            parse(content),
+            [
+                ("GODSPEED_API", "abc".to_string()),
+                ("GODSPEED_PROFILE", "work".to_string()),
+                ("SPACED", "value".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_and_comments() {
+        let content =
+            "# a comment\n\nA=\"quoted # kept\" trailing\nB='single'\nC=plain # dropped\nD=a#b\n";
+        assert_eq!(
+            parse(content),
+            [
+                ("A", "quoted # kept".to_string()),
+                ("B", "single".to_string()),
+                ("C", "plain".to_string()),
+                ("D", "a#b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_without_an_assignment_are_skipped() {
+        assert_eq!(parse("not an assignment\nE=\n"), [("E", String::new())]);
+    }
+}
@@ -0,0 +1,305 @@
+use serde_json::Value;
+use std::fmt;
+use std::io;
+
+use reqwest::StatusCode;
+
+/// Why talking to the API failed. The kind decides whether a task is queued
+/// for a retry, dead-lettered or reported, and the exit status.
+#[derive(Debug)]
+pub enum GodspeedError {
+    /// The API key was refused (401 or 403); retrying works once it's fixed
+    Auth(ApiError),
+    /// No connection, or it dropped or timed out
+    Network(reqwest::Error),
+    /// 429; the queue waits for `Retry-After`
+    RateLimited(ApiError),
+    /// The server refused the request as it stands, naming the first field
+    /// it objected to when it said
+    Validation {
+        field: Option<String>,
+        error: ApiError,
+    },
+    /// 404; a wrong `base_url` answers this to everything, so it's retried
+    NotFound(ApiError),
+    /// 5xx, or a 408 timeout
+    Server(ApiError),
+    /// A response that couldn't be read
+    Parse(String),
+    Io(io::Error),
+}
+
+impl GodspeedError {
+    pub fn from_response(response: reqwest::blocking::Response) -> Self {
+        Self::from_api_error(ApiError::from_response(response))
+    }
+
+    fn from_api_error(error: ApiError) -> Self {
+        match error.status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => GodspeedError::Auth(error),
+            StatusCode::TOO_MANY_REQUESTS => GodspeedError::RateLimited(error),
+            StatusCode::NOT_FOUND => GodspeedError::NotFound(error),
+            status if status.is_client_error() && status != StatusCode::REQUEST_TIMEOUT => {
+                let field = error
+                    .fields
+                    .first()
+                    .and_then(|f| f.split_once(": "))
+                    .map(|(field, _)| field.to_string());
+                GodspeedError::Validation { field, error }
+            }
+            _ => GodspeedError::Server(error),
+        }
+    }
+
+    /// Sending the same request again can't work, so the task shouldn't be
+    /// retried. A 404 isn't one: it's as likely to mean the URL is wrong as
+    /// the task, and dead-lettering everything for a typo would lose the queue.
+    pub fn is_rejection(&self) -> bool {
+        matches!(self, GodspeedError::Validation { .. })
+    }
+
+    /// The field a validation error is about, if the server named one
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            GodspeedError::Validation { field, .. } => field.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// A stable code for `--json` output
+    pub fn code(&self) -> &'static str {
+        match self {
+            GodspeedError::Auth(_) => "auth",
+            GodspeedError::Network(_) => "network",
+            GodspeedError::RateLimited(_) => "rate_limited",
+            GodspeedError::Validation { .. } => "validation",
+            GodspeedError::NotFound(_) => "not_found",
+            GodspeedError::Server(_) => "server",
+            GodspeedError::Parse(_) => "parse",
+            GodspeedError::Io(_) => "io",
+        }
+    }
+
+    /// The process exit status for a command that failed this way
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GodspeedError::Auth(_) => 3,
+            GodspeedError::Network(_) | GodspeedError::Server(_) => 4,
+            GodspeedError::RateLimited(_) => 5,
+            GodspeedError::Validation { .. } => 6,
+            GodspeedError::NotFound(_) => 7,
+            GodspeedError::Parse(_) => 8,
+            GodspeedError::Io(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for GodspeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GodspeedError::Auth(error) if error.status == StatusCode::FORBIDDEN => {
+                write!(
+                    f,
+                    "The API key isn't allowed to do that ({}); check the account it belongs to with whoami",
+                    error
+                )
+            }
+            // Distinct from a network failure: retrying won't help until the key is fixed
+            GodspeedError::Auth(error) => write!(
+                f,
+                "Invalid or expired API key ({}); check it, or save a new one with auth login",
+                error
+            ),
+            GodspeedError::Network(error) => {
+                write!(f, "The connection to the API failed: {}", error)
+            }
+            GodspeedError::RateLimited(error)
+            | GodspeedError::Validation { error, .. }
+            | GodspeedError::NotFound(error)
+            | GodspeedError::Server(error) => write!(f, "{}", error),
+            GodspeedError::Parse(message) => write!(f, "Unexpected response: {}", message),
+            GodspeedError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GodspeedError {}
+
+impl From<reqwest::Error> for GodspeedError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            GodspeedError::Parse(e.to_string())
+        } else {
+            GodspeedError::Network(e)
+        }
+    }
+}
+
+impl From<io::Error> for GodspeedError {
+    fn from(e: io::Error) -> Self {
+        GodspeedError::Io(e)
+    }
+}
+
+/// A response other than success, with the server's explanation if it gave one
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: Option<String>,
+    /// Problems with particular fields, such as `title: can't be blank`
+    pub fields: Vec<String>,
+}
+
+impl ApiError {
+    fn from_response(response: reqwest::blocking::Response) -> Self {
+        let status = response.status();
+        ApiError::from_body(status, &response.text().unwrap_or_default())
+    }
+
+    fn from_body(status: StatusCode, body: &str) -> Self {
+        let Ok(json) = serde_json::from_str::<Value>(body) else {
+            let text: String = body.trim().chars().take(200).collect();
+            return ApiError {
+                status,
+                message: Some(text).filter(|m| !m.is_empty()),
+                fields: Vec::new(),
+            };
+        };
+        let message = ["message", "error"]
+            .iter()
+            .find_map(|key| json.get(key).and_then(|m| m.as_str()))
+            .filter(|m| !m.is_empty())
+            .map(str::to_string);
+        ApiError {
+            status,
+            message,
+            fields: json.get("errors").map(field_errors).unwrap_or_default(),
+        }
+    }
+}
+
+/// The usual shapes of an `errors` value: `{"title": ["can't be blank"]}`,
+/// `[{"field": "title", "message": "..."}]` or a list of messages
+fn field_errors(errors: &Value) -> Vec<String> {
+    let text = |value: &Value| match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map_or_else(|| item.to_string(), str::to_string)
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    };
+    match errors {
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(field, problem)| format!("{}: {}", field, text(problem)))
+            .collect(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                let message = item.get("message").map_or_else(|| text(item), text);
+                match item.get("field").and_then(|f| f.as_str()) {
+                    Some(field) => format!("{}: {}", field, message),
+                    None => message,
+                }
+            })
+            .collect(),
+        other => vec![text(other)],
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API error: {}", self.status)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        if !self.fields.is_empty() {
+            write!(f, " ({})", self.fields.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn field_errors_from_an_object_of_lists() {
+        // What the mock API, like the real one, answers a 422 with
+        let errors = json!({ "list_id": ["not found"], "title": ["is too long", "has a tab"] });
+        assert_eq!(
+            field_errors(&errors),
+            ["list_id: not found", "title: is too long, has a tab"]
+        );
+    }
+
+    #[test]
+    fn field_errors_from_a_list_of_fields() {
+        let errors = json!([
+            { "field": "title", "message": "can't be blank" },
+            { "message": "too many labels" },
+        ]);
+        assert_eq!(
+            field_errors(&errors),
+            ["title: can't be blank", "too many labels"]
+        );
+    }
+
+    #[test]
+    fn field_errors_from_plain_messages() {
+        assert_eq!(field_errors(&json!(["bad date"])), ["bad date"]);
+        assert_eq!(field_errors(&json!("bad date")), ["bad date"]);
+        assert_eq!(field_errors(&json!({ "due": 3 })), ["due: 3"]);
+    }
+
+    #[test]
+    fn unprocessable_response_is_flattened() {
+        let body =
+            json!({ "message": "Validation failed", "errors": { "list_id": ["not found"] } });
+        let error = ApiError::from_body(StatusCode::UNPROCESSABLE_ENTITY, &body.to_string());
+        assert_eq!(error.message.as_deref(), Some("Validation failed"));
+        assert_eq!(error.fields, ["list_id: not found"]);
+        assert_eq!(
+            error.to_string(),
+            "API error: 422 Unprocessable Entity: Validation failed (list_id: not found)"
+        );
+    }
+
+    #[test]
+    fn body_that_is_not_json_is_the_message() {
+        let error = ApiError::from_body(StatusCode::BAD_GATEWAY, "  upstream timed out\n");
+        assert_eq!(error.message.as_deref(), Some("upstream timed out"));
+        assert!(error.fields.is_empty());
+        let error = ApiError::from_body(StatusCode::BAD_GATEWAY, "");
+        assert_eq!(error.message, None);
+    }
+
+    #[test]
+    fn only_validation_errors_are_rejections() {
+        let error =
+            |status, body: &str| GodspeedError::from_api_error(ApiError::from_body(status, body));
+        let invalid = error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            r#"{"errors":{"title":["is too long"]}}"#,
+        );
+        assert!(invalid.is_rejection());
+        assert_eq!(invalid.field(), Some("title"));
+        for status in [
+            StatusCode::NOT_FOUND,
+            StatusCode::UNAUTHORIZED,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::REQUEST_TIMEOUT,
+            StatusCode::BAD_GATEWAY,
+        ] {
+            assert!(!error(status, "").is_rejection(), "{}", status);
+        }
+        assert_eq!(error(StatusCode::NOT_FOUND, "").code(), "not_found");
+    }
+}
@@ -0,0 +1,64 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelSource {
+    #[default]
+    Off,
+    Repo,
+    Branch,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Label captures made inside a git repo by the repo's name or the current branch
+    pub label: LabelSource,
+    /// Repo or branch name → label, for names that don't match a label directly
+    pub labels: HashMap<String, String>,
+}
+
+/// The working tree containing the current directory
+fn repo_root() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// The git directory, following the `gitdir:` file used by worktrees and submodules
+fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let dir = content.strip_prefix("gitdir:")?.trim();
+    Some(root.join(dir))
+}
+
+fn branch(root: &Path) -> Option<String> {
+    let head = fs::read_to_string(git_dir(root)?.join("HEAD")).ok()?;
+    // A detached HEAD holds a commit hash, which makes a poor label
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(str::to_string)
+}
+
+/// The label for a capture made in the current directory, if enabled
+pub fn label(config: &GitConfig) -> Option<String> {
+    if config.label == LabelSource::Off {
+        return None;
+    }
+    let root = repo_root()?;
+    let name = match config.label {
+        LabelSource::Repo => root.file_name()?.to_str()?.to_string(),
+        LabelSource::Branch => branch(&root)?,
+        LabelSource::Off => return None,
+    };
+    Some(config.labels.get(&name).cloned().unwrap_or(name))
+}
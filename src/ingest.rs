@@ -0,0 +1,133 @@
+use std::io::{self, Read};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+#[derive(Debug, PartialEq)]
+pub struct Decoded {
+    pub text: String,
+    /// True when invalid bytes were replaced with U+FFFD
+    pub lossy: bool,
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Decoded {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|pair| from_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+        .collect();
+    let lossy = !bytes.len().is_multiple_of(2)
+        || char::decode_utf16(units.iter().copied()).any(|c| c.is_err());
+    Decoded {
+        text: String::from_utf16_lossy(&units),
+        lossy,
+    }
+}
+
+/// Normalize CRLF and lone CR line endings to LF
+pub fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Decode raw input bytes into text, handling BOMs, UTF-16 and Windows line endings.
+/// Invalid UTF-8 is replaced rather than rejected, with `lossy` set so callers can warn.
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let decoded = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        decode_utf8(rest)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16_LE_BOM) {
+        decode_utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16_BE_BOM) {
+        decode_utf16(rest, u16::from_be_bytes)
+    } else {
+        decode_utf8(bytes)
+    };
+
+    Decoded {
+        text: normalize_newlines(&decoded.text),
+        lossy: decoded.lossy,
+    }
+}
+
+fn decode_utf8(bytes: &[u8]) -> Decoded {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Decoded {
+            text: text.to_string(),
+            lossy: false,
+        },
+        Err(_) => Decoded {
+            text: String::from_utf8_lossy(bytes).into_owned(),
+            lossy: true,
+        },
+    }
+}
+
+fn decode_with_warning(bytes: &[u8], source: &str) -> String {
+    let decoded = decode(bytes);
+    if decoded.lossy {
+        eprintln!(
+            "Warning: {} is not valid UTF-8; invalid characters were replaced",
+            source
+        );
+    }
+    decoded.text
+}
+
+/// Read all of stdin, warning on stderr if it had to be decoded lossily
+pub fn read_stdin() -> io::Result<String> {
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+    Ok(decode_with_warning(&bytes, "stdin"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_utf8_is_unchanged() {
+        let decoded = decode("Buy milk @Errands".as_bytes());
+        assert_eq!(decoded.text, "Buy milk @Errands");
+        assert!(!decoded.lossy);
+    }
+
+    #[test]
+    fn utf8_bom_is_stripped() {
+        let decoded = decode(b"\xEF\xBB\xBFBuy milk");
+        assert_eq!(decoded.text, "Buy milk");
+        assert!(!decoded.lossy);
+    }
+
+    #[test]
+    fn crlf_and_cr_become_lf() {
+        let decoded = decode(b"first\r\nsecond\rthird\n");
+        assert_eq!(decoded.text, "first\nsecond\nthird\n");
+    }
+
+    #[test]
+    fn utf16_le_with_bom_is_decoded() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "Café\r\nTea".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        let decoded = decode(&bytes);
+        assert_eq!(decoded.text, "Café\nTea");
+        assert!(!decoded.lossy);
+    }
+
+    #[test]
+    fn utf16_be_with_bom_is_decoded() {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in "Tea".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        assert_eq!(decode(&bytes).text, "Tea");
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_and_flagged() {
+        // "Caf\xE9" is Latin-1, not UTF-8
+        let decoded = decode(b"Caf\xE9 order\r\n");
+        assert_eq!(decoded.text, "Caf\u{FFFD} order\n");
+        assert!(decoded.lossy);
+    }
+}
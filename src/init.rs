@@ -0,0 +1,278 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::config;
+use crate::credentials::{self, Keyring};
+use crate::error::GodspeedError;
+use crate::{mock, picker, profile, term};
+use crate::{Api, Resolver};
+
+/// Keys asked for before giving up
+const ATTEMPTS: usize = 3;
+
+/// `init`: ask for the API key and check it against the API, keep it, fill
+/// the name caches and offer a default list. `connect` makes a client for a
+/// key with the run's URL, proxy and certificates.
+pub fn run(connect: impl Fn(String) -> Api) -> Result<(), String> {
+    if mock::enabled() {
+        return Err("init sets up a real account; --mock needs no setup".into());
+    }
+    let mut tty = Tty::open().ok_or("init asks questions, so it needs a terminal")?;
+    let profile = profile::active();
+    if profile == profile::DEFAULT {
+        tty.say("Setting up godspeed-cli.")?;
+    } else {
+        tty.say(&format!("Setting up godspeed-cli for profile {}.", profile))?;
+    }
+
+    let (key, connected) = ask_key(&mut tty, profile, connect)?;
+
+    store_key(&mut tty, profile, &key)?;
+
+    if let Some((api, mut resolver)) = connected {
+        // Labels and teammates may not exist on every account; the lists did
+        let mut counts = vec![format!("{} lists", cached(&resolver.lists.entries))];
+        for cache in [&mut resolver.labels, &mut resolver.members] {
+            match cache.refetch(&api) {
+                Ok(()) => counts.push(format!("{} {}s", cached(&cache.entries), cache.kind)),
+                Err(e) => tty.say(&format!("Couldn't fetch {}s: {}", cache.kind, e))?,
+            }
+        }
+        tty.say(&format!("Cached {}.", counts.join(", ")))?;
+        choose_default_list(&mut tty, &resolver)?;
+    }
+
+    tty.say("Done. Try it: godspeed-cli \"My first task\"")?;
+    Ok(())
+}
+
+/// A checked API key, and the client and caches used to check it when the
+/// API could be reached. Offers `GODSPEED_API` for the default profile, then
+/// asks until the API takes a key or the tries run out.
+pub fn ask_key(
+    tty: &mut Tty,
+    profile: &str,
+    connect: impl Fn(String) -> Api,
+) -> Result<(String, Option<(Api, Resolver)>), String> {
+    let existing = env::var("GODSPEED_API")
+        .ok()
+        .filter(|key| !key.is_empty() && profile == profile::DEFAULT);
+    let mut checked = None;
+    for attempt in 0..ATTEMPTS {
+        let key = match &existing {
+            Some(key)
+                if attempt == 0 && tty.confirm("GODSPEED_API is set. Use that key?", true)? =>
+            {
+                key.clone()
+            }
+            _ => tty.ask_hidden("API key (it isn't shown as you type): ")?,
+        };
+        if key.is_empty() {
+            return Err("No API key given".into());
+        }
+        let api = connect(key.clone());
+        let mut resolver = Resolver::new(config::load());
+        match resolver.lists.refetch(&api) {
+            Ok(()) => {
+                tty.say("The key works.")?;
+                checked = Some((key, Some((api, resolver))));
+                break;
+            }
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<GodspeedError>(),
+                    Some(GodspeedError::Auth(_))
+                ) =>
+            {
+                tty.say("The API refused that key; check it and try again.")?;
+            }
+            Err(e) => {
+                tty.say(&format!("Couldn't check the key: {}", e))?;
+                if tty.confirm("Keep it anyway?", false)? {
+                    checked = Some((key, None));
+                    break;
+                }
+                return Err("Setup stopped; nothing was saved".into());
+            }
+        }
+    }
+    checked.ok_or_else(|| "No working API key; nothing was saved".to_string())
+}
+
+fn cached(entries: &Option<std::collections::HashMap<String, String>>) -> usize {
+    entries.as_ref().map_or(0, |entries| entries.len())
+}
+
+/// Where `init` can keep the key
+enum Store {
+    Keyring(&'static dyn Keyring),
+    Config,
+    Nowhere,
+}
+
+/// Save the key where the user says: in the system's keyring, in the config file,
+/// private to them, or nowhere, with what to set instead
+fn store_key(tty: &mut Tty, profile: &str, key: &str) -> Result<(), String> {
+    let path = config::get_config_path();
+    let mut stores = Vec::new();
+    if let Some(keyring) = credentials::keyring() {
+        stores.push((Store::Keyring(keyring), format!("In {}", keyring.name())));
+    }
+    stores.push((
+        Store::Config,
+        format!("In {}, readable only by you", path.display()),
+    ));
+    stores.push((
+        Store::Nowhere,
+        if profile == profile::DEFAULT {
+            "Nowhere; I'll set GODSPEED_API myself".to_string()
+        } else {
+            "Nowhere; I'll set an environment variable myself".to_string()
+        },
+    ));
+    tty.say("Where should the key be kept?")?;
+    for (i, (_, description)) in stores.iter().enumerate() {
+        tty.say(&format!("  [{}] {}", i + 1, description))?;
+    }
+    let answer = tty.ask("Choice [1]: ")?;
+    let chosen = match answer.as_str() {
+        "" => Some(0),
+        number => number.parse::<usize>().ok().and_then(|n| n.checked_sub(1)),
+    };
+    let Some((store, _)) = chosen.and_then(|i| stores.get(i)) else {
+        return Err(format!("Unknown choice {}; nothing was saved", answer));
+    };
+    match store {
+        Store::Keyring(keyring) => {
+            keyring.set(profile, key)?;
+            tty.say(&format!("Saved the key in {}.", keyring.name()))
+        }
+        Store::Config => {
+            config::set(&format!("profiles.{}.api_key", profile), key)?;
+            make_private(&path).map_err(|e| format!("Can't protect {}: {}", path.display(), e))?;
+            tty.say(&format!("Saved the key in {}.", path.display()))
+        }
+        Store::Nowhere if profile == profile::DEFAULT => {
+            tty.say("Add `export GODSPEED_API=<your key>` to your shell's startup file.")
+        }
+        Store::Nowhere => {
+            let var = format!(
+                "GODSPEED_API_{}",
+                profile
+                    .to_uppercase()
+                    .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            );
+            config::set(&format!("profiles.{}.api_key_env", profile), &var)?;
+            tty.say(&format!(
+                "Add `export {}=<your key>` to your shell's startup file.",
+                var
+            ))
+        }
+    }
+}
+
+/// Offer the cached lists as the default for tasks without `@list`
+fn choose_default_list(tty: &mut Tty, resolver: &Resolver) -> Result<(), String> {
+    let mut names: Vec<String> = resolver
+        .lists
+        .entries
+        .as_ref()
+        .map(|entries| entries.keys().cloned().collect())
+        .unwrap_or_default();
+    if names.is_empty() || !tty.confirm("Choose a default list for tasks without @list?", false)? {
+        return Ok(());
+    }
+    names.sort();
+    if let Some(name) =
+        picker::pick("default list", &names, false).and_then(|picked| picked.into_iter().next())
+    {
+        config::set("default_list", &name)?;
+        tty.say(&format!("Tasks without @list go to {}.", name))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_private(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+}
+
+#[cfg(not(unix))]
+fn make_private(_path: &std::path::Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Questions and answers on the terminal, even when stdin is a pipe
+pub struct Tty {
+    reader: BufReader<File>,
+    writer: File,
+}
+
+impl Tty {
+    pub fn open() -> Option<Self> {
+        let (reader, writer) = term::open_tty()?;
+        Some(Tty {
+            writer,
+            reader: BufReader::new(reader),
+        })
+    }
+
+    pub fn say(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.writer, "{}", line).map_err(|e| e.to_string())
+    }
+
+    pub fn ask(&mut self, question: &str) -> Result<String, String> {
+        write!(self.writer, "{}", question).map_err(|e| e.to_string())?;
+        let _ = self.writer.flush();
+        let mut answer = String::new();
+        self.reader
+            .read_line(&mut answer)
+            .map_err(|e| e.to_string())?;
+        Ok(answer.trim().to_string())
+    }
+
+    pub fn confirm(&mut self, question: &str, default: bool) -> Result<bool, String> {
+        let hint = if default { "[Y/n]" } else { "[y/N]" };
+        let answer = self.ask(&format!("{} {} ", question, hint))?.to_lowercase();
+        Ok(match answer.as_str() {
+            "" => default,
+            answer => matches!(answer, "y" | "yes"),
+        })
+    }
+
+    /// Like `ask`, with typing not echoed, for secrets
+    pub fn ask_hidden(&mut self, question: &str) -> Result<String, String> {
+        let echo = self.set_echo(false);
+        let answer = self.ask(question);
+        if echo {
+            self.set_echo(true);
+            // The Enter that ended the answer wasn't echoed either
+            let _ = writeln!(self.writer);
+        }
+        answer
+    }
+
+    /// Turn the terminal's echo on or off, returning whether that worked
+    #[cfg(unix)]
+    fn set_echo(&mut self, on: bool) -> bool {
+        use std::os::unix::io::AsRawFd;
+        let fd = self.reader.get_ref().as_raw_fd();
+        let mut term: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut term) } != 0 {
+            return false;
+        }
+        if on {
+            term.c_lflag |= libc::ECHO;
+        } else {
+            term.c_lflag &= !libc::ECHO;
+        }
+        unsafe { libc::tcsetattr(fd, libc::TCSANOW, &term) == 0 }
+    }
+
+    #[cfg(not(unix))]
+    fn set_echo(&mut self, _on: bool) -> bool {
+        false
+    }
+}
@@ -0,0 +1,157 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+/// An advisory lock on a file next to the data it guards, released when
+/// dropped. Every run that reads and rewrites that data takes it first, so
+/// two invocations at once (a hotkey that fires twice) can't interleave.
+pub struct FileLock {
+    _file: File,
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+}
+
+/// Wait for the lock at `path`
+pub fn exclusive(path: &Path) -> io::Result<FileLock> {
+    let file = open(path)?;
+    flock(&file, false)?;
+    Ok(FileLock { _file: file })
+}
+
+/// Take the lock at `path` if nobody holds it, without waiting
+pub fn try_exclusive(path: &Path) -> io::Result<Option<FileLock>> {
+    let file = open(path)?;
+    match flock(&file, true) {
+        Ok(()) => Ok(Some(FileLock { _file: file })),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+fn flock(file: &File, nonblocking: bool) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let mut operation = libc::LOCK_EX;
+    if nonblocking {
+        operation |= libc::LOCK_NB;
+    }
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        // A signal such as Ctrl-C interrupts the wait; the write still has to happen
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn flock(file: &File, nonblocking: bool) -> io::Result<()> {
+    let mut flags = lock_file::LOCKFILE_EXCLUSIVE_LOCK;
+    if nonblocking {
+        flags |= lock_file::LOCKFILE_FAIL_IMMEDIATELY;
+    }
+    lock_file::lock(file, flags)
+}
+
+/// Windows drops a handle's locks when it's closed, but only "eventually",
+/// so the lock is let go of explicitly for the next run to take at once
+#[cfg(windows)]
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = lock_file::unlock(&self._file);
+    }
+}
+
+/// Other platforms go without the lock, so runs at once may interleave
+#[cfg(not(any(unix, windows)))]
+fn flock(_file: &File, _nonblocking: bool) -> io::Result<()> {
+    Ok(())
+}
+
+/// `LockFileEx` and `UnlockFileEx` from kernel32, over the whole file
+#[cfg(windows)]
+mod lock_file {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::windows::io::AsRawHandle;
+
+    pub const LOCKFILE_FAIL_IMMEDIATELY: u32 = 1;
+    pub const LOCKFILE_EXCLUSIVE_LOCK: u32 = 2;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    /// `OVERLAPPED`, whose offset is where the locked range starts
+    #[repr(C)]
+    struct Overlapped {
+        internal: usize,
+        internal_high: usize,
+        offset: u32,
+        offset_high: u32,
+        event: *mut c_void,
+    }
+
+    impl Overlapped {
+        fn start() -> Self {
+            Overlapped {
+                internal: 0,
+                internal_high: 0,
+                offset: 0,
+                offset_high: 0,
+                event: std::ptr::null_mut(),
+            }
+        }
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LockFileEx(
+            file: *mut c_void,
+            flags: u32,
+            reserved: u32,
+            length_low: u32,
+            length_high: u32,
+            overlapped: *mut Overlapped,
+        ) -> i32;
+        fn UnlockFileEx(
+            file: *mut c_void,
+            reserved: u32,
+            length_low: u32,
+            length_high: u32,
+            overlapped: *mut Overlapped,
+        ) -> i32;
+    }
+
+    pub fn lock(file: &File, flags: u32) -> io::Result<()> {
+        let mut overlapped = Overlapped::start();
+        let handle = file.as_raw_handle();
+        if unsafe { LockFileEx(handle, flags, 0, u32::MAX, u32::MAX, &mut overlapped) } != 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        // What a held lock looks like with LOCKFILE_FAIL_IMMEDIATELY
+        if e.raw_os_error() == Some(ERROR_LOCK_VIOLATION) {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, e));
+        }
+        Err(e)
+    }
+
+    pub fn unlock(file: &File) -> io::Result<()> {
+        let mut overlapped = Overlapped::start();
+        let handle = file.as_raw_handle();
+        if unsafe { UnlockFileEx(handle, 0, u32::MAX, u32::MAX, &mut overlapped) } != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
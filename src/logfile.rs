@@ -0,0 +1,286 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::dates;
+use crate::get_data_dir;
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Keep a log file; `GODSPEED_LOG` takes precedence
+    pub enabled: bool,
+    pub level: LogLevel,
+    /// The file is rotated once it's past this size
+    pub max_size_kb: u64,
+    /// Rotated files kept next to it, `godspeed-cli.log.1` the newest
+    pub keep: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            enabled: false,
+            level: LogLevel::Info,
+            max_size_kb: 1024,
+            keep: 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    /// Requests, created tasks and queue changes
+    Info,
+    /// Cache lookups too
+    Debug,
+}
+
+impl LogLevel {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "error" => Some(LogLevel::Error),
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+
+    fn level(self) -> Level {
+        match self {
+            LogLevel::Error => Level::ERROR,
+            LogLevel::Warn => Level::WARN,
+            LogLevel::Info => Level::INFO,
+            LogLevel::Debug => Level::DEBUG,
+        }
+    }
+}
+
+pub fn get_log_path() -> PathBuf {
+    get_data_dir().join("godspeed-cli.log")
+}
+
+/// Start writing this run's events to the log file, if the config or
+/// `GODSPEED_LOG` (`1`, `0` or a level such as `debug`) says to. Call it
+/// once, early; events before it go nowhere.
+pub fn init(config: &LogConfig) {
+    let level = match env::var("GODSPEED_LOG") {
+        Ok(value) if value == "0" => None,
+        Ok(value) if !value.is_empty() => Some(LogLevel::parse(&value).unwrap_or(config.level)),
+        _ if config.enabled => Some(config.level),
+        _ => None,
+    };
+    let Some(level) = level else {
+        return;
+    };
+    let log = FileLog {
+        path: get_log_path(),
+        max_size: config.max_size_kb.saturating_mul(1024),
+        keep: config.keep,
+        level: level.level(),
+        file: Mutex::new(None),
+    };
+    if let Err(e) = log.open() {
+        eprintln!(
+            "Warning: failed to open the log file {}: {}",
+            log.path.display(),
+            e
+        );
+        return;
+    }
+    let _ = tracing::subscriber::set_global_default(log);
+}
+
+/// Writes events as JSON lines, one object per event with its fields,
+/// rotating the file as it grows. Spans aren't used, so they're ignored.
+struct FileLog {
+    path: PathBuf,
+    max_size: u64,
+    keep: usize,
+    level: Level,
+    file: Mutex<Option<File>>,
+}
+
+impl FileLog {
+    fn open(&self) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        *file = Some(self.reopen()?);
+        Ok(())
+    }
+
+    /// The log file, rotated first if it's too big
+    fn reopen(&self) -> io::Result<File> {
+        if fs::metadata(&self.path).is_ok_and(|m| m.len() >= self.max_size) {
+            rotate(&self.path, self.keep)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+    }
+
+    fn write(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // The daemon runs for days, so the size is checked as it goes
+        let full = match file.as_ref() {
+            Some(file) => file.metadata()?.len() >= self.max_size,
+            None => true,
+        };
+        if full {
+            *file = Some(self.reopen()?);
+        }
+        match file.as_mut() {
+            Some(file) => writeln!(file, "{}", line),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Shift `godspeed-cli.log` to `.1`, `.1` to `.2` and so on, dropping the
+/// oldest past `keep`
+fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+    let numbered = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    if keep == 0 {
+        return fs::remove_file(path);
+    }
+    match fs::remove_file(numbered(keep)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    for n in (1..keep).rev() {
+        match fs::rename(numbered(n), numbered(n + 1)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    // Another run may have rotated it already
+    match fs::rename(path, numbered(1)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Now as a UTC timestamp with milliseconds, since a run's events are often
+/// within the same second
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = dates::format_utc(now.as_secs() as i64);
+    format!(
+        "{}.{:03}Z",
+        seconds.trim_end_matches('Z'),
+        now.subsec_millis()
+    )
+}
+
+/// Collects an event's fields into a JSON object
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+}
+
+impl Subscriber for FileLog {
+    /// Only the CLI's own events; the HTTP libraries' are too detailed
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.level && metadata.target().starts_with("godspeed_cli")
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::from_level(self.level))
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let mut fields = Map::new();
+        fields.insert("at".into(), json!(timestamp()));
+        fields.insert("level".into(), json!(metadata.level().as_str()));
+        fields.insert("target".into(), json!(metadata.target()));
+        fields.insert("pid".into(), json!(std::process::id()));
+        event.record(&mut Fields(&mut fields));
+        // Nowhere to report a failure that wouldn't get in the way
+        let _ = self.write(&Value::Object(fields).to_string());
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn rotation_shifts_the_files_and_drops_the_oldest() {
+        let dir = std::env::temp_dir().join(format!("godspeed-cli-rotate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("godspeed-cli.log");
+        let numbered = |n: usize| dir.join(format!("godspeed-cli.log.{}", n));
+
+        for run in ["first", "second", "third", "fourth"] {
+            fs::write(&log, run).unwrap();
+            rotate(&log, 2).unwrap();
+        }
+        assert_eq!(read(&log), None);
+        assert_eq!(read(&numbered(1)).as_deref(), Some("fourth"));
+        assert_eq!(read(&numbered(2)).as_deref(), Some("third"));
+        assert_eq!(read(&numbered(3)), None);
+
+        // Already rotated by another run
+        rotate(&log, 2).unwrap();
+        assert_eq!(read(&numbered(2)).as_deref(), Some("fourth"));
+
+        fs::write(&log, "dropped").unwrap();
+        rotate(&log, 0).unwrap();
+        assert_eq!(read(&log), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,11 +2,58 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::io::{self, Read};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-#[derive(Serialize, Deserialize, Debug)]
+mod audit;
+mod auth;
+mod backup;
+mod cancel;
+mod config;
+mod credentials;
+mod daemon;
+mod dates;
+mod dotenv;
+mod error;
+mod git;
+mod ingest;
+mod init;
+mod lock;
+mod logfile;
+mod matching;
+mod metrics;
+mod mock;
+mod notify;
+mod online;
+mod picker;
+mod plan;
+mod profile;
+mod project;
+mod queue;
+mod receipt;
+mod sha256;
+mod sigils;
+mod task_file;
+mod task_json;
+mod templates;
+mod term;
+mod tls;
+mod urls;
+mod version;
+mod watch;
+
+use config::{Config, CreateLists, PickList};
+use error::GodspeedError;
+use matching::{Lookup, MatchingConfig};
+use term::Style;
+use urls::UrlConfig;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 struct TaskRequest {
     title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -17,6 +64,18 @@ struct TaskRequest {
     label_ids: Vec<String>,
     #[serde(skip_serializing_if = "String::is_empty")]
     notes: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeless_due_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timeless_starts_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recurrence_rule: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignee_id: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -41,78 +100,758 @@ struct LabelItem {
     name: String,
 }
 
-fn get_xdg_data_home() -> PathBuf {
-    if let Ok(xdg) = env::var("XDG_DATA_HOME") {
-        PathBuf::from(xdg)
-    } else if let Ok(home) = env::var("HOME") {
-        PathBuf::from(home).join(".local").join("share")
+#[derive(Deserialize, Debug)]
+struct MembersResponse {
+    members: Vec<MemberItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MemberItem {
+    id: String,
+    name: String,
+}
+
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+const DEFAULT_API_URL: &str = "https://api.godspeedapp.com";
+
+/// Set by `--safe` or `GODSPEED_SAFE=1`: captures are queued and nothing is changed remotely
+static SAFE_MODE: AtomicBool = AtomicBool::new(false);
+
+fn safe_mode() -> bool {
+    SAFE_MODE.load(Ordering::SeqCst)
+}
+
+/// Report an error and exit with `status`: as `Error: ...` on stderr, or with
+/// `--json` as `{"error": {"code", "message"}}` on stdout
+fn fail(code: &str, message: impl std::fmt::Display, status: i32) -> ! {
+    tracing::error!(code, message = %message, "failed");
+    if term::json() {
+        term::print_json(&error_json(code, &message.to_string()));
+    } else {
+        eprintln!("{} {}", term::paint_err(Style::Error, "Error:"), message);
+    }
+    std::process::exit(status)
+}
+
+fn error_json(code: &str, message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": { "code": code, "message": message } })
+}
+
+/// A stable code for a failed task, for `--json` output
+fn error_code(e: &(dyn std::error::Error + 'static)) -> &'static str {
+    if e.is::<UnknownName>() {
+        "unknown_name"
+    } else if e.is::<AmbiguousName>() {
+        "ambiguous_name"
+    } else if let Some(e) = e.downcast_ref::<GodspeedError>() {
+        e.code()
+    } else if cancel::is_cancelled() {
+        "cancelled"
     } else {
-        PathBuf::from(".local").join("share")
+        "send_failed"
+    }
+}
+
+/// The exit status for a failed task: one per kind of API failure, so
+/// scripts can tell a bad key from a dropped connection, and 1 otherwise
+fn exit_code(e: &(dyn std::error::Error + 'static)) -> i32 {
+    match e.downcast_ref::<GodspeedError>() {
+        Some(e) => e.exit_code(),
+        None => 1,
+    }
+}
+
+/// `--json` details for a failed task
+fn task_error_json(e: &(dyn std::error::Error + 'static)) -> serde_json::Value {
+    let mut error = error_json(error_code(e), &e.to_string());
+    if let Some(field) = e
+        .downcast_ref::<GodspeedError>()
+        .and_then(GodspeedError::field)
+    {
+        error["error"]["field"] = serde_json::json!(field);
+    }
+    error
+}
+
+#[derive(Clone)]
+struct Api {
+    key: String,
+    /// Without a trailing slash, so paths can be appended
+    base_url: String,
+    timeout: Duration,
+    /// From `[api] proxy`; without one, the proxy environment variables apply
+    proxy: Option<reqwest::Proxy>,
+    tls: tls::Tls,
+    /// Shared by every clone, so a run's requests reuse one connection
+    /// instead of paying for a new TLS handshake per task
+    client: Arc<OnceLock<reqwest::blocking::Client>>,
+    /// The same for pages that aren't the API (URL titles, the release
+    /// feed), which don't get the API's certificate pins
+    pages: Arc<OnceLock<reqwest::blocking::Client>>,
+}
+
+impl Api {
+    fn new(
+        key: String,
+        base_url: String,
+        timeout: Duration,
+        proxy: Option<reqwest::Proxy>,
+        tls: tls::Tls,
+    ) -> Self {
+        Api {
+            key,
+            base_url,
+            timeout,
+            proxy,
+            tls,
+            client: Arc::default(),
+            pages: Arc::default(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    fn client(&self) -> reqwest::Result<reqwest::blocking::Client> {
+        if let Some(client) = self.client.get() {
+            return Ok(client.clone());
+        }
+        let mut builder = self.tls.apply_pinned(self.base_builder());
+        // The mock is on this machine; a proxy couldn't reach it
+        if mock::enabled() {
+            builder = builder.no_proxy();
+        }
+        let client = builder.build()?;
+        Ok(self.client.get_or_init(|| client).clone())
+    }
+
+    /// The client for pages that aren't the API, built on first use
+    fn page_client(&self) -> reqwest::Result<reqwest::blocking::Client> {
+        if let Some(client) = self.pages.get() {
+            return Ok(client.clone());
+        }
+        let client = self.tls.apply(self.base_builder()).build()?;
+        Ok(self.pages.get_or_init(|| client).clone())
+    }
+
+    fn base_builder(&self) -> reqwest::blocking::ClientBuilder {
+        let builder = reqwest::blocking::Client::builder().timeout(self.timeout);
+        match &self.proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder,
+        }
+    }
+
+    /// Send a request, trying again after a short, jittered wait when it
+    /// fails in a way that's likely to pass: the connection couldn't be made,
+    /// or the server answered 5xx or 429. A timeout is only retried when
+    /// `idempotent`, since a create that timed out may still have happened.
+    fn send(
+        &self,
+        idempotent: bool,
+        request: impl Fn(&reqwest::blocking::Client) -> reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response, GodspeedError> {
+        let client = self.client()?;
+        let mut attempt = 0;
+        loop {
+            let result = request(&client).build().and_then(|request| {
+                trace_request(&request);
+                let (method, url) = (request.method().clone(), request.url().clone());
+                let started = Instant::now();
+                let result = client.execute(request);
+                trace_response(&method, &url, started.elapsed(), &result);
+                result
+            });
+            if let Ok(response) = &result {
+                version::note_deprecation(response);
+            }
+            let transient = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => e.is_connect() || (idempotent && e.is_timeout()),
+            };
+            // A rate limit says how long to wait; only a short wait is worth it here
+            let wait = result.as_ref().ok().and_then(rate_limit_wait);
+            if !transient
+                || attempt >= RETRIES
+                || cancel::is_cancelled()
+                || wait.is_some_and(|wait| wait > MAX_RETRY_AFTER)
+            {
+                return result.map_err(GodspeedError::from);
+            }
+            let delay = retry_delay(attempt).max(wait.unwrap_or_default());
+            tracing::warn!(attempt, delay_ms = delay.as_millis() as u64, "retrying");
+            if term::verbose(1) {
+                term::debug(&format!("retrying in {}ms", delay.as_millis()));
+            }
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+/// With `-v`, the request about to be sent; with `-vv`, its headers and body
+/// too, with the API key left out
+fn trace_request(request: &reqwest::blocking::Request) {
+    if !term::verbose(1) {
+        return;
+    }
+    term::debug(&format!("-> {} {}", request.method(), request.url()));
+    if !term::verbose(2) {
+        return;
+    }
+    for (name, value) in request.headers() {
+        let value = if name == reqwest::header::AUTHORIZATION {
+            "Bearer <redacted>".into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        term::debug(&format!("   {}: {}", name, value));
+    }
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        term::debug(&format!("   {}", String::from_utf8_lossy(body)));
+    }
+}
+
+/// Record how a request went in the log file, and with `-v` on stderr. The
+/// URL has no secrets; the key is only ever in a header.
+fn trace_response(
+    method: &reqwest::Method,
+    url: &reqwest::Url,
+    took: Duration,
+    result: &reqwest::Result<reqwest::blocking::Response>,
+) {
+    let ms = took.as_millis() as u64;
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            if status.is_success() || status == reqwest::StatusCode::NOT_MODIFIED {
+                tracing::info!(%method, %url, status = status.as_u16(), ms, "request");
+            } else {
+                tracing::warn!(%method, %url, status = status.as_u16(), ms, "request");
+            }
+            if term::verbose(1) {
+                term::debug(&format!("<- {} {} {} in {}ms", status, method, url, ms));
+            }
+        }
+        Err(e) => {
+            tracing::warn!(%method, %url, ms, error = %e, "request failed");
+            if term::verbose(1) {
+                term::debug(&format!("<- {} {} failed in {}ms: {}", method, url, ms, e));
+            }
+        }
+    }
+}
+
+/// Check an API base URL from the config or environment, dropping any
+/// trailing slash. A path is kept, for proxies that serve the API under one.
+fn parse_base_url(url: &str) -> Result<String, String> {
+    let parsed = reqwest::Url::parse(url.trim())
+        .map_err(|e| format!("invalid API base URL {}: {}", url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        return Err(format!(
+            "invalid API base URL {}: it must start with http:// or https://",
+            url
+        ));
+    }
+    Ok(parsed.as_str().trim_end_matches('/').to_string())
+}
+
+/// Check a proxy URL from the config. SOCKS5 needs the `socks` feature,
+/// which release builds have.
+fn parse_proxy(url: &str) -> Result<reqwest::Proxy, String> {
+    let url = url.trim();
+    let scheme = url.split_once("://").map_or("", |(scheme, _)| scheme);
+    if !cfg!(feature = "socks") && scheme.starts_with("socks") {
+        return Err(format!(
+            "proxy {}: this build can't use SOCKS proxies (build with --features socks)",
+            url
+        ));
+    }
+    if !matches!(scheme, "http" | "https" | "socks5" | "socks5h") {
+        return Err(format!(
+            "invalid proxy {}: it must start with http://, https://, socks5:// or socks5h://",
+            url
+        ));
+    }
+    reqwest::Proxy::all(url).map_err(|e| format!("invalid proxy {}: {}", url, e))
+}
+
+/// Extra attempts for a request that failed in a way that's likely to pass
+const RETRIES: u32 = 2;
+
+/// The longest `Retry-After` waited out before a request is given up on
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(2);
+
+/// How long a 429 response's `Retry-After` asks for, in seconds or as an
+/// HTTP date. The time is saved so the queue isn't sent before it passes.
+fn rate_limit_wait(response: &reqwest::blocking::Response) -> Option<Duration> {
+    if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let value = response.headers().get(reqwest::header::RETRY_AFTER)?;
+    let value = value.to_str().ok()?.trim();
+    let now = dates::now_unix();
+    let until = match value.parse::<i64>() {
+        Ok(secs) => now + secs.max(0),
+        Err(_) => dates::parse_http_date(value)?,
+    };
+    if let Err(e) = queue::set_rate_limited_until(until) {
+        eprintln!("Warning: failed to save the rate limit: {}", e);
+    }
+    Some(Duration::from_secs((until - now).max(0) as u64))
+}
+
+/// 250ms, then 500ms, each plus up to half again, so runs started together
+/// don't retry in step
+fn retry_delay(attempt: u32) -> Duration {
+    let base = 250u64 << attempt;
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    Duration::from_millis(base + nanos % (base / 2 + 1))
+}
+
+/// `HOME`, or on Windows `USERPROFILE` when `HOME` isn't set
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .or_else(|| cfg!(windows).then(|| env::var_os("USERPROFILE")).flatten())
+        .map(PathBuf::from)
+}
+
+/// `XDG_DATA_HOME`, or the platform's place for per-user data: `%LOCALAPPDATA%`
+/// on Windows, since the caches and queue belong to this machine, and
+/// `~/.local/share` elsewhere
+fn get_data_home() -> PathBuf {
+    if let Some(xdg) = env::var_os("XDG_DATA_HOME").filter(|xdg| !xdg.is_empty()) {
+        return PathBuf::from(xdg);
+    }
+    if cfg!(windows) {
+        if let Some(local) = env::var_os("LOCALAPPDATA") {
+            return PathBuf::from(local);
+        }
+    }
+    home_dir().unwrap_or_default().join(".local").join("share")
+}
+
+/// A path from the config, with a leading `~/` meaning the home directory
+fn expand_home(path: &str) -> PathBuf {
+    let rest = path
+        .strip_prefix("~/")
+        .or_else(|| cfg!(windows).then(|| path.strip_prefix("~\\")).flatten());
+    match (rest, home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
     }
 }
 
-fn get_cache_path() -> PathBuf {
-    get_xdg_data_home().join("godspeed-cli").join("cache")
+/// Caches and queue for the active profile
+fn get_data_dir() -> PathBuf {
+    // The mock's ids must never mix with a real account's caches or queue
+    if mock::enabled() {
+        return profile::get_base_dir().join("mock");
+    }
+    match profile::active() {
+        profile::DEFAULT => profile::get_base_dir(),
+        name => profile::get_base_dir().join("profiles").join(name),
+    }
 }
 
 fn get_lists_path() -> PathBuf {
-    get_xdg_data_home().join("godspeed-cli").join("lists.toml")
+    get_data_dir().join("lists.toml")
 }
 
 fn get_labels_path() -> PathBuf {
-    get_xdg_data_home().join("godspeed-cli").join("labels.toml")
+    get_data_dir().join("labels.toml")
+}
+
+fn get_members_path() -> PathBuf {
+    get_data_dir().join("members.toml")
 }
 
 fn ensure_directories() -> io::Result<()> {
-    let data_dir = get_xdg_data_home().join("godspeed-cli");
+    let data_dir = get_data_dir();
     fs::create_dir_all(&data_dir)?;
     Ok(())
 }
 
-fn send_notification(message: &str) {
-    let script = format!(
-        r#"display notification "{}" with title "Godspeed CLI""#,
-        message.replace('"', "\\\"")
-    );
-    let _ = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output();
+/// Replace a file by writing a temporary file next to it and renaming that
+/// into place, so a crash or power loss mid-write leaves either the old
+/// contents or the new, never a truncated file
+fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".tmp-{}", std::process::id()));
+    let temp = path.with_file_name(name);
+
+    let written = fs::File::create(&temp).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        // A file kept private, such as a config holding an API key, stays so
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|()| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    // Make the rename itself durable; not every platform can sync a directory
+    if let Some(dir) = path.parent() {
+        let _ = fs::File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
+/// Whether `program` is in a directory on `PATH`, as on Unix, where the
+/// name is the whole file name
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Ask for a task in a native text-input dialog. None if it was cancelled
+/// or no dialog tool is available.
+fn ask_dialog() -> Option<String> {
+    let output = if cfg!(target_os = "macos") {
+        Command::new("osascript")
+            .arg("-e")
+            .arg(r#"text returned of (display dialog "New task" default answer "" with title "Godspeed CLI")"#)
+            .output()
+            .ok()?
+    } else if cfg!(windows) {
+        // Cancel gives an empty answer, the same as entering nothing
+        Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "Add-Type -AssemblyName Microsoft.VisualBasic; [Microsoft.VisualBasic.Interaction]::InputBox('New task', 'Godspeed CLI')",
+            ])
+            .output()
+            .ok()?
+    } else {
+        Command::new("zenity")
+            .args(["--entry", "--title=Godspeed CLI", "--text=New task"])
+            .output()
+            .or_else(|_| {
+                Command::new("kdialog")
+                    .args(["--title", "Godspeed CLI", "--inputbox", "New task"])
+                    .output()
+            })
+            .ok()?
+    };
+
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn parse_priority(s: &str) -> Option<i32> {
+    match s.to_lowercase().as_str() {
+        "1" | "high" => Some(1),
+        "2" | "medium" | "med" => Some(2),
+        "3" | "low" => Some(3),
+        "4" | "none" => Some(4),
+        _ => None,
+    }
+}
+
+fn parse_duration(s: &str) -> Option<i32> {
+    if let Ok(minutes) = s.parse::<i32>() {
+        return (minutes > 0).then_some(minutes);
+    }
+
+    // Units like `1h`, `90m`, `1.5h`, `1h30m` or `1h30`
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut last_unit = None;
+    for c in s.to_lowercase().chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let value = number.parse::<f64>().ok()?;
+        total += match c {
+            'h' => value * 60.0,
+            'm' => value,
+            _ => return None,
+        };
+        number.clear();
+        last_unit = Some(c);
+    }
+    if !number.is_empty() {
+        // A bare number after hours means minutes; after minutes it means
+        // nothing, so `30m15` is a typo rather than 45 minutes
+        if last_unit != Some('h') {
+            return None;
+        }
+        total += number.parse::<f64>().ok()?;
+    }
+
+    let minutes = total.round() as i32;
+    (last_unit.is_some() && minutes > 0).then_some(minutes)
+}
+
+/// Minutes the way `:duration` is written, such as `15m`, `1h` or `1h30m`
+fn format_duration(minutes: i32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h{}m", hours, minutes),
+    }
+}
+
+struct Word {
+    text: String,
+    /// Escaped with a leading backslash (`\@handle`), so never treated as a token
+    literal: bool,
+}
+
+/// Split task text into words. A sigil followed by a quote (`@"Home Projects"`)
+/// takes everything up to the closing quote, and a backslash escapes whitespace,
+/// quotes and backslashes (`@Home\ Projects`) or, at the start of a word, the
+/// sigil itself (`\@handle`).
+fn tokenize(input: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut literal = false;
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars
+                .peek()
+                .is_some_and(|next| next.is_whitespace() || *next == '"' || *next == '\\') =>
+            {
+                current.extend(chars.next());
+            }
+            '\\' if current.is_empty() && chars.peek().is_some() => {
+                literal = true;
+                current.extend(chars.next());
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if current == "." || current == "@" || current == "+" => in_quotes = true,
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    words.push(Word {
+                        text: std::mem::take(&mut current),
+                        literal,
+                    });
+                }
+                literal = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(Word {
+            text: current,
+            literal,
+        });
+    }
+
+    words
+}
+
+/// A sigil token for `name` that tokenizes back to exactly `name`
+fn sigil_token(sigil: char, name: &str) -> String {
+    let mut token = sigil.to_string();
+    for c in name.chars() {
+        if c.is_whitespace() || c == '"' || c == '\\' {
+            token.push('\\');
+        }
+        token.push(c);
+    }
+    token
+}
+
+/// Byte offset of a standalone `--`, after which the rest of the input is raw title.
+/// Escaped whitespace (`@a\ --`) doesn't end a word, as in `tokenize`.
+fn find_raw_marker(input: &str) -> Option<usize> {
+    let mut start = None;
+    let mut escaped = false;
+    for (i, c) in input
+        .char_indices()
+        .chain(std::iter::once((input.len(), ' ')))
+    {
+        if c.is_whitespace() && !escaped {
+            if start.is_some_and(|s| &input[s..i] == "--") {
+                return start;
+            }
+            start = None;
+        } else if start.is_none() {
+            start = Some(i);
+        }
+        escaped = !escaped && c == '\\';
+    }
+    None
+}
+
+/// Byte offsets where `pattern` starts in `input`, not overlapping, leaving out
+/// any that start with a character escaped by a backslash
+fn find_unescaped(input: &str, pattern: &str) -> Vec<usize> {
+    let mut found: Vec<usize> = Vec::new();
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if !escaped
+            && input[i..].starts_with(pattern)
+            && found.last().is_none_or(|last| i >= last + pattern.len())
+        {
+            found.push(i);
+        }
+        escaped = !escaped && c == '\\';
+    }
+    found
+}
+
+/// Rewrite whitespace-separated words before any standalone `--`, keeping the
+/// whitespace between them. `f` returns the replacement, or None to keep a word.
+fn map_words(
+    input: &str,
+    mut f: impl FnMut(&str) -> Result<Option<String>, String>,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut start = None;
+
+    for (i, c) in input
+        .char_indices()
+        .chain(std::iter::once((input.len(), ' ')))
+    {
+        if !c.is_whitespace() {
+            start.get_or_insert(i);
+            continue;
+        }
+        if let Some(s) = start.take() {
+            let word = &input[s..i];
+            if word == "--" {
+                result.push_str(&input[s..]);
+                return Ok(result);
+            }
+            match f(word)? {
+                Some(replacement) => result.push_str(&replacement),
+                None => result.push_str(word),
+            }
+        }
+        result.push_str(&input[i..(i + c.len_utf8()).min(input.len())]);
+    }
+    Ok(result)
+}
+
+/// Names referenced by a task's tokens, resolved to ids after parsing
+struct TaskRefs {
+    lists: Vec<String>,
+    labels: Vec<String>,
+    assignee: Option<String>,
 }
 
-fn parse_task(input: &str) -> (TaskRequest, Option<String>, Vec<String>) {
+fn parse_task(input: &str) -> (TaskRequest, TaskRefs) {
     let mut title = String::new();
-    let mut list_name: Option<String> = None;
+    let mut list_names: Vec<String> = Vec::new();
     let mut duration_minutes: Option<i32> = None;
     let mut label_names: Vec<String> = Vec::new();
+    let mut due_date: Option<String> = None;
+    let mut start_date: Option<String> = None;
+    let mut priority: Option<i32> = None;
+    let mut recurrence_rule: Option<String> = None;
+    let mut assignee: Option<String> = None;
+    let today = dates::today();
+
+    // Everything after the first line becomes notes
+    let (input, body) = match input.split_once('\n') {
+        Some((first, rest)) => (first, rest.trim()),
+        None => (input, ""),
+    };
+
+    // Everything after a standalone `--` is kept in the title as typed
+    let (input, raw_title) = match find_raw_marker(input) {
+        Some(pos) => (&input[..pos], input[pos + 2..].trim()),
+        None => (input, ""),
+    };
 
     // Check for notes separator
-    let (main_part, notes_part) = if let Some(pos) = input.find(" n:") {
+    let (main_part, notes_part) = if let Some(&pos) = find_unescaped(input, " n:").first() {
         let (main, note) = input.split_at(pos);
         (main, note.trim_start_matches(" n:").trim())
     } else {
         (input, "")
     };
 
-    let notes = notes_part.to_string();
+    let notes = match (notes_part, body) {
+        (notes_part, "") => notes_part.to_string(),
+        ("", body) => body.to_string(),
+        (notes_part, body) => format!("{}\n{}", notes_part, body),
+    };
+
+    let mut words = tokenize(main_part).into_iter().peekable();
+    while let Some(word) = words.next() {
+        if word.literal {
+            if !title.is_empty() {
+                title.push(' ');
+            }
+            title.push_str(&word.text);
+            continue;
+        }
+        let word = word.text.as_str();
+        let date_token = match word.strip_prefix('^') {
+            Some(date_str) => Some((date_str, &mut due_date)),
+            None => word
+                .strip_prefix("s:")
+                .map(|date_str| (date_str, &mut start_date)),
+        };
 
-    for word in main_part.split_whitespace() {
-        if word.starts_with('.') {
-            // Extract label
-            let label = word.trim_start_matches('.');
+        if let Some((date_str, target)) = date_token {
+            // Extract due or start date
+            if let Some(date) = dates::parse_date(date_str, today) {
+                *target = Some(date.to_string());
+            } else {
+                // If parsing fails, include it in the title
+                if !title.is_empty() {
+                    title.push(' ');
+                }
+                title.push_str(word);
+            }
+        } else if word.eq_ignore_ascii_case("*every")
+            && words
+                .peek()
+                .is_some_and(|next| !next.literal && dates::parse_every(&next.text).is_some())
+        {
+            // Extract recurrence from `*every <days or interval>`
+            recurrence_rule = words.next().and_then(|next| dates::parse_every(&next.text));
+        } else if let Some(rule) = word.strip_prefix('*').and_then(dates::parse_recurrence) {
+            // Extract recurrence
+            recurrence_rule = Some(rule);
+        } else if let Some(level) = word.strip_prefix('!').and_then(parse_priority) {
+            // Extract priority
+            priority = Some(level);
+        } else if let Some(name) = word
+            .strip_prefix('+')
+            .filter(|name| name.starts_with(char::is_alphabetic))
+        {
+            // Extract assignee
+            assignee = Some(name.to_string());
+        } else if let Some(label) = word.strip_prefix('.') {
+            // Extract label; only the one sigil goes, so `..NET` is `.NET`
             if !label.is_empty() {
                 label_names.push(label.to_string());
             }
-        } else if word.starts_with('@') {
+        } else if let Some(list) = word.strip_prefix('@') {
             // Extract list name
-            let list = word.trim_start_matches('@');
             if !list.is_empty() {
-                list_name = Some(list.to_string());
+                list_names.push(list.to_string());
             }
         } else if word.starts_with(':') {
             // Extract duration
             let duration_str = word.trim_start_matches(':');
-            if let Ok(duration) = duration_str.parse::<i32>() {
+            if let Some(duration) = parse_duration(duration_str) {
                 duration_minutes = Some(duration);
             } else {
                 // If parsing fails, include it in the title
@@ -129,6 +868,13 @@ fn parse_task(input: &str) -> (TaskRequest, Option<String>, Vec<String>) {
         }
     }
 
+    if !raw_title.is_empty() {
+        if !title.is_empty() {
+            title.push(' ');
+        }
+        title.push_str(raw_title);
+    }
+
     let title = title.trim_end().to_string();
 
     (
@@ -138,9 +884,18 @@ fn parse_task(input: &str) -> (TaskRequest, Option<String>, Vec<String>) {
             duration_minutes,
             label_ids: Vec::new(), // Will be resolved later
             notes,
+            timeless_due_at: due_date,
+            timeless_starts_at: start_date,
+            priority,
+            recurrence_rule,
+            parent_id: None,
+            assignee_id: None, // Will be resolved later
+        },
+        TaskRefs {
+            lists: list_names,
+            labels: label_names,
+            assignee,
         },
-        list_name,
-        label_names,
     )
 }
 
@@ -159,221 +914,4082 @@ fn load_cache(path: &PathBuf) -> HashMap<String, String> {
     HashMap::new()
 }
 
-fn save_cache(path: &PathBuf, cache: &HashMap<String, String>) -> io::Result<()> {
+/// Held while a name cache file is rewritten
+fn lock_cache(path: &Path) -> io::Result<lock::FileLock> {
+    lock::exclusive(&path.with_extension("lock"))
+}
+
+fn save_cache(path: &Path, cache: &HashMap<String, String>) -> io::Result<()> {
     let mut table = toml::Table::new();
     for (key, value) in cache {
         table.insert(key.clone(), toml::Value::String(value.clone()));
     }
     let toml_string = toml::to_string(&table).unwrap();
-    fs::write(path, toml_string)?;
+    write_atomic(path, toml_string)?;
     Ok(())
 }
 
-fn fetch_lists(api_key: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .get("https://api.godspeedapp.com/lists")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .send()?;
+/// What a name list fetch found
+enum Fetched {
+    /// The server answered 304: the cached copy matches its `ETag`
+    Unchanged,
+    Names {
+        entries: HashMap<String, String>,
+        etag: Option<String>,
+    },
+}
 
-    let lists_response: ListsResponse = response.json()?;
-    let mut map = HashMap::new();
-    for list in lists_response.lists {
-        map.insert(list.name.to_lowercase(), list.id);
-    }
-    Ok(map)
+/// Every page of a response and the `ETag` it came with
+struct Tagged<T> {
+    pages: Vec<T>,
+    /// Only kept when everything fit on one page, since the first page's tag
+    /// doesn't say whether later pages changed
+    etag: Option<String>,
 }
 
-fn fetch_labels(api_key: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .get("https://api.godspeedapp.com/labels")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .send()?;
+/// A runaway `next` link can't keep a capture waiting forever
+const MAX_PAGES: usize = 100;
 
-    let labels_response: LabelsResponse = response.json()?;
-    let mut map = HashMap::new();
-    for label in labels_response.labels {
-        map.insert(label.name.to_lowercase(), label.id);
-    }
-    Ok(map)
+/// GET `endpoint` and every page after it, sending `etag` as
+/// `If-None-Match` so an unchanged list costs a 304 instead of the whole
+/// body. None when it was unchanged.
+///
+/// The next page is the `Link` header's `rel="next"` URL, or the body's
+/// `next_cursor` passed back as `?cursor=`.
+fn fetch_conditional<T: serde::de::DeserializeOwned + Send + 'static>(
+    api: &Api,
+    endpoint: &'static str,
+    etag: Option<String>,
+) -> Result<Option<Tagged<T>>, Box<dyn std::error::Error>> {
+    let api = api.clone();
+    cancel::run(move || {
+        let mut pages = Vec::new();
+        let mut first_etag = None;
+        let mut next = Page::First;
+        loop {
+            let response = api.send(true, |client| {
+                let request = match &next {
+                    Page::First => client.get(api.url(endpoint)),
+                    Page::Url(url) => client.get(url),
+                    Page::Cursor(cursor) => {
+                        client.get(api.url(endpoint)).query(&[("cursor", cursor)])
+                    }
+                };
+                let request = request.header("Authorization", format!("Bearer {}", api.key));
+                match (&next, &etag) {
+                    (Page::First, Some(etag)) => {
+                        request.header(reqwest::header::IF_NONE_MATCH, etag)
+                    }
+                    _ => request,
+                }
+            })?;
+            if response.status() == reqwest::StatusCode::NOT_MODIFIED && matches!(next, Page::First)
+            {
+                return Ok(None);
+            }
+            if !response.status().is_success() {
+                return Err(GodspeedError::from_response(response).into());
+            }
+
+            if matches!(next, Page::First) {
+                first_etag = response
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string);
+            }
+            // A relative link is relative to the page it came with
+            let link = response
+                .headers()
+                .get(reqwest::header::LINK)
+                .and_then(|link| link.to_str().ok())
+                .and_then(next_link)
+                .and_then(|link| response.url().join(&link).ok())
+                .map(String::from);
+            let body: serde_json::Value = response.json().map_err(GodspeedError::from)?;
+            let cursor = body
+                .get("next_cursor")
+                .and_then(|cursor| cursor.as_str())
+                .filter(|cursor| !cursor.is_empty())
+                .map(str::to_string);
+            pages.push(
+                serde_json::from_value(body).map_err(|e| GodspeedError::Parse(e.to_string()))?,
+            );
+
+            next = match (link, cursor) {
+                // The API key goes with every request, so only follow links
+                // back to the API
+                (Some(url), _) if url.starts_with(&format!("{}/", api.base_url)) => Page::Url(url),
+                (Some(url), _) => {
+                    return Err(format!("The API linked to a page elsewhere: {}", url).into())
+                }
+                (None, Some(cursor)) => Page::Cursor(cursor),
+                (None, None) => break,
+            };
+            if pages.len() >= MAX_PAGES {
+                return Err(format!(
+                    "The API returned more than {} pages of {}",
+                    MAX_PAGES, endpoint
+                )
+                .into());
+            }
+        }
+        let etag = first_etag.filter(|_| pages.len() == 1);
+        Ok(Some(Tagged { pages, etag }))
+    })
 }
 
-fn find_matching_key(cache: &HashMap<String, String>, search: &str) -> Option<String> {
-    let search_lower = search.to_lowercase();
+enum Page {
+    First,
+    Url(String),
+    Cursor(String),
+}
 
-    // First try exact match
-    if let Some(id) = cache.get(&search_lower) {
-        return Some(id.clone());
+/// The `rel="next"` target of a `Link` header. A link can have several
+/// relations (`rel="next last"`), and quoted parameters may hold commas.
+fn next_link(header: &str) -> Option<String> {
+    let mut rest = header;
+    loop {
+        let start = rest.find('<')?;
+        let end = start + rest[start..].find('>')?;
+        let target = &rest[start + 1..end];
+        let params = split_unquoted(&rest[end + 1..], ',')[0];
+        rest = &rest[end + 1 + params.len()..];
+        let is_next = split_unquoted(params, ';').into_iter().any(|param| {
+            param.split_once('=').is_some_and(|(name, value)| {
+                name.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+        });
+        if is_next {
+            return Some(target.to_string());
+        }
     }
+}
 
-    // Then try prefix match
-    for (key, id) in cache {
-        if key.starts_with(&search_lower) {
-            return Some(id.clone());
+/// Split `s` on `separator` where it isn't inside double quotes
+fn split_unquoted(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == separator && !in_quotes {
+            parts.push(&s[start..i]);
+            start = i + c.len_utf8();
         }
     }
-
-    None
+    parts.push(&s[start..]);
+    parts
 }
 
-fn send_task(task: &TaskRequest, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post("https://api.godspeedapp.com/tasks")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(task)
-        .send()?;
-
-    if response.status().is_success() {
-        Ok(())
-    } else {
-        Err(format!("API error: {}", response.status()).into())
+fn fetch_lists(api: &Api, etag: Option<String>) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let Some(Tagged { pages, etag }) = fetch_conditional::<ListsResponse>(api, "lists", etag)?
+    else {
+        return Ok(Fetched::Unchanged);
+    };
+    let mut entries = HashMap::new();
+    for list in pages.into_iter().flat_map(|page| page.lists) {
+        entries.insert(list.name.to_lowercase(), list.id);
     }
+    Ok(Fetched::Names { entries, etag })
 }
 
-fn add_to_cache(task_str: &str) -> io::Result<()> {
-    let cache_path = get_cache_path();
-    let mut cache_content = fs::read_to_string(&cache_path).unwrap_or_default();
-    cache_content.push_str(task_str);
-    cache_content.push('\n');
-    cache_content.push_str("---\n");
-    fs::write(&cache_path, cache_content)?;
-    Ok(())
+fn fetch_labels(api: &Api, etag: Option<String>) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let Some(Tagged { pages, etag }) = fetch_conditional::<LabelsResponse>(api, "labels", etag)?
+    else {
+        return Ok(Fetched::Unchanged);
+    };
+    let mut entries = HashMap::new();
+    for label in pages.into_iter().flat_map(|page| page.labels) {
+        entries.insert(label.name.to_lowercase(), label.id);
+    }
+    Ok(Fetched::Names { entries, etag })
 }
 
-fn get_cached_tasks() -> Vec<String> {
-    let cache_path = get_cache_path();
-    if let Ok(content) = fs::read_to_string(&cache_path) {
-        content
-            .split("---\n")
-            .filter(|s| !s.trim().is_empty())
-            .map(|s| s.trim().to_string())
-            .collect()
-    } else {
-        Vec::new()
+fn fetch_members(api: &Api, etag: Option<String>) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let Some(Tagged { pages, etag }) = fetch_conditional::<MembersResponse>(api, "members", etag)?
+    else {
+        return Ok(Fetched::Unchanged);
+    };
+    let mut entries = HashMap::new();
+    for member in pages.into_iter().flat_map(|page| page.members) {
+        entries.insert(member.name.to_lowercase(), member.id);
     }
+    Ok(Fetched::Names { entries, etag })
 }
 
-fn remove_from_cache(task_str: &str) -> io::Result<()> {
-    let cache_path = get_cache_path();
-    let content = fs::read_to_string(&cache_path).unwrap_or_default();
-    let remaining: Vec<&str> = content
-        .split("---\n")
-        .filter(|s| !s.trim().is_empty() && s.trim() != task_str)
-        .collect();
-
-    let new_content = if remaining.is_empty() {
-        String::new()
-    } else {
-        remaining.join("---\n") + "\n---\n"
-    };
+/// Link that opens a task in the Godspeed app
+fn task_url(id: &str) -> String {
+    format!("godspeed://tasks/{}", id)
+}
 
-    fs::write(&cache_path, new_content)?;
-    Ok(())
+/// Create a task, returning its id when the API reports one
+fn send_task(
+    task: &TaskRequest,
+    key: &str,
+    api: &Api,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    // Backstop for any path that reaches the API without checking first
+    if safe_mode() {
+        return Err("Safe mode is on; not sending".into());
+    }
+    let api = api.clone();
+    let body = serde_json::to_value(task)?;
+    let payload = body.to_string();
+    let key = key.to_string();
+    let result = cancel::run(move || post_task(&api, &body, &key).map_err(Into::into));
+    audit_create(&payload, &result);
+    result
 }
 
-fn process_task(task_str: &str, api_key: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let (mut parsed, list_name, label_names) = parse_task(task_str);
+/// A create's outcome: the new task's id, when the API reports one
+type SendResult = Result<Option<String>, Box<dyn std::error::Error>>;
 
-    // Handle list resolution
-    if let Some(list_name_clean) = list_name {
-        let mut lists_cache = load_cache(&get_lists_path());
+fn audit_create(payload: &str, result: &SendResult) {
+    match result {
+        Ok(Some(id)) => audit::record("create", payload, &format!("created {}", id)),
+        Ok(None) => audit::record("create", payload, "created"),
+        Err(e) => audit::record("create", payload, &format!("failed: {}", e)),
+    }
+}
 
-        if let Some(list_id) = find_matching_key(&lists_cache, &list_name_clean) {
-            parsed.list_id = Some(list_id);
-        } else {
-            // Fetch lists from API
-            lists_cache = fetch_lists(api_key)?;
-            save_cache(&get_lists_path(), &lists_cache)?;
+/// POST a task, blocking until the API answers. `key` is the same for every
+/// attempt at one capture, so the API can tell a retry from a new task.
+fn post_task(
+    api: &Api,
+    body: &serde_json::Value,
+    key: &str,
+) -> Result<Option<String>, GodspeedError> {
+    let response = api.send(false, |client| {
+        client
+            .post(api.url("tasks"))
+            .header("Authorization", format!("Bearer {}", api.key))
+            .header("Content-Type", "application/json")
+            .header("Idempotency-Key", key)
+            .json(body)
+    })?;
 
-            if let Some(list_id) = find_matching_key(&lists_cache, &list_name_clean) {
-                parsed.list_id = Some(list_id);
-            }
-        }
+    if !response.status().is_success() {
+        return Err(GodspeedError::from_response(response));
     }
+    let created: serde_json::Value = response.json().unwrap_or_default();
+    Ok(created
+        .get("task")
+        .unwrap_or(&created)
+        .get("id")
+        .and_then(|id| id.as_str())
+        .map(|id| id.to_string()))
+}
 
-    // Handle label resolution
-    if !label_names.is_empty() {
-        let mut labels_cache = load_cache(&get_labels_path());
-
-        // Check if we need to fetch labels
-        let mut need_fetch = false;
-        for label_name in &label_names {
-            if find_matching_key(&labels_cache, label_name).is_none() {
-                need_fetch = true;
+/// Send tasks with up to `concurrency` requests in flight, returning each
+/// one's result and time on the network, in order. Ctrl-C stops the wait;
+/// tasks that hadn't been answered come back as cancelled.
+///
+/// The concurrency comes from a few worker threads over the blocking
+/// client, not an async runtime: a handful of requests in flight doesn't
+/// need one, every other call (`cancel::run`, the version check, the URL
+/// title) is built on blocking calls that can be abandoned, and a runtime
+/// would add to the startup time and size of a binary that mostly sends
+/// one task. The wait polls because the Ctrl-C flag can't wake a channel.
+fn send_tasks(
+    tasks: &[(TaskRequest, String)],
+    api: &Api,
+    concurrency: usize,
+) -> Vec<(SendResult, Duration)> {
+    if safe_mode() {
+        return tasks
+            .iter()
+            .map(|_| (Err("Safe mode is on; not sending".into()), Duration::ZERO))
+            .collect();
+    }
+    let bodies: Arc<Vec<(serde_json::Value, String)>> = Arc::new(
+        tasks
+            .iter()
+            .map(|(task, key)| (serde_json::json!(task), key.clone()))
+            .collect(),
+    );
+    let next = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    for _ in 0..concurrency.clamp(1, tasks.len().max(1)) {
+        let (api, bodies, next, sender) =
+            (api.clone(), bodies.clone(), next.clone(), sender.clone());
+        // Like `cancel::run`, the workers only do network I/O, so they can be
+        // abandoned on Ctrl-C
+        thread::spawn(move || loop {
+            let i = next.fetch_add(1, Ordering::SeqCst);
+            if i >= bodies.len() || cancel::is_cancelled() {
                 break;
             }
-        }
-
-        if need_fetch {
-            labels_cache = fetch_labels(api_key)?;
-            save_cache(&get_labels_path(), &labels_cache)?;
-        }
-
-        // Resolve all label names to IDs
-        for label_name in label_names {
-            if let Some(label_id) = find_matching_key(&labels_cache, &label_name) {
-                parsed.label_ids.push(label_id);
+            let started = Instant::now();
+            let (body, key) = &bodies[i];
+            let result = post_task(&api, body, key);
+            if sender.send((i, result, started.elapsed())).is_err() {
+                break;
             }
-        }
+        });
     }
+    drop(sender);
 
-    // Check for multiple lists
-    let list_count = task_str.split_whitespace().filter(|w| w.starts_with('@')).count();
-    if list_count > 1 {
-        send_notification("Error: Multiple lists specified");
-        return Err("Multiple lists specified".into());
+    let mut answers: Vec<Option<_>> = tasks.iter().map(|_| None).collect();
+    loop {
+        match receiver.recv_timeout(Duration::from_millis(50)) {
+            Ok((i, result, network)) => answers[i] = Some((result, network)),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) if cancel::is_cancelled() => break,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        }
     }
 
-    send_task(&parsed, api_key)?;
-    Ok(())
+    // The audit log is a hash chain, so it's written here, in order
+    answers
+        .into_iter()
+        .zip(bodies.iter())
+        .map(|(answer, (body, _))| {
+            let (result, network) = match answer {
+                Some((result, network)) => (result.map_err(Into::into), network),
+                None => (Err("Cancelled".into()), Duration::ZERO),
+            };
+            audit_create(&body.to_string(), &result);
+            (result, network)
+        })
+        .collect()
 }
 
-fn main() {
-    if let Err(e) = ensure_directories() {
-        eprintln!("Failed to create directories: {}", e);
-        return;
+/// Create a named object such as a label, returning its id. `kind` is the
+/// endpoint's singular name (`label` for `/labels`).
+fn create_named(
+    api: &Api,
+    kind: &'static str,
+    name: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if safe_mode() {
+        return Err("Safe mode is on; not sending".into());
     }
+    let api = api.clone();
+    let body = serde_json::json!({ "name": name });
+    let payload = body.to_string();
+    let result = cancel::run(move || {
+        let response = api.send(false, |client| {
+            client
+                .post(api.url(&format!("{}s", kind)))
+                .header("Authorization", format!("Bearer {}", api.key))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })?;
 
-    let api_key = match env::var("GODSPEED_API") {
-        Ok(key) => key,
-        Err(_) => {
-            send_notification("GODSPEED_API environment variable not set");
-            eprintln!("Error: GODSPEED_API environment variable not set");
-            return;
+        if !response.status().is_success() {
+            return Err(GodspeedError::from_response(response).into());
         }
-    };
+        let created: serde_json::Value = response.json().unwrap_or_default();
+        created
+            .get(kind)
+            .unwrap_or(&created)
+            .get("id")
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string())
+            .ok_or_else(|| format!("The API did not return the new {}'s id", kind).into())
+    });
 
-    // Get input from args or stdin
-    let input = {
-        let args: Vec<String> = env::args().skip(1).collect();
-        if !args.is_empty() {
-            // Join all arguments with spaces to handle multi-word input
-            args.join(" ")
-        } else {
-            // Read from stdin
-            let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer).unwrap_or_default();
-            buffer.trim_end().to_string()
+    let command = format!("create {}", kind);
+    match &result {
+        Ok(id) => audit::record(&command, &payload, &format!("created {}", id)),
+        Err(e) => audit::record(&command, &payload, &format!("failed: {}", e)),
+    }
+    result
+}
+
+type FetchFn = fn(&Api, Option<String>) -> Result<Fetched, Box<dyn std::error::Error>>;
+
+/// One name → id cache file, loaded on first use and refreshed from the API at
+/// most once per run
+struct NameCache {
+    /// `list`, `label` or `teammate`, for messages
+    kind: &'static str,
+    path: PathBuf,
+    fetch: FetchFn,
+    entries: Option<HashMap<String, String>>,
+    fetched: bool,
+}
+
+impl NameCache {
+    fn new(kind: &'static str, path: PathBuf, fetch: FetchFn) -> Self {
+        NameCache {
+            kind,
+            path,
+            fetch,
+            entries: None,
+            fetched: false,
+        }
+    }
+
+    /// The name an id is cached under, if it's loaded
+    fn name_of(&self, id: &str) -> Option<&str> {
+        self.entries
+            .as_ref()?
+            .iter()
+            .find(|(_, known)| *known == id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The `ETag` of the names in the cache file, kept next to it. It's
+    /// only trusted while the cache file is there to match it.
+    fn etag_path(&self) -> PathBuf {
+        self.path.with_extension("etag")
+    }
+
+    fn refresh(&mut self, api: &Api) -> Result<(), Box<dyn std::error::Error>> {
+        let etag = if self.path.exists() {
+            fs::read_to_string(self.etag_path())
+                .ok()
+                .map(|etag| etag.trim().to_string())
+                .filter(|etag| !etag.is_empty())
+        } else {
+            None
+        };
+        let fetched = (self.fetch)(api, etag)?;
+        if term::verbose(1) {
+            match &fetched {
+                Fetched::Unchanged => term::debug(&format!("{} cache is current", self.kind)),
+                Fetched::Names { entries, .. } => {
+                    term::debug(&format!("fetched {} {}s", entries.len(), self.kind))
+                }
+            }
+        }
+        match fetched {
+            Fetched::Unchanged => {
+                // Still current, so it isn't stale until max_age_hours from now
+                fs::File::options()
+                    .write(true)
+                    .open(&self.path)?
+                    .set_modified(std::time::SystemTime::now())?;
+                self.entries = Some(load_cache(&self.path));
+            }
+            Fetched::Names { entries, etag } => {
+                let _lock = lock_cache(&self.path)?;
+                save_cache(&self.path, &entries)?;
+                match etag {
+                    Some(etag) => write_atomic(&self.etag_path(), etag)?,
+                    None => match fs::remove_file(self.etag_path()) {
+                        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                        _ => {}
+                    },
+                }
+                self.entries = Some(entries);
+            }
+        }
+        self.fetched = true;
+        Ok(())
+    }
+
+    /// Fetch every name again, even if the server says its `ETag` still
+    /// matches, dropping names that no longer exist
+    fn refetch(&mut self, api: &Api) -> Result<(), Box<dyn std::error::Error>> {
+        match fs::remove_file(self.etag_path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        self.refresh(api)
+    }
+
+    /// Refresh the cache file on another thread if it's older than
+    /// `max_age`. Names it brings in are for later runs; this one keeps the
+    /// ones it has.
+    fn refresh_if_stale(&self, api: &Api, max_age: Duration) -> Option<thread::JoinHandle<()>> {
+        if self.fetched {
+            return None;
+        }
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        if modified.elapsed().is_ok_and(|age| age < max_age) {
+            return None;
+        }
+        let mut cache = NameCache::new(self.kind, self.path.clone(), self.fetch);
+        let api = api.clone();
+        // It's only a refresh, so a failure waits for the next one
+        Some(thread::spawn(move || {
+            let _ = cache.refresh(&api);
+        }))
+    }
+
+    /// Remember an id for a name that was just created. The file is re-read
+    /// first, so names another run added meanwhile are kept.
+    fn insert(&mut self, name: &str, id: &str) -> io::Result<()> {
+        let _lock = lock_cache(&self.path)?;
+        let mut entries = load_cache(&self.path);
+        entries.extend(self.entries.take().unwrap_or_default());
+        entries.insert(name.to_lowercase(), id.to_string());
+        let saved = save_cache(&self.path, &entries);
+        self.entries = Some(entries);
+        saved
+    }
+
+    /// Every cached name, sorted, fetching them first if nothing is cached
+    fn names(&mut self, api: &Api) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let entries = self.entries.get_or_insert_with(|| load_cache(&self.path));
+        if entries.is_empty() && !self.fetched {
+            self.refresh(api)?;
+        }
+        let mut names: Vec<String> = self
+            .entries
+            .iter()
+            .flat_map(|e| e.keys().cloned())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Resolve names to ids, fetching from the API if any name is unknown.
+    /// Also returns the names that still weren't found.
+    fn resolve(
+        &mut self,
+        matching: &MatchingConfig,
+        names: &[String],
+        api: &Api,
+    ) -> Result<(Vec<String>, Vec<String>), Box<dyn std::error::Error>> {
+        let entries = self.entries.get_or_insert_with(|| load_cache(&self.path));
+
+        // Check if we need to fetch
+        let need_fetch = !self.fetched
+            && names
+                .iter()
+                .any(|name| matching.find(entries, name) == Lookup::Missing);
+        for name in names {
+            let found = matching.find(entries, name) != Lookup::Missing;
+            tracing::debug!(
+                kind = self.kind,
+                name = name.as_str(),
+                found,
+                "cache lookup"
+            );
+            if term::verbose(1) {
+                term::debug(&format!(
+                    "{} cache {}: {}",
+                    self.kind,
+                    if found { "hit" } else { "miss" },
+                    name
+                ));
+            }
+        }
+
+        if need_fetch {
+            self.refresh(api)?;
+        }
+
+        let entries = self.entries.as_ref().unwrap();
+        let mut ids = Vec::new();
+        let mut missing = Vec::new();
+        for name in names {
+            let id = match matching.find(entries, name) {
+                Lookup::Found(id) => id,
+                Lookup::Ambiguous(candidates) => {
+                    let chosen = choose_name(self.kind, name, &candidates);
+                    match chosen.and_then(|key| entries.get(&key)) {
+                        Some(id) => id.clone(),
+                        None => {
+                            return Err(Box::new(AmbiguousName {
+                                kind: self.kind,
+                                name: name.clone(),
+                                candidates,
+                            }))
+                        }
+                    }
+                }
+                Lookup::Missing => {
+                    missing.push(name.clone());
+                    continue;
+                }
+            };
+            // The same label can be named twice, e.g. by a sigil and by hand
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        Ok((ids, missing))
+    }
+}
+
+/// A list, label or teammate that doesn't exist, reported in strict mode.
+/// Retrying can't fix it, so the task isn't cached.
+#[derive(Debug)]
+struct UnknownName {
+    kind: &'static str,
+    name: String,
+    /// Similar names that do exist
+    suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for UnknownName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No {} '{}'", self.kind, self.name)?;
+        if !self.suggestions.is_empty() {
+            let quoted: Vec<String> = self
+                .suggestions
+                .iter()
+                .map(|s| format!("'{}'", s))
+                .collect();
+            write!(f, " — did you mean {}?", quoted.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnknownName {}
+
+/// A name that matches several lists, labels or teammates by prefix, when
+/// there's no terminal to ask which one was meant
+#[derive(Debug)]
+struct AmbiguousName {
+    kind: &'static str,
+    name: String,
+    candidates: Vec<String>,
+}
+
+impl std::fmt::Display for AmbiguousName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "'{}' matches several {}s: {}",
+            self.name,
+            self.kind,
+            self.candidates.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousName {}
+
+/// Whether the API refused the task itself, so sending it again can't work
+fn is_rejected(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<GodspeedError>()
+        .is_some_and(GodspeedError::is_rejection)
+}
+
+/// Move a task the API rejected to the dead-letter file rather than losing
+/// what was typed. Returns whether it was rejected.
+fn dead_letter_rejected(task_str: &str, e: &(dyn std::error::Error + 'static)) -> bool {
+    if !is_rejected(e) {
+        return false;
+    }
+    if let Err(err) = queue::dead_letter(task_str, &e.to_string()) {
+        eprintln!("Warning: failed to save the rejected task: {}", err);
+    }
+    true
+}
+
+/// Whether retrying a task can't help, so it shouldn't be cached: names
+/// that don't exist or are ambiguous stay that way until the input changes,
+/// and so does a task the API rejected
+fn is_permanent(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.is::<UnknownName>() || e.is::<AmbiguousName>() || is_rejected(e)
+}
+
+/// Ask on the terminal which of several matching names was meant
+fn choose_name(kind: &str, name: &str, candidates: &[String]) -> Option<String> {
+    let (tty, mut writer) = term::open_tty()?;
+    writeln!(writer, "'{}' matches several {}s:", name, kind).ok()?;
+    for (i, candidate) in candidates.iter().enumerate() {
+        writeln!(writer, "  [{}] {}", i + 1, candidate).ok()?;
+    }
+    write!(writer, "Choice: ").ok()?;
+    writer.flush().ok()?;
+
+    let mut answer = String::new();
+    BufReader::new(tty).read_line(&mut answer).ok()?;
+    let choice = answer.trim().parse::<usize>().ok()?;
+    candidates.get(choice.checked_sub(1)?).cloned()
+}
+
+/// Name → id caches shared by every task processed in one run, so a long queue
+/// or import loads each cache file once and hits the API at most once per kind
+struct Resolver {
+    matching: MatchingConfig,
+    urls: UrlConfig,
+    lists: NameCache,
+    labels: NameCache,
+    members: NameCache,
+    /// List for tasks without `@list`: the nearest `.godspeed` file's, then the config's
+    default_list: Option<String>,
+    /// Labels for tasks without any `.label`
+    default_labels: Vec<String>,
+    /// Minutes for tasks without `:duration`
+    default_duration: Option<i32>,
+    /// Labels added to every capture, such as the git repo's label
+    auto_labels: Vec<String>,
+    receipts: receipt::Receipts,
+    /// Fail instead of ignoring names that can't be resolved
+    strict: bool,
+    /// Create labels that don't exist yet
+    create_labels: bool,
+    create_lists: CreateLists,
+    pick_list: PickList,
+    /// Custom sigils from the config, expanded before parsing
+    sigils: HashMap<String, String>,
+    /// Record what would be created in `planned` instead of creating it
+    dry_run: bool,
+    /// Queue captures without trying the network (`--offline`, or no network)
+    offline: bool,
+    /// Report tasks that couldn't be sent instead of queueing them (`--no-queue`)
+    no_queue: bool,
+    watch: watch::WatchConfig,
+    planned: Vec<plan::Change>,
+    /// Where the current capture's time went, for `metrics`
+    timings: metrics::Timings,
+    /// Tasks created so far, with their ids, for `--json` output
+    created: Vec<serde_json::Value>,
+    /// Capture notes and retry limits for queued tasks
+    queue: queue::QueueConfig,
+    /// Age at which name caches are refreshed in the background
+    cache_max_age: Option<Duration>,
+    /// Requests in flight at once when sending several tasks
+    concurrency: usize,
+}
+
+impl Resolver {
+    fn new(config: Config) -> Self {
+        let mut matching = config.matching;
+        matching.aliases = config
+            .aliases
+            .into_iter()
+            .map(|(alias, name)| (matching::fold(&alias), name))
+            .collect();
+
+        let invalid = sigils::invalid(&config.sigils);
+        if !invalid.is_empty() {
+            eprintln!(
+                "Warning: ignoring sigils that clash with built-in syntax: {}",
+                invalid.join(" ")
+            );
+        }
+
+        let default_duration = config.default_duration.as_deref().and_then(|value| {
+            let minutes = parse_duration(value);
+            if minutes.is_none() {
+                eprintln!("Warning: ignoring invalid default_duration {:?}", value);
+            }
+            minutes
+        });
+
+        Resolver {
+            matching,
+            urls: config.urls,
+            lists: NameCache::new("list", get_lists_path(), fetch_lists),
+            labels: NameCache::new("label", get_labels_path(), fetch_labels),
+            members: NameCache::new("teammate", get_members_path(), fetch_members),
+            default_list: project::find()
+                .and_then(|(_, project)| project.list)
+                .or(config.default_list),
+            default_labels: config.default_labels,
+            default_duration,
+            auto_labels: git::label(&config.git).into_iter().collect(),
+            receipts: receipt::Receipts::new(config.receipts),
+            strict: config.strict,
+            create_labels: config.create_labels,
+            create_lists: config.create_lists,
+            pick_list: config.pick_list,
+            sigils: config.sigils,
+            dry_run: false,
+            offline: false,
+            no_queue: false,
+            planned: Vec::new(),
+            timings: metrics::Timings::default(),
+            created: Vec::new(),
+            queue: config.queue,
+            concurrency: config.api.concurrency.max(1),
+            cache_max_age: (config.cache.max_age_hours > 0)
+                .then(|| Duration::from_secs(config.cache.max_age_hours * 60 * 60)),
+            watch: config.watch,
+        }
+    }
+
+    /// Start refreshing the name caches that are older than `max_age_hours`,
+    /// returning the threads doing it
+    fn refresh_stale(&self, api: &Api) -> Vec<thread::JoinHandle<()>> {
+        let Some(max_age) = self.cache_max_age else {
+            return Vec::new();
+        };
+        [&self.lists, &self.labels, &self.members]
+            .into_iter()
+            .filter_map(|cache| cache.refresh_if_stale(api, max_age))
+            .collect()
+    }
+
+    /// Expand custom sigils in a captured task and add the defaults it doesn't
+    /// override. This happens before queueing, so a retry from another
+    /// directory or with another config sends the same task.
+    fn prepare_capture(&self, task_str: &str) -> String {
+        // JSON tasks are taken as given
+        if task_json::parse(task_str).is_some() {
+            return task_str.to_string();
+        }
+        let task_str = &sigils::expand(task_str, &self.sigils);
+        if self.default_list.is_none()
+            && self.default_labels.is_empty()
+            && self.default_duration.is_none()
+            && self.auto_labels.is_empty()
+        {
+            return task_str.to_string();
+        }
+        let (parent_str, _) = split_subtasks(task_str);
+        let (parsed, refs) = parse_task(&parent_str);
+
+        let mut tokens = Vec::new();
+        if let (Some(list), true) = (&self.default_list, refs.lists.is_empty()) {
+            tokens.push(sigil_token('@', list));
+        }
+        let defaults: &[String] = if refs.labels.is_empty() {
+            &self.default_labels
+        } else {
+            &[]
+        };
+        tokens.extend(defaults.iter().map(|l| sigil_token('.', l)));
+        if let (Some(minutes), None) = (self.default_duration, parsed.duration_minutes) {
+            tokens.push(format!(":{}", minutes));
+        }
+        for label in &self.auto_labels {
+            let typed = refs.labels.iter().chain(defaults);
+            if !typed.into_iter().any(|l| l.eq_ignore_ascii_case(label)) {
+                tokens.push(sigil_token('.', label));
+            }
+        }
+
+        if tokens.is_empty() {
+            task_str.to_string()
+        } else {
+            format!("{} {}", tokens.join(" "), task_str)
+        }
+    }
+
+    /// After the API rejected `task`, fetch again the caches its ids came
+    /// from if the error points at one of them, so a list or label deleted or
+    /// renamed since it was cached stops resolving to its old id. Returns
+    /// whether any cache was refreshed, so resolving again could help.
+    fn purge_stale(
+        &mut self,
+        task: &TaskRequest,
+        e: &(dyn std::error::Error + 'static),
+        api: &Api,
+    ) -> bool {
+        let field = match e.downcast_ref::<GodspeedError>() {
+            Some(GodspeedError::Validation { field, .. }) => field.as_deref(),
+            Some(GodspeedError::NotFound(_)) => None,
+            _ => return false,
+        };
+        let (lists, labels, members) = match field {
+            Some(field) if field.starts_with("list") => (true, false, false),
+            Some(field) if field.starts_with("label") => (false, true, false),
+            Some(field) if field.starts_with("assignee") => (false, false, true),
+            // Some other field was wrong, such as the title
+            Some(_) => return false,
+            None => (true, true, true),
+        };
+        let mut refreshed = false;
+        for (cache, used) in [
+            (&mut self.lists, lists && task.list_id.is_some()),
+            (&mut self.labels, labels && !task.label_ids.is_empty()),
+            (&mut self.members, members && task.assignee_id.is_some()),
+        ] {
+            // Fetched this run, so its ids are already current
+            if !used || cache.fetched {
+                continue;
+            }
+            match cache.refetch(api) {
+                Ok(()) => {
+                    if !term::json() {
+                        eprintln!(
+                            "Cached {}s were out of date; fetched them again",
+                            cache.kind
+                        );
+                    }
+                    refreshed = true;
+                }
+                Err(e) => eprintln!("Warning: failed to refresh the {} cache: {}", cache.kind, e),
+            }
+        }
+        refreshed
+    }
+
+    /// Note a task that was just created, for receipts, `--json` output and
+    /// the success notification
+    fn record_created(&mut self, task: &TaskRequest, id: Option<&str>) {
+        tracing::info!(id, title = task.title.as_str(), "created");
+        self.receipts.record(task, id);
+        if !term::json() && !term::quiet() && io::stderr().is_terminal() {
+            let indent = if task.parent_id.is_some() { "  " } else { "" };
+            eprintln!(
+                "{}{} {}",
+                indent,
+                term::paint_err(Style::Success, "✓"),
+                self.confirmation(task)
+            );
+        }
+        if task.parent_id.is_none() {
+            let link = id.map(task_url);
+            notify::success(&format!("Added {}", self.summarize(task)), link.as_deref());
+        }
+        let mut created = serde_json::to_value(task).unwrap_or_default();
+        created["id"] = serde_json::json!(id);
+        created["url"] = serde_json::json!(id.map(task_url));
+        self.created.push(created);
+    }
+
+    /// A task's title, with its list and duration when it has them: `'Buy
+    /// milk' to Errands, 15m`
+    fn summarize(&self, task: &TaskRequest) -> String {
+        let mut summary = format!("'{}'", task.title);
+        if let Some(id) = &task.list_id {
+            summary.push_str(" to ");
+            summary.push_str(self.lists.name_of(id).unwrap_or(id));
+        }
+        if let Some(minutes) = task.duration_minutes {
+            summary.push_str(", ");
+            summary.push_str(&format_duration(minutes));
+        }
+        summary
+    }
+
+    /// A created task for the terminal, its title then what it was filed
+    /// under in capture syntax: `Buy milk @errands .shop :15m`
+    fn confirmation(&self, task: &TaskRequest) -> String {
+        let mut details = Vec::new();
+        if let Some(id) = &task.list_id {
+            details.push(format!("@{}", self.lists.name_of(id).unwrap_or(id)));
+        }
+        for id in &task.label_ids {
+            details.push(format!(".{}", self.labels.name_of(id).unwrap_or(id)));
+        }
+        if let Some(minutes) = task.duration_minutes {
+            details.push(format!(":{}", format_duration(minutes)));
+        }
+        if details.is_empty() {
+            return task.title.clone();
+        }
+        format!(
+            "{} {}",
+            task.title,
+            term::paint_err(Style::Detail, &details.join(" "))
+        )
+    }
+
+    /// Let the user pick a list for a capture that doesn't name one, and with
+    /// `pick_list = "always"` labels too. The choices are added to the text
+    /// as `@list` and `.label` so retries from the queue stay the same.
+    fn pick_missing(&mut self, task_str: &str, api: &Api) -> String {
+        if task_json::parse(task_str).is_some() {
+            return task_str.to_string();
+        }
+        let pick_labels = self.pick_list == PickList::Always;
+        let pick_list = match self.pick_list {
+            PickList::Always => true,
+            PickList::Missing => self.default_list.is_none() && io::stdin().is_terminal(),
+            PickList::Never => false,
+        };
+        if !pick_list {
+            return task_str.to_string();
+        }
+
+        let expanded = sigils::expand(task_str, &self.sigils);
+        let (parent_str, _) = split_subtasks(&expanded);
+        let (_, refs) = parse_task(&parent_str);
+
+        let mut tokens = Vec::new();
+        if refs.lists.is_empty() {
+            let names = self.lists.names(api).unwrap_or_default();
+            if let Some(list) =
+                picker::pick("list", &names, false).and_then(|l| l.into_iter().next())
+            {
+                tokens.push(sigil_token('@', &list));
+            }
+        }
+        if pick_labels && refs.labels.is_empty() {
+            let names = self.labels.names(api).unwrap_or_default();
+            for label in picker::pick("label", &names, true).unwrap_or_default() {
+                tokens.push(sigil_token('.', &label));
+            }
+        }
+
+        if tokens.is_empty() {
+            task_str.to_string()
+        } else {
+            format!("{} {}", tokens.join(" "), task_str)
+        }
+    }
+
+    /// Fail on the first missing name in strict mode; otherwise warn about them
+    fn check_missing(
+        &self,
+        kind: &'static str,
+        missing: Vec<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let cache = match kind {
+            "list" => &self.lists,
+            "label" => &self.labels,
+            _ => &self.members,
+        };
+        for name in missing {
+            let suggestions = cache
+                .entries
+                .as_ref()
+                .map(|entries| matching::suggest(entries, &name))
+                .unwrap_or_default();
+            let unknown = UnknownName {
+                kind,
+                name,
+                suggestions,
+            };
+            if self.strict {
+                return Err(Box::new(unknown));
+            }
+            eprintln!("Warning: {} (left off the task)", unknown);
+        }
+        Ok(())
+    }
+
+    /// Create a missing label or list, or plan to in a dry run, returning its id
+    fn create(
+        &mut self,
+        kind: &'static str,
+        name: String,
+        api: &Api,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        // Create what an alias points to, not the alias itself
+        let name = self
+            .matching
+            .aliases
+            .get(&matching::fold(&name))
+            .cloned()
+            .unwrap_or(name);
+        let cache = if kind == "list" {
+            &mut self.lists
+        } else {
+            &mut self.labels
+        };
+
+        // Already created for an earlier spelling of the same name
+        let known = cache.entries.as_ref();
+        if let Some(id) = known.and_then(|entries| entries.get(&name.to_lowercase())) {
+            return Ok(Some(id.clone()));
+        }
+
+        if self.dry_run {
+            let planned = self.planned.iter().any(|change| match change {
+                plan::Change::CreateLabel { name: n } => {
+                    kind == "label" && n.eq_ignore_ascii_case(&name)
+                }
+                plan::Change::CreateList { name: n } => {
+                    kind == "list" && n.eq_ignore_ascii_case(&name)
+                }
+                _ => false,
+            });
+            if !planned {
+                self.planned.push(if kind == "list" {
+                    plan::Change::CreateList { name }
+                } else {
+                    plan::Change::CreateLabel { name }
+                });
+            }
+            return Ok(None);
+        }
+
+        let id = create_named(api, kind, &name)?;
+        eprintln!("Created {} \"{}\"", kind, name);
+        cache.insert(&name, &id)?;
+        Ok(Some(id))
+    }
+
+    fn resolve_list(
+        &mut self,
+        name: &str,
+        api: &Api,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let (ids, missing) = self
+            .lists
+            .resolve(&self.matching, &[name.to_string()], api)?;
+        let Some(name) = missing.into_iter().next() else {
+            return Ok(ids.into_iter().next());
+        };
+
+        let create = match self.create_lists {
+            CreateLists::Always => true,
+            CreateLists::Ask => !self.dry_run && confirm_create_list(&name),
+            CreateLists::Never => false,
+        };
+        if create {
+            self.create("list", name, api)
+        } else {
+            self.check_missing("list", vec![name])?;
+            Ok(None)
+        }
+    }
+
+    fn resolve_labels(
+        &mut self,
+        names: &[String],
+        api: &Api,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let (mut ids, missing) = self.labels.resolve(&self.matching, names, api)?;
+        if !self.create_labels {
+            self.check_missing("label", missing)?;
+            return Ok(ids);
+        }
+
+        for name in missing {
+            if let Some(id) = self.create("label", name, api)? {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn resolve_member(
+        &mut self,
+        name: &str,
+        api: &Api,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let (ids, missing) = self
+            .members
+            .resolve(&self.matching, &[name.to_string()], api)?;
+        self.check_missing("teammate", missing)?;
+        Ok(ids.into_iter().next())
+    }
+}
+
+/// Ask on the terminal whether to create a list that doesn't exist. Without a
+/// terminal, as in scripts and hotkeys, the answer is no.
+fn confirm_create_list(name: &str) -> bool {
+    let Some((tty, mut writer)) = term::open_tty() else {
+        return false;
+    };
+    if write!(
+        writer,
+        "List \"{}\" doesn't exist. Create it? [y/N]: ",
+        name
+    )
+    .is_err()
+    {
+        return false;
+    }
+    let _ = writer.flush();
+
+    let mut answer = String::new();
+    if BufReader::new(tty).read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Split off subtasks: `>` separated segments on the first line
+/// (`plan trip > book flight`) and indented lines after it
+fn split_subtasks(task_str: &str) -> (String, Vec<String>) {
+    if task_json::parse(task_str).is_some() {
+        return (task_str.to_string(), Vec::new());
+    }
+    let mut lines = task_str.lines();
+    let first_line = lines.next().unwrap_or_default();
+
+    // A `>` after a raw `--` marker is part of the title
+    let (tokens_part, raw_part) = match find_raw_marker(first_line) {
+        Some(pos) => first_line.split_at(pos),
+        None => (first_line, ""),
+    };
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for pos in find_unescaped(tokens_part, " > ") {
+        segments.push(tokens_part[start..pos].trim().to_string());
+        start = pos + " > ".len();
+    }
+    segments.push(tokens_part[start..].trim().to_string());
+    let mut parent = segments.remove(0);
+    let mut subtasks = segments;
+    if !raw_part.is_empty() {
+        // The raw title belongs to whichever task the marker ended up in
+        let last = subtasks.last_mut().unwrap_or(&mut parent);
+        last.push(' ');
+        last.push_str(raw_part);
+    }
+
+    for line in lines {
+        if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            let item = line.trim();
+            let item = item
+                .strip_prefix("- ")
+                .or_else(|| item.strip_prefix("* "))
+                .unwrap_or(item);
+            subtasks.push(item.to_string());
+        } else {
+            parent.push('\n');
+            parent.push_str(line);
+        }
+    }
+
+    subtasks.retain(|s| !s.is_empty());
+    // Subtasks need something to go under, so without a parent nothing splits
+    if parent.trim().is_empty() && !subtasks.is_empty() {
+        return (task_str.to_string(), Vec::new());
+    }
+    (parent, subtasks)
+}
+
+fn resolve_task(
+    task_str: &str,
+    api: &Api,
+    resolver: &mut Resolver,
+) -> Result<TaskRequest, Box<dyn std::error::Error>> {
+    let started = Instant::now();
+    let result = resolve_parsed(task_str, api, resolver);
+    resolver.timings.resolve += started.elapsed();
+    result
+}
+
+fn resolve_parsed(
+    task_str: &str,
+    api: &Api,
+    resolver: &mut Resolver,
+) -> Result<TaskRequest, Box<dyn std::error::Error>> {
+    if let Some(input) = task_json::parse(task_str) {
+        return resolve_input(input, api, resolver);
+    }
+
+    let started = Instant::now();
+    let (mut parsed, refs) = parse_task(task_str);
+    resolver.timings.parse += started.elapsed();
+
+    // Handle URLs in the title
+    if resolver.urls.move_to_notes || resolver.urls.fetch_title {
+        let (title, found) = urls::extract_urls(&parsed.title);
+        let page_title = match found.as_slice() {
+            [url] if title.is_empty() && resolver.urls.fetch_title => api
+                .page_client()
+                .ok()
+                .and_then(|client| urls::fetch_page_title(url, client)),
+            _ => None,
+        };
+
+        if !found.is_empty() && (resolver.urls.move_to_notes || page_title.is_some()) {
+            // A title is required, so a bare URL stays put unless its page title was found
+            if let Some(page_title) = page_title {
+                parsed.title = page_title;
+            } else if !title.is_empty() {
+                parsed.title = title;
+            }
+            if parsed.title != found.join(" ") {
+                let links = found.join("\n");
+                parsed.notes = if parsed.notes.is_empty() {
+                    links
+                } else {
+                    format!("{}\n{}", links, parsed.notes)
+                };
+            }
+        }
+    }
+
+    // Check for multiple lists
+    if refs.lists.len() > 1 {
+        notify::failure("Error: Multiple lists specified");
+        return Err("Multiple lists specified".into());
+    }
+
+    // Handle list resolution
+    if let Some(list_name_clean) = refs.lists.first() {
+        parsed.list_id = resolver.resolve_list(list_name_clean, api)?;
+    }
+
+    // Handle label resolution
+    if !refs.labels.is_empty() {
+        parsed.label_ids = resolver.resolve_labels(&refs.labels, api)?;
+    }
+
+    // Handle assignee resolution
+    if let Some(member_name) = &refs.assignee {
+        parsed.assignee_id = resolver.resolve_member(member_name, api)?;
+    }
+
+    Ok(parsed)
+}
+
+/// Resolve the names in a JSON task, keeping any ids it gives directly
+fn resolve_input(
+    input: task_json::TaskInput,
+    api: &Api,
+    resolver: &mut Resolver,
+) -> Result<TaskRequest, Box<dyn std::error::Error>> {
+    let list_id = match &input.list {
+        Some(name) => resolver.resolve_list(name, api)?,
+        None => input.list_id,
+    };
+    let mut label_ids = input.label_ids;
+    if !input.labels.is_empty() {
+        for id in resolver.resolve_labels(&input.labels, api)? {
+            if !label_ids.contains(&id) {
+                label_ids.push(id);
+            }
+        }
+    }
+    let assignee_id = match &input.assignee {
+        Some(name) => resolver.resolve_member(name, api)?,
+        None => input.assignee_id,
+    };
+
+    Ok(TaskRequest {
+        title: input.title,
+        list_id,
+        duration_minutes: input.duration_minutes,
+        label_ids,
+        notes: input.notes,
+        timeless_due_at: input.timeless_due_at,
+        timeless_starts_at: input.timeless_starts_at,
+        priority: input.priority,
+        recurrence_rule: input.recurrence_rule,
+        parent_id: None,
+        assignee_id,
+    })
+}
+
+/// Retry cached tasks, returning how many were sent and how many were tried.
+/// Tasks still backing off after a failure wait unless `force` is set, as
+/// for an explicit `sync`.
+fn flush_queue(api: &Api, resolver: &mut Resolver, force: bool) -> (usize, usize) {
+    // Skip reading the queue entirely on the common empty-queue path
+    if queue::is_empty() {
+        return (0, 0);
+    }
+    // The API asked for a pause; a capture may still try, but not the backlog
+    if let Some(until) = queue::rate_limited_until() {
+        if force {
+            eprintln!(
+                "The API is rate limiting requests until {}; try again then",
+                dates::format_local(until)
+            );
+        }
+        return (0, 0);
+    }
+    if let Err(e) = queue::prune(&resolver.queue) {
+        eprintln!("Warning: failed to prune the offline queue: {}", e);
+    }
+    // Another run already sending the queue will get to everything; an
+    // explicit sync waits for it instead of returning straight away
+    let _flushing = match queue::flush_lock(force) {
+        Ok(Some(lock)) => lock,
+        Ok(None) => return (0, 0),
+        Err(e) => {
+            eprintln!("Warning: failed to lock the offline queue: {}", e);
+            return (0, 0);
+        }
+    };
+
+    let now = dates::now_unix();
+    let entries: Vec<queue::Entry> = queue::load()
+        .into_iter()
+        .filter(|entry| force || entry.is_due(now))
+        .collect();
+    let tried = entries.len();
+    let mut sent = 0;
+    let inputs: Vec<Capture> = entries
+        .iter()
+        .map(|entry| Capture::queued(entry, resolver.queue.capture_note))
+        .collect();
+    let results = process_tasks(&inputs, api, resolver);
+    for (entry, result) in entries.into_iter().zip(results) {
+        let outcome = match result {
+            Ok(_) => {
+                sent += 1;
+                queue::Outcome::Sent
+            }
+            // Left as it was, to be sent next time
+            Err(_) if cancel::is_cancelled() => continue,
+            Err(e) if is_permanent(e.as_ref()) => queue::Outcome::Permanent(e.to_string()),
+            Err(e) => queue::Outcome::Failed(e.to_string()),
+        };
+        match queue::update(&entry, outcome, &resolver.queue) {
+            Ok(true) => eprintln!(
+                "Gave up on \"{}\"; it was moved to {}",
+                entry.input.lines().next().unwrap_or_default(),
+                queue::get_dead_letter_path().display()
+            ),
+            Ok(false) => {}
+            Err(e) => eprintln!("Warning: failed to update the offline queue: {}", e),
+        }
+    }
+    if sent > 0 {
+        notify::flushed(&format!(
+            "Sent {} cached task{}",
+            sent,
+            if sent == 1 { "" } else { "s" }
+        ));
+    }
+    (sent, tried)
+}
+
+/// Start `--flush-only` in a detached process with the same sending options,
+/// so this run can exit while the queue is sent. False if it couldn't start.
+fn spawn_flush(options: &Options) -> bool {
+    let Ok(exe) = env::current_exe() else {
+        return false;
+    };
+    let mut command = Command::new(exe);
+    command
+        .arg("--flush-only")
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    let flags = [
+        (options.strict, "--strict"),
+        (options.create_labels, "--create-labels"),
+        (options.create_lists, "--create-lists"),
+        (options.url_notes, "--url-notes"),
+        (options.fetch_title, "--fetch-title"),
+        (options.safe, "--safe"),
+        (options.offline, "--offline"),
+        (options.no_notify, "--silent"),
+        (options.verbosity < 0, "--quiet"),
+        (options.verbosity == 1, "-v"),
+        (options.verbosity > 1, "-vv"),
+        (mock::enabled(), "--mock"),
+    ];
+    for (set, flag) in flags {
+        if set {
+            command.arg(flag);
+        }
+    }
+    // Without these it reads the same environment and config as this run
+    if let Some(timeout) = options.timeout {
+        command.args(["--timeout", &timeout.as_secs().to_string()]);
+    }
+    if let Some(url) = &options.api_url {
+        command.args(["--api-url", url]);
+    }
+    if let Some(name) = &options.profile {
+        command.args(["--profile", name]);
+    }
+    // Its receipts go after this run's, rather than replacing them
+    if let Some(path) = &options.receipt {
+        command.arg("--append-receipt").arg(path);
+    }
+    // Its own process group, so Ctrl-C in the terminal doesn't reach it
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    #[cfg(windows)]
+    {
+        // CREATE_NEW_PROCESS_GROUP, and CREATE_NO_WINDOW so no console flashes up
+        const FLAGS: u32 = 0x0000_0200 | 0x0800_0000;
+        std::os::windows::process::CommandExt::creation_flags(&mut command, FLAGS);
+    }
+    command.spawn().is_ok()
+}
+
+/// Input to send, with what stays the same each time it's tried
+struct Capture {
+    input: String,
+    /// Added below the notes of a task sent late from the queue
+    note: Option<String>,
+    /// Sent as `Idempotency-Key`; each subtask adds its number
+    key: String,
+    /// The existing parent of a subtask queued after its parent was created
+    parent: Option<queue::Parent>,
+}
+
+impl Capture {
+    fn new(input: &str) -> Self {
+        Capture {
+            input: input.to_string(),
+            note: None,
+            key: queue::new_idempotency_key(input),
+            parent: None,
+        }
+    }
+
+    fn queued(entry: &queue::Entry, capture_note: bool) -> Self {
+        Capture {
+            input: entry.input.clone(),
+            note: entry.capture_note().filter(|_| capture_note),
+            key: entry.idempotency_key(),
+            parent: entry.parent.clone(),
+        }
+    }
+
+    /// Finish a resolved task with what the capture keeps between tries:
+    /// the capture note, and the parent of a queued subtask
+    fn prepare(&self, task: &mut TaskRequest) {
+        add_note(task, self.note.as_deref());
+        if let Some(parent) = &self.parent {
+            task.parent_id = Some(parent.id.clone());
+            if task.list_id.is_none() {
+                task.list_id = parent.list_id.clone();
+            }
+        }
+    }
+}
+
+/// Send a task and its subtasks, returning the created parent's id
+fn process_task(
+    capture: &Capture,
+    api: &Api,
+    resolver: &mut Resolver,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let (parent_str, subtasks) = split_subtasks(&capture.input);
+    let (parent, parent_id) = send_resolved(&parent_str, &capture.key, api, resolver, |parent| {
+        capture.prepare(parent)
+    })?;
+    resolver.record_created(&parent, parent_id.as_deref());
+
+    if !subtasks.is_empty() && parent_id.is_none() {
+        eprintln!("Warning: the API did not return the parent task id; subtasks are created as separate tasks");
+    }
+
+    // The parent exists now, so a failing subtask is cached on its own
+    // rather than failing the whole input and duplicating the parent on retry
+    let mut unsent = 0;
+    for (i, subtask_str) in subtasks.into_iter().enumerate() {
+        let key = format!("{}-{}", capture.key, i + 1);
+        let result = send_resolved(&subtask_str, &key, api, resolver, |subtask| {
+            subtask.parent_id = parent_id.clone();
+            if subtask.list_id.is_none() {
+                subtask.list_id = parent.list_id.clone();
+            }
+        })
+        .map(|(subtask, id)| {
+            resolver.record_created(&subtask, id.as_deref());
+            id
+        });
+        if let Err(e) = result {
+            tracing::error!(input = subtask_str.as_str(), error = %e, "subtask not sent");
+            eprintln!("Failed to send subtask \"{}\": {}", subtask_str, e);
+            if resolver.no_queue {
+                unsent += 1;
+            } else if !dead_letter_rejected(&subtask_str, e.as_ref()) && !is_permanent(e.as_ref()) {
+                let entry = unsent_subtask(&subtask_str, &key, parent_id.as_deref(), &parent);
+                requeue(entry, resolver);
+            }
+        }
+    }
+
+    if unsent > 0 {
+        return Err(format!(
+            "the task was created, but {} of its subtasks weren't",
+            unsent
+        )
+        .into());
+    }
+    Ok(parent_id)
+}
+
+/// Queue a capture that wasn't sent, returning whether that added it. One
+/// already waiting, or a queue that can't be written, is reported instead.
+fn requeue(entry: queue::Entry, resolver: &Resolver) -> bool {
+    let title = entry.input.lines().next().unwrap_or_default().to_string();
+    match queue::push_entry(entry, &resolver.queue) {
+        Ok(true) => true,
+        Ok(false) => {
+            if !term::json() && !term::quiet() {
+                eprintln!("Already queued: {}", title);
+            }
+            false
+        }
+        Err(e) => {
+            eprintln!("Warning: failed to queue \"{}\": {}", title, e);
+            false
+        }
+    }
+}
+
+/// The queue entry for a subtask that failed after its parent was created,
+/// keeping the parent so the retry is filed under it rather than on its own
+fn unsent_subtask(
+    subtask_str: &str,
+    key: &str,
+    parent_id: Option<&str>,
+    parent: &TaskRequest,
+) -> queue::Entry {
+    queue::Entry {
+        parent: parent_id.map(|id| queue::Parent {
+            id: id.to_string(),
+            list_id: parent.list_id.clone(),
+        }),
+        ..queue::Entry::attempted(subtask_str, key)
+    }
+}
+
+/// Add a queued task's capture note below its own notes
+fn add_note(task: &mut TaskRequest, note: Option<&str>) {
+    if let Some(note) = note {
+        if !task.notes.is_empty() {
+            task.notes.push('\n');
+        }
+        task.notes.push_str(note);
+    }
+}
+
+/// Send several captures, each with its queued note, as `process_task`
+/// would one at a time, but with the requests for the ones without subtasks
+/// in flight together. Captures with subtasks follow, one at a time, since
+/// each subtask waits for its parent's id. Results are in input order.
+fn process_tasks(inputs: &[Capture], api: &Api, resolver: &mut Resolver) -> Vec<SendResult> {
+    let mut results: Vec<Option<SendResult>> = inputs.iter().map(|_| None).collect();
+
+    // Names are resolved here, one at a time, since that can ask on the
+    // terminal or create lists and labels
+    let mut batch = Vec::new();
+    for (i, capture) in inputs.iter().enumerate() {
+        if !split_subtasks(&capture.input).1.is_empty() {
+            continue;
+        }
+        match resolve_task(&capture.input, api, resolver) {
+            Ok(mut task) => {
+                capture.prepare(&mut task);
+                batch.push((i, (task, capture.key.clone())));
+            }
+            Err(e) => results[i] = Some(Err(e)),
+        }
+    }
+
+    let (indices, tasks): (Vec<usize>, Vec<(TaskRequest, String)>) = batch.into_iter().unzip();
+    let sent = send_tasks(&tasks, api, resolver.concurrency);
+    for ((i, (task, _)), (result, network)) in indices.into_iter().zip(tasks).zip(sent) {
+        resolver.timings.network += network;
+        let capture = &inputs[i];
+        let result = match result {
+            Err(e) if resolver.purge_stale(&task, e.as_ref(), api) => {
+                send_resolved(&capture.input, &capture.key, api, resolver, |task| {
+                    capture.prepare(task)
+                })
+            }
+            result => result.map(|id| (task, id)),
+        };
+        results[i] = Some(result.map(|(task, id)| {
+            resolver.record_created(&task, id.as_deref());
+            id
+        }));
+    }
+
+    inputs
+        .iter()
+        .zip(results)
+        .map(|(capture, result)| {
+            result.unwrap_or_else(|| {
+                if cancel::is_cancelled() {
+                    return Err("Cancelled".into());
+                }
+                process_task(capture, api, resolver)
+            })
+        })
+        .collect()
+}
+
+/// Resolve a task, let `prepare` finish it and send it. When the API
+/// rejects an id that came from a cache, the cache is fetched again and the
+/// task resolved and sent once more.
+fn send_resolved(
+    task_str: &str,
+    key: &str,
+    api: &Api,
+    resolver: &mut Resolver,
+    prepare: impl Fn(&mut TaskRequest),
+) -> Result<(TaskRequest, Option<String>), Box<dyn std::error::Error>> {
+    let mut task = resolve_task(task_str, api, resolver)?;
+    prepare(&mut task);
+    let started = Instant::now();
+    let sent = send_task(&task, key, api);
+    resolver.timings.network += started.elapsed();
+    match sent {
+        Err(e) if resolver.purge_stale(&task, e.as_ref(), api) => {
+            let mut task = resolve_task(task_str, api, resolver)?;
+            prepare(&mut task);
+            let started = Instant::now();
+            let sent = send_task(&task, key, api);
+            resolver.timings.network += started.elapsed();
+            Ok((task, sent?))
+        }
+        sent => Ok((task, sent?)),
+    }
+}
+
+/// Resolve a task and its subtasks without sending anything, for `--dry-run`
+fn plan_task(
+    task_str: &str,
+    api: &Api,
+    resolver: &mut Resolver,
+) -> Result<Vec<plan::Change>, Box<dyn std::error::Error>> {
+    let (parent_str, subtasks) = split_subtasks(task_str);
+    let parent = resolve_task(&parent_str, api, resolver)?;
+
+    let mut tasks = Vec::new();
+    for subtask_str in subtasks {
+        let mut subtask = resolve_task(&subtask_str, api, resolver)?;
+        if subtask.list_id.is_none() {
+            subtask.list_id = parent.list_id.clone();
+        }
+        tasks.push(plan::Change::Create {
+            task: Box::new(subtask),
+            parent: Some(parent.title.clone()),
+        });
+    }
+
+    // Labels are created first, since the tasks refer to them
+    let mut changes: Vec<plan::Change> = resolver.planned.drain(..).collect();
+    changes.push(plan::Change::Create {
+        task: Box::new(parent),
+        parent: None,
+    });
+    changes.extend(tasks);
+    Ok(changes)
+}
+
+/// How multi-line input should be turned into tasks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PasteMode {
+    /// The first line is the title, the remaining lines become notes
+    TitleNotes,
+    /// Every non-empty line becomes its own task
+    PerLine,
+    /// All lines form a single task, as if typed on one line
+    Joined,
+}
+
+fn ask_paste_mode(line_count: usize) -> Option<PasteMode> {
+    // Read the answer from the terminal directly, since stdin may be the pasted content
+    let (tty, mut writer) = term::open_tty()?;
+    write!(
+        writer,
+        "Input has {} lines. Create:\n  [1] one task: first line as title, rest as notes\n  [2] one task per line\n  [3] one task with all lines joined\nChoice [1]: ",
+        line_count
+    )
+    .ok()?;
+    writer.flush().ok()?;
+
+    let mut answer = String::new();
+    BufReader::new(tty).read_line(&mut answer).ok()?;
+    match answer.trim() {
+        "" | "1" => Some(PasteMode::TitleNotes),
+        "2" => Some(PasteMode::PerLine),
+        "3" => Some(PasteMode::Joined),
+        _ => None,
+    }
+}
+
+fn split_input(input: &str, mode: PasteMode) -> Vec<String> {
+    let lines: Vec<&str> = input
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    match mode {
+        // parse_task already treats lines after the first as notes
+        PasteMode::TitleNotes => vec![input.to_string()],
+        PasteMode::PerLine => lines.iter().map(|l| l.to_string()).collect(),
+        PasteMode::Joined => vec![lines.join(" ")],
+    }
+}
+
+struct Options {
+    stdin_lines: bool,
+    url_notes: bool,
+    fetch_title: bool,
+    dry_run: bool,
+    safe: bool,
+    mock: bool,
+    strict: bool,
+    create_labels: bool,
+    create_lists: bool,
+    pick: bool,
+    allow_duplicates: bool,
+    offline: bool,
+    no_queue: bool,
+    json: bool,
+    /// Read tasks from stdin as JSON (`--format json`)
+    json_input: bool,
+    /// Only send the queue, quietly; what a capture starts in the background
+    flush_only: bool,
+    /// Over `GODSPEED_TIMEOUT` and `[api] timeout_secs`
+    timeout: Option<Duration>,
+    /// Over `GODSPEED_API_URL` and the config's `base_url`
+    api_url: Option<String>,
+    /// Over `GODSPEED_PROFILE` and `profile use`
+    profile: Option<String>,
+    /// Skip desktop notifications (`--no-notify` or `--silent`)
+    no_notify: bool,
+    /// Plain output whatever `[output] color` says (`--no-color`)
+    no_color: bool,
+    /// -1 with `-q`, or how many times `-v` was given
+    verbosity: i8,
+    /// `--help`: print the usage and exit
+    help: bool,
+    /// `--version`: print the version and exit
+    version: bool,
+    receipt: Option<PathBuf>,
+    /// `--append-receipt`: add to the receipt file instead of starting it over
+    append_receipt: bool,
+    /// `--var name=value` pairs for `template apply`
+    vars: Vec<(String, String)>,
+    fields: TaskFlags,
+    words: Vec<String>,
+}
+
+/// Task fields given as `--title`, `--list` and so on, which never go through
+/// the shorthand parser, so scripts don't have to worry about `@` or `.` in
+/// their text
+#[derive(Default)]
+struct TaskFlags {
+    title: Option<String>,
+    list: Option<String>,
+    labels: Vec<String>,
+    minutes: Option<i32>,
+    notes: Option<String>,
+    /// ISO date, resolved when the flags are read so a queued retry keeps it
+    due: Option<String>,
+}
+
+impl TaskFlags {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.list.is_none()
+            && self.labels.is_empty()
+            && self.minutes.is_none()
+            && self.notes.is_none()
+            && self.due.is_none()
+    }
+
+    /// The fields as task text that parses back to exactly these fields: names
+    /// as escaped tokens, and the title after a raw `--` marker
+    fn to_task_text(&self) -> Result<String, String> {
+        let title = self
+            .title
+            .as_deref()
+            .map(|title| title.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|title| !title.is_empty())
+            .ok_or("--title is required with --list, --label, --minutes, --notes or --due")?;
+
+        let mut tokens: Vec<String> = Vec::new();
+        tokens.extend(self.list.iter().map(|list| sigil_token('@', list)));
+        tokens.extend(self.labels.iter().map(|label| sigil_token('.', label)));
+        tokens.extend(self.minutes.map(|minutes| format!(":{}", minutes)));
+        tokens.extend(self.due.iter().map(|due| format!("^{}", due)));
+        tokens.push("--".to_string());
+        tokens.push(title);
+        let mut text = tokens.join(" ");
+
+        if let Some(notes) = &self.notes {
+            // Indented lines would be read as subtasks
+            for line in notes.lines() {
+                text.push('\n');
+                text.push_str(line.trim_start());
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Options only a command reads, passed through to it as words
+const COMMAND_OPTIONS: &[&str] = &["-f", "--file", "--from-snapshot", "--once"];
+
+/// A lone word shaped like a flag (`-x`, `--xxx`) that nothing knows.
+/// Quoted task text with spaces, `-` and negative numbers are still words.
+fn is_unknown_option(arg: &str) -> bool {
+    let shaped = arg
+        .strip_prefix('-')
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|next| next == '-' || next.is_alphabetic());
+    shaped && !arg.contains(char::is_whitespace) && !COMMAND_OPTIONS.contains(&arg)
+}
+
+const USAGE: &str = "\
+Usage: godspeed-cli [options] [task text]
+       godspeed-cli <command> [args]
+
+Captures a task from the arguments or stdin, e.g.
+  godspeed-cli \"Buy milk @errands .shop :15m ^tomorrow\"
+
+Commands:
+  sync, queue [list|retry|remove|clear|edit]   send or manage cached tasks
+  auth [login|logout], whoami, init            set up the API key
+  config [path|get|set|unset|edit]             manage the config file
+  profile [show|list|check|use]                switch between accounts
+  template [list|apply NAME]                   capture from a template
+  refresh, restore, watch, metrics, audit, daemon, shell-init
+
+Options:
+  --dry-run        show what would be sent without sending it
+  --offline        queue the task instead of sending it
+  --profile NAME   use another profile
+  --json           machine-readable output
+  -q, -v, -vv      less or more output
+  -h, --help       print this help
+  -V, --version    print the version
+
+Text after -- is always task text. See the README for every option.
+";
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        stdin_lines: false,
+        url_notes: false,
+        fetch_title: false,
+        dry_run: false,
+        safe: false,
+        mock: false,
+        strict: false,
+        create_labels: false,
+        create_lists: false,
+        pick: false,
+        allow_duplicates: false,
+        offline: false,
+        no_queue: false,
+        json: false,
+        json_input: false,
+        flush_only: false,
+        timeout: None,
+        api_url: None,
+        profile: None,
+        no_notify: false,
+        no_color: false,
+        verbosity: 0,
+        help: false,
+        version: false,
+        receipt: None,
+        append_receipt: false,
+        vars: Vec::new(),
+        fields: TaskFlags::default(),
+        words: Vec::new(),
+    };
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stdin-lines" => options.stdin_lines = true,
+            "--dry-run" => options.dry_run = true,
+            "--safe" => options.safe = true,
+            "--mock" => options.mock = true,
+            "--strict" => options.strict = true,
+            "--pick" => options.pick = true,
+            "--allow-duplicates" => options.allow_duplicates = true,
+            "--offline" => options.offline = true,
+            "--no-queue" => options.no_queue = true,
+            "--create-labels" => options.create_labels = true,
+            "--create-lists" => options.create_lists = true,
+            "--url-notes" => options.url_notes = true,
+            "--fetch-title" => options.fetch_title = true,
+            "--json" => options.json = true,
+            "-h" | "--help" => options.help = true,
+            "-V" | "--version" => options.version = true,
+            "--flush-only" => options.flush_only = true,
+            "--timeout" => {
+                let value = args
+                    .next()
+                    .ok_or("--timeout requires a number of seconds")?;
+                let secs = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid --timeout value: {}", value))?;
+                options.timeout = Some(Duration::from_secs(secs));
+            }
+            "--api-url" => {
+                let url = args.next().ok_or("--api-url requires a URL")?;
+                options.api_url = Some(url);
+            }
+            "--no-notify" | "--silent" => options.no_notify = true,
+            "--no-color" => options.no_color = true,
+            "-q" | "--quiet" => options.verbosity = -1,
+            "-v" | "--verbose" => options.verbosity = options.verbosity.max(0) + 1,
+            "-vv" => options.verbosity = options.verbosity.max(0) + 2,
+            "--profile" => {
+                let name = args.next().ok_or("--profile requires a profile name")?;
+                options.profile = Some(name);
+            }
+            "--format" => match args.next().as_deref() {
+                Some("json") => options.json_input = true,
+                Some("text") => options.json_input = false,
+                _ => return Err("--format requires text or json".into()),
+            },
+            "--receipt" => {
+                let path = args.next().ok_or("--receipt requires a file path")?;
+                options.receipt = Some(PathBuf::from(path));
+            }
+            "--append-receipt" => {
+                let path = args.next().ok_or("--append-receipt requires a file path")?;
+                options.receipt = Some(PathBuf::from(path));
+                options.append_receipt = true;
+            }
+            "--var" => {
+                let value = args.next().ok_or("--var requires name=value")?;
+                let (name, value) = value
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid --var value: {} (use name=value)", value))?;
+                options.vars.push((name.to_string(), value.to_string()));
+            }
+            "--title" => {
+                options.fields.title = Some(args.next().ok_or("--title requires a title")?)
+            }
+            "--list" => {
+                options.fields.list = Some(args.next().ok_or("--list requires a list name")?)
+            }
+            "--label" => options
+                .fields
+                .labels
+                .push(args.next().ok_or("--label requires a label name")?),
+            "--notes" => {
+                options.fields.notes = Some(args.next().ok_or("--notes requires the notes")?)
+            }
+            "--minutes" => {
+                let value = args
+                    .next()
+                    .ok_or("--minutes requires a number of minutes")?;
+                let minutes = value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|minutes| *minutes > 0)
+                    .ok_or_else(|| format!("Invalid --minutes value: {}", value))?;
+                options.fields.minutes = Some(minutes);
+            }
+            "--due" => {
+                let value = args.next().ok_or("--due requires a date")?;
+                let date = dates::parse_date(&value, dates::today())
+                    .ok_or_else(|| format!("Invalid --due date: {}", value))?;
+                options.fields.due = Some(date.to_string());
+            }
+            "--" => {
+                // Everything after `--` is task text, even if it looks like a flag.
+                // The marker itself is kept so the parser treats the rest as raw title.
+                options.words.push(arg);
+                options.words.extend(args.by_ref());
+            }
+            _ if is_unknown_option(&arg) => {
+                return Err(format!(
+                    "Unknown option: {} (put task text that starts with - after --)",
+                    arg
+                ))
+            }
+            _ => options.words.push(arg),
+        }
+    }
+
+    if options.json_input && !(options.fields.is_empty() && options.words.is_empty()) {
+        return Err("--format json reads tasks from stdin; drop the task text and flags".into());
+    }
+    if options.offline && options.no_queue {
+        return Err("--offline queues every task, so it can't be used with --no-queue".into());
+    }
+    if !options.fields.is_empty() && !options.words.is_empty() {
+        return Err(format!(
+            "Unexpected text alongside --title and the other task flags: {} (put it in --title)",
+            options.words.join(" ")
+        ));
+    }
+
+    Ok(options)
+}
+
+/// The file in `add -f <file>`; `add` followed by anything else is task text
+fn task_file_arg(words: &[String]) -> Option<&str> {
+    match words {
+        [add, flag, path] if add == "add" && (flag == "-f" || flag == "--file") => Some(path),
+        _ => None,
+    }
+}
+
+fn profile_command(args: &[String], config: &Config) -> Result<(), String> {
+    let known = |name: &str| name == profile::DEFAULT || config.profiles.contains_key(name);
+
+    match args.first().map(String::as_str) {
+        None | Some("show") if term::json() => {
+            term::print_json(&serde_json::json!({ "profile": profile::active() }))
+        }
+        None | Some("show") => println!("{}", profile::active()),
+        Some("list") => {
+            let mut names: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+            if !names.contains(&profile::DEFAULT) {
+                names.push(profile::DEFAULT);
+            }
+            names.sort();
+            if term::json() {
+                let profiles: Vec<serde_json::Value> = names
+                    .iter()
+                    .map(|name| serde_json::json!({ "name": name, "active": *name == profile::active() }))
+                    .collect();
+                term::print_json(&serde_json::json!(profiles));
+                return Ok(());
+            }
+            for name in names {
+                let marker = if name == profile::active() { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+        }
+        Some("check") => {
+            let name = args
+                .get(1)
+                .ok_or("Usage: godspeed-cli profile check <name>")?;
+            if !known(name) {
+                return Err(format!("Unknown profile: {}", name));
+            }
+        }
+        Some("use") => {
+            let name = args
+                .get(1)
+                .ok_or("Usage: godspeed-cli profile use <name>")?;
+            if !known(name) {
+                return Err(format!("Unknown profile: {}", name));
+            }
+            profile::set_default(name).map_err(|e| format!("Failed to save profile: {}", e))?;
+            if term::json() {
+                term::print_json(&serde_json::json!({
+                    "default": name,
+                    "overridden_by_env": env::var_os("GODSPEED_PROFILE").is_some(),
+                }));
+                return Ok(());
+            }
+            println!("Default profile is now {}", name);
+            if env::var_os("GODSPEED_PROFILE").is_some() {
+                println!("GODSPEED_PROFILE is set, so this terminal keeps its current profile");
+            }
+        }
+        Some(other) => return Err(format!("Unknown profile command: {}", other)),
+    }
+    Ok(())
+}
+
+/// `config path|get|set|unset|edit`: read and change settings without
+/// opening the file by hand
+fn config_command(args: &[String]) -> Result<(), String> {
+    let path = config::get_config_path();
+    let key = || {
+        args.get(1)
+            .ok_or(format!("Usage: godspeed-cli config {} <key>", args[0]))
+    };
+    match args.first().map(String::as_str) {
+        None | Some("path") if term::json() => {
+            term::print_json(&serde_json::json!({ "path": path }))
+        }
+        None | Some("path") => println!("{}", path.display()),
+        Some("get") => {
+            let key = key()?;
+            let value = config::get(key)?;
+            let found = value.is_some();
+            if term::json() {
+                term::print_json(&serde_json::json!({ "key": key, "value": value }));
+            } else {
+                match value {
+                    Some(serde_json::Value::String(text)) => println!("{}", text),
+                    Some(value) => match toml::Value::try_from(value) {
+                        Ok(toml::Value::Table(table)) => {
+                            print!("{}", toml::to_string(&table).unwrap_or_default())
+                        }
+                        Ok(value) => println!("{}", value),
+                        Err(e) => return Err(e.to_string()),
+                    },
+                    None => {}
+                }
+            }
+            if !found {
+                // Like `git config`, so scripts can tell unset from empty
+                std::process::exit(1);
+            }
+        }
+        Some("set") => {
+            let key = key()?;
+            let value = args
+                .get(2)
+                .ok_or("Usage: godspeed-cli config set <key> <value>")?;
+            config::set(key, value).map_err(|e| format!("Can't set {}: {}", key, e))?;
+            if term::json() {
+                term::print_json(&serde_json::json!({ "key": key, "value": config::get(key)? }));
+            } else {
+                println!("Set {} in {}", key, path.display());
+            }
+        }
+        Some("unset") => {
+            let key = key()?;
+            let removed = config::unset(key).map_err(|e| format!("Can't unset {}: {}", key, e))?;
+            if term::json() {
+                term::print_json(&serde_json::json!({ "key": key, "removed": removed }));
+            } else if removed {
+                println!("Removed {} from {}", key, path.display());
+            } else {
+                println!("{} wasn't set", key);
+            }
+        }
+        Some("edit") => {
+            fs::create_dir_all(config::get_config_dir()).map_err(|e| e.to_string())?;
+            if !path.exists() {
+                fs::write(&path, "").map_err(|e| e.to_string())?;
+            }
+            edit_file(&path)?;
+            let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            config::check(&content).map_err(|e| {
+                format!(
+                    "{} can't be loaded, so it's ignored until it's fixed: {}",
+                    path.display(),
+                    e
+                )
+            })?;
+        }
+        Some(other) => return Err(format!("Unknown config command: {}", other)),
+    }
+    Ok(())
+}
+
+fn restore_command(args: &[String], config: &Config) -> Result<(), String> {
+    let snapshots = backup::list();
+    match args {
+        [] if term::json() => term::print_json(&serde_json::json!(snapshots)),
+        [] => {
+            if snapshots.is_empty() {
+                println!("No snapshots yet");
+            }
+            for name in &snapshots {
+                println!("{}", name);
+            }
+            println!("Restore one with: godspeed-cli restore --from-snapshot <name|latest>");
+        }
+        [flag, name] if flag == "--from-snapshot" => {
+            let name = match name.as_str() {
+                "latest" => snapshots.last().ok_or("No snapshots yet")?,
+                name => name,
+            };
+            let undo = backup::restore(name, config.backups.keep).map_err(|e| e.to_string())?;
+            if term::json() {
+                term::print_json(&serde_json::json!({ "restored": name, "previous": undo }));
+                return Ok(());
+            }
+            println!("Restored {}", name);
+            println!("The previous state was saved as {}", undo);
+        }
+        _ => return Err("Usage: godspeed-cli restore [--from-snapshot <name|latest>]".into()),
+    }
+    Ok(())
+}
+
+/// `queue list|remove <n>|clear|edit [n]`; `queue retry` needs the API and
+/// is handled with `sync`
+/// What `queue remove` or `queue clear` would drop, for `--dry-run`
+fn removals(entries: &[queue::Entry]) -> Vec<plan::Change> {
+    entries
+        .iter()
+        .map(|entry| plan::Change::RemoveQueued {
+            input: entry.input.clone(),
+        })
+        .collect()
+}
+
+/// The command in `words` if it would change something `--dry-run` can't
+/// preview, so the flag is refused rather than ignored
+fn dry_run_unsupported(words: &[String]) -> Option<String> {
+    let command = words.first()?.as_str();
+    let sub = words.get(1).map(String::as_str);
+    let unsupported = match command {
+        "queue" => sub == Some("edit"),
+        "config" => matches!(sub, Some("set" | "unset" | "edit")),
+        "profile" => sub == Some("use"),
+        "auth" => matches!(sub, Some("login" | "logout")),
+        "restore" | "refresh" | "watch" | "init" => true,
+        "daemon" => sub.is_some(),
+        _ => false,
+    };
+    unsupported.then(|| words[..words.len().min(2)].join(" "))
+}
+
+fn queue_command(args: &[String], config: &Config, dry_run: bool) -> Result<(), String> {
+    let entries = queue::load();
+    // Entries are numbered from 1, as `queue list` shows them
+    let index = |arg: Option<&String>| -> Result<usize, String> {
+        let arg = arg.ok_or("Give the entry's number from `queue list`")?;
+        arg.parse::<usize>()
+            .ok()
+            .filter(|n| (1..=entries.len()).contains(n))
+            .map(|n| n - 1)
+            .ok_or_else(|| format!("No queue entry {} ({} queued)", arg, entries.len()))
+    };
+
+    match args.first().map(String::as_str) {
+        None | Some("list") if term::json() => term::print_json(&serde_json::json!(entries)),
+        None | Some("list") => {
+            if entries.is_empty() {
+                println!("Nothing queued");
+            }
+            let rows: Vec<Vec<String>> = entries
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    vec![
+                        (i + 1).to_string(),
+                        entry.captured_at.clone(),
+                        entry.attempts.to_string(),
+                        entry.input.lines().next().unwrap_or_default().to_string(),
+                    ]
+                })
+                .collect();
+            let lines = term::table(&["#", "CAPTURED", "TRIES", "TASK"], &rows);
+            if !entries.is_empty() {
+                println!("{}", lines[0]);
+            }
+            for (entry, line) in entries.iter().zip(&lines[1..]) {
+                println!("{}", line);
+                if let Some(error) = &entry.last_error {
+                    println!("    last failure: {}", error);
+                }
+                if let Some(after) = &entry.retry_after {
+                    println!("    next automatic retry after {}", after);
+                }
+            }
+            let dead = queue::dead_letter_count();
+            if dead > 0 {
+                println!(
+                    "{} tasks are no longer retried; see {}",
+                    dead,
+                    queue::get_dead_letter_path().display()
+                );
+            }
+        }
+        Some("remove") if dry_run => plan::render(&removals(std::slice::from_ref(
+            &entries[index(args.get(1))?],
+        ))),
+        Some("clear") if dry_run => plan::render(&removals(&entries)),
+        Some("remove") => {
+            let removed = entries[index(args.get(1))?].clone();
+            queue::modify(|entries| entries.retain(|entry| !entry.same_capture(&removed)))
+                .map_err(|e| format!("Failed to save the queue: {}", e))?;
+            if term::json() {
+                term::print_json(&serde_json::json!({ "removed": removed }));
+            } else {
+                println!(
+                    "Removed: {}",
+                    removed.input.lines().next().unwrap_or_default()
+                );
+            }
+        }
+        Some("clear") => {
+            // The queue can't be recovered otherwise, so keep a copy first
+            let snapshot = backup::snapshot("before-clear", config.backups.keep)
+                .map_err(|e| format!("Failed to save a snapshot: {}", e))?;
+            queue::modify(|queued| {
+                queued.retain(|entry| !entries.iter().any(|e| e.same_capture(entry)))
+            })
+            .map_err(|e| format!("Failed to save the queue: {}", e))?;
+            if term::json() {
+                term::print_json(&serde_json::json!({
+                    "cleared": entries.len(),
+                    "snapshot": snapshot,
+                }));
+            } else {
+                println!("Cleared {} queued tasks", entries.len());
+                println!("They were saved in snapshot {}", snapshot);
+            }
+        }
+        Some("edit") => match args.get(1) {
+            // A copy is edited, so captures queued meanwhile aren't overwritten
+            None => {
+                let path = get_data_dir().join("queue-edit.jsonl");
+                let content = fs::read_to_string(queue::get_queue_path()).unwrap_or_default();
+                fs::write(&path, content).map_err(|e| e.to_string())?;
+                edit_file(&path)?;
+                let edited = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+                let _ = fs::remove_file(&path);
+                queue::replace_edited(&entries, &edited)
+                    .map_err(|e| format!("Failed to save the queue: {}", e))?;
+            }
+            Some(arg) => {
+                let i = index(Some(arg))?;
+                let path = get_data_dir().join("queue-entry.txt");
+                fs::write(&path, &entries[i].input).map_err(|e| e.to_string())?;
+                edit_file(&path)?;
+                let edited = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+                let _ = fs::remove_file(&path);
+
+                let edited = edited.trim();
+                let original = &entries[i];
+                queue::modify(|entries| {
+                    let Some(pos) = entries
+                        .iter()
+                        .position(|entry| entry.same_capture(original))
+                    else {
+                        return;
+                    };
+                    if edited.is_empty() {
+                        entries.remove(pos);
+                    } else {
+                        if entries[pos].input != edited {
+                            entries[pos].idempotency_key = Some(queue::new_idempotency_key(edited));
+                        }
+                        entries[pos].input = edited.to_string();
+                        // A fixed entry starts with a clean slate
+                        entries[pos].attempts = 0;
+                        entries[pos].last_error = None;
+                        entries[pos].retry_after = None;
+                    }
+                })
+                .map_err(|e| format!("Failed to save the queue: {}", e))?;
+                if edited.is_empty() {
+                    eprintln!("Removed the entry, since it's empty now");
+                }
+            }
+        },
+        Some(other) => return Err(format!("Unknown queue command: {}", other)),
+    }
+    Ok(())
+}
+
+/// Open a file in `$VISUAL` or `$EDITOR`, waiting until the editor exits
+fn edit_file(path: &Path) -> Result<(), String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor may carry arguments, as in `code --wait`
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("EDITOR is empty")?;
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Failed to start {}: {}", editor, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", editor, status))
+    }
+}
+
+/// `watch [folder|file] [--once]`: capture files dropped in a folder, or
+/// task lines added to a file
+fn watch_command(args: &[String], api: &Api, resolver: &mut Resolver) -> Result<(), String> {
+    let once = args.iter().any(|arg| arg == "--once");
+    let path = match args.iter().find(|arg| *arg != "--once") {
+        Some(path) => PathBuf::from(path),
+        None => resolver
+            .watch
+            .folder
+            .as_deref()
+            .map(expand_home)
+            .ok_or("Give a folder to watch, or set folder under [watch] in the config")?,
+    };
+    let config = std::mem::take(&mut resolver.watch);
+    let capture = |text: &str| capture_watched(text, api, resolver);
+    if path.is_file() {
+        watch::file(&path, &config, once, capture)
+    } else {
+        watch::folder(&path, &config, once, capture)
+    }
+}
+
+/// Fetch lists, labels and teammates again, rewriting their caches, and show
+/// what changed since they were cached
+fn refresh_command(api: &Api, resolver: &mut Resolver) -> Result<(), Box<dyn std::error::Error>> {
+    let mut report = serde_json::Map::new();
+    for cache in [
+        &mut resolver.lists,
+        &mut resolver.labels,
+        &mut resolver.members,
+    ] {
+        let before = load_cache(&cache.path);
+        cache.refetch(api)?;
+        let after = cache.entries.clone().unwrap_or_default();
+        let changes = NameChanges::between(&before, &after);
+        if term::json() {
+            report.insert(format!("{}s", cache.kind), changes.to_json());
+        } else {
+            changes.print(cache.kind);
+        }
+    }
+    if term::json() {
+        term::print_json(&serde_json::Value::Object(report));
+    }
+    Ok(())
+}
+
+/// `lists`, `labels` and `teammates`: the cached names with their ids,
+/// fetched first if nothing is cached
+fn names_command(cache: &mut NameCache, api: &Api) -> Result<(), Box<dyn std::error::Error>> {
+    let names = cache.names(api)?;
+    let entries = cache.entries.clone().unwrap_or_default();
+    let id = |name: &String| entries.get(name).cloned().unwrap_or_default();
+    if term::json() {
+        let names: Vec<serde_json::Value> = names
+            .iter()
+            .map(|name| serde_json::json!({ "name": name, "id": id(name) }))
+            .collect();
+        term::print_json(&serde_json::json!(names));
+        return Ok(());
+    }
+    if names.is_empty() {
+        println!("No {}s", cache.kind);
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = names
+        .iter()
+        .map(|name| vec![name.clone(), id(name)])
+        .collect();
+    for line in term::table(&["NAME", "ID"], &rows) {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Who the API key belongs to, from `GET /user`
+fn fetch_account(api: &Api) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let api = api.clone();
+    cancel::run(move || {
+        let response = api.send(true, |client| {
+            client
+                .get(api.url("user"))
+                .header("Authorization", format!("Bearer {}", api.key))
+        })?;
+        if !response.status().is_success() {
+            return Err(GodspeedError::from_response(response).into());
+        }
+        Ok(response.json().map_err(GodspeedError::from)?)
+    })
+}
+
+/// `whoami`: the account and workspace the active profile's key belongs to
+fn whoami_command(api: &Api) -> Result<(), Box<dyn std::error::Error>> {
+    let body = fetch_account(api)?;
+    // The user may be the whole body or under `user`
+    let user = body.get("user").unwrap_or(&body);
+    let text = |value: &serde_json::Value, key: &str| {
+        value
+            .get(key)
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+    };
+    let name = text(user, "name");
+    let email = text(user, "email");
+    let workspace = body
+        .get("workspace")
+        .or_else(|| user.get("workspace"))
+        .and_then(|workspace| {
+            workspace
+                .as_str()
+                .map(str::to_string)
+                .or_else(|| text(workspace, "name"))
+        });
+    if term::json() {
+        term::print_json(&serde_json::json!({
+            "name": name,
+            "email": email,
+            "workspace": workspace,
+            "profile": profile::active(),
+            "api_url": api.base_url,
+        }));
+        return Ok(());
+    }
+    match (name, email) {
+        (Some(name), Some(email)) => println!("{} <{}>", name, email),
+        (Some(one), None) | (None, Some(one)) => println!("{}", one),
+        (None, None) => println!("The API key works, but the API didn't say whose it is"),
+    }
+    if let Some(workspace) = workspace {
+        println!("Workspace: {}", workspace);
+    }
+    println!("Profile: {}", profile::active());
+    println!("API: {}", api.base_url);
+    Ok(())
+}
+
+/// How a name cache changed, matched up by id so a rename isn't reported as
+/// one name removed and another added
+struct NameChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+    renamed: Vec<(String, String)>,
+    total: usize,
+}
+
+impl NameChanges {
+    fn between(before: &HashMap<String, String>, after: &HashMap<String, String>) -> Self {
+        // Of several names for one id, the first in order stands for it, so
+        // the same cache always compares the same way
+        let by_id = |cache: &HashMap<String, String>| -> HashMap<String, String> {
+            let mut names: HashMap<String, String> = HashMap::new();
+            for (name, id) in cache {
+                names
+                    .entry(id.clone())
+                    .and_modify(|first| {
+                        if name < first {
+                            *first = name.clone();
+                        }
+                    })
+                    .or_insert_with(|| name.clone());
+            }
+            names
+        };
+        let (before, after) = (by_id(before), by_id(after));
+        let mut changes = NameChanges {
+            added: Vec::new(),
+            removed: Vec::new(),
+            renamed: Vec::new(),
+            total: after.len(),
+        };
+        for (id, name) in &after {
+            match before.get(id) {
+                None => changes.added.push(name.clone()),
+                Some(old) if old != name => changes.renamed.push((old.clone(), name.clone())),
+                Some(_) => {}
+            }
+        }
+        for (id, name) in &before {
+            if !after.contains_key(id) {
+                changes.removed.push(name.clone());
+            }
+        }
+        changes.added.sort();
+        changes.removed.sort();
+        changes.renamed.sort();
+        changes
+    }
+
+    fn print(&self, kind: &str) {
+        if self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty() {
+            println!("{}s: {} cached, unchanged", kind, self.total);
+            return;
+        }
+        println!(
+            "{}s: {} cached, {} added, {} removed, {} renamed",
+            kind,
+            self.total,
+            self.added.len(),
+            self.removed.len(),
+            self.renamed.len()
+        );
+        for name in &self.added {
+            println!("  + {}", name);
+        }
+        for name in &self.removed {
+            println!("  - {}", name);
+        }
+        for (from, to) in &self.renamed {
+            println!("  ~ {} → {}", from, to);
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let renamed: Vec<serde_json::Value> = self
+            .renamed
+            .iter()
+            .map(|(from, to)| serde_json::json!({ "from": from, "to": to }))
+            .collect();
+        serde_json::json!({
+            "total": self.total,
+            "added": self.added,
+            "removed": self.removed,
+            "renamed": renamed,
+        })
+    }
+}
+
+/// Send one task found by `watch`, queueing it if that fails, and say what
+/// happened; the watcher carries on either way
+fn capture_watched(text: &str, api: &Api, resolver: &mut Resolver) {
+    let task_str = resolver.prepare_capture(text);
+    let title = task_str.lines().next().unwrap_or_default().to_string();
+    if safe_mode() || resolver.offline {
+        match queue::push(&task_str, &resolver.queue) {
+            Ok(_) => println!("Queued: {}", title),
+            Err(e) => eprintln!("Failed to queue {}: {}", title, e),
+        }
+        return;
+    }
+    let capture = Capture::new(&task_str);
+    match process_task(&capture, api, resolver) {
+        Ok(Some(id)) => println!("Created: {}", term::hyperlink(&title, &task_url(&id))),
+        Ok(None) => println!("Created: {}", title),
+        Err(e) if dead_letter_rejected(&task_str, e.as_ref()) => {
+            eprintln!(
+                "Rejected: {} ({}); it was saved in the dead-letter file",
+                title, e
+            )
+        }
+        Err(e) if is_permanent(e.as_ref()) => eprintln!("Failed: {} ({})", title, e),
+        Err(e) => {
+            if requeue(queue::Entry::attempted(&task_str, &capture.key), resolver) {
+                eprintln!("Cached: {} ({})", title, e);
+            } else {
+                eprintln!("Failed: {} ({})", title, e);
+            }
+        }
+    }
+}
+
+/// Create every task in a multi-line template, reporting each one
+fn apply_template(options: &Options, api: &Api, resolver: &mut Resolver) -> Result<(), String> {
+    let name = options
+        .words
+        .get(2)
+        .ok_or("Usage: godspeed-cli template apply <name> [--var key=value]...")?;
+    let tasks = templates::render_tasks(name, &templates::load(), &options.vars)?;
+
+    if options.dry_run {
+        let mut changes = Vec::new();
+        for task_str in &tasks {
+            let task_str = resolver.prepare_capture(task_str);
+            changes.extend(plan_task(&task_str, api, resolver).map_err(|e| e.to_string())?);
+        }
+        plan::render(&changes);
+        return Ok(());
+    }
+
+    // Per-task lines are for people; `--json` prints one summary at the end
+    let say = |line: String| {
+        if !term::json() {
+            println!("{}", line);
+        }
+    };
+
+    if safe_mode() || resolver.offline {
+        let mut queued = Vec::new();
+        for task_str in &tasks {
+            let task_str = resolver.prepare_capture(task_str);
+            let title = task_str.lines().next().unwrap_or_default().to_string();
+            if queue::push(&task_str, &resolver.queue)
+                .map_err(|e| format!("Failed to queue task: {}", e))?
+            {
+                say(format!("Queued: {}", title));
+                queued.push(task_str);
+            } else {
+                say(format!("Already queued: {}", title));
+            }
+        }
+        if term::json() {
+            term::print_json(&serde_json::json!({ "template": name, "queued": queued }));
+        }
+        if safe_mode() {
+            say(format!(
+                "Safe mode: queued {} tasks from {} without sending",
+                queued.len(),
+                name
+            ));
+        } else {
+            say(format!(
+                "Offline: queued {} tasks from {}; they'll be sent once you're back online",
+                queued.len(),
+                name
+            ));
+        }
+        return Ok(());
+    }
+
+    cancel::install_handler();
+    let mut created = 0;
+    let mut queued = Vec::new();
+    let mut errors = Vec::new();
+    for task_str in &tasks {
+        let task_str = &resolver.prepare_capture(task_str);
+        let title = task_str.lines().next().unwrap_or_default();
+        if cancel::is_cancelled() {
+            if !resolver.no_queue && requeue(queue::Entry::new(task_str), resolver) {
+                say(format!("Cached: {}", title));
+                queued.push(task_str.clone());
+            }
+            continue;
+        }
+        let capture = Capture::new(task_str);
+        match process_task(&capture, api, resolver) {
+            Ok(id) => {
+                created += 1;
+                match id {
+                    Some(id) => say(format!(
+                        "Created: {}",
+                        term::hyperlink(title, &task_url(&id))
+                    )),
+                    None => say(format!("Created: {}", title)),
+                }
+            }
+            Err(e) => {
+                say(format!("Failed: {} ({})", title, e));
+                let mut error = task_error_json(e.as_ref());
+                error["input"] = serde_json::json!(task_str);
+                errors.push(error);
+                if !resolver.no_queue
+                    && !dead_letter_rejected(task_str, e.as_ref())
+                    && requeue(queue::Entry::attempted(task_str, &capture.key), resolver)
+                {
+                    queued.push(task_str.clone());
+                }
+            }
+        }
+    }
+
+    if term::json() {
+        term::print_json(&serde_json::json!({
+            "template": name,
+            "created": resolver.created,
+            "queued": queued,
+            "errors": errors,
+        }));
+    }
+    say(format!(
+        "Created {} of {} tasks from {}",
+        created,
+        tasks.len(),
+        name
+    ));
+    if created < tasks.len() {
+        notify::failure(&format!("Template {}: some tasks failed", name));
+        if resolver.no_queue {
+            return Err("Some tasks failed and, with --no-queue, weren't cached".into());
+        }
+        return Err("Tasks that failed were cached; run `godspeed-cli sync` to retry".into());
+    }
+    Ok(())
+}
+
+fn main() {
+    // Known before parsing, so a bad argument is reported as JSON too
+    term::set_json(env::args().skip(1).any(|arg| arg == "--json"));
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => fail("usage", e, 2),
+    };
+    term::set_json(options.json);
+    term::set_verbosity(options.verbosity);
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
+    if options.version {
+        println!("godspeed-cli {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    if let Some(command) = dry_run_unsupported(&options.words).filter(|_| options.dry_run) {
+        fail(
+            "usage",
+            format!("--dry-run can't preview `{}`; run it without", command),
+            2,
+        );
+    }
+    if let Some(name) = &options.profile {
+        profile::select(name);
+    }
+    // The name becomes a directory, so it mustn't lead out of the data dir
+    if let Err(e) = profile::check_name(profile::active()) {
+        fail("usage", e, 2);
+    }
+
+    let mut config = config::load();
+    term::configure(&config.output, options.no_color);
+    dotenv::load(&config);
+    let notify = match env::var("GODSPEED_NOTIFY") {
+        _ if options.no_notify => false,
+        Ok(value) if !value.is_empty() => value != "0",
+        _ => config.notifications.enabled,
+    };
+    notify::configure(&config.notifications, notify);
+
+    let safe_env = env::var("GODSPEED_SAFE").is_ok_and(|v| !v.is_empty() && v != "0");
+    SAFE_MODE.store(options.safe || safe_env, Ordering::SeqCst);
+    if options.mock || env::var("GODSPEED_MOCK").is_ok_and(|v| !v.is_empty() && v != "0") {
+        mock::enable();
+    }
+    // After the mock, whose runs log to its own data directory
+    logfile::init(&config.log);
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "started");
+
+    // Commands that don't need an API key
+    match options.words.first().map(String::as_str) {
+        Some("profile") => {
+            if let Err(e) = profile_command(&options.words[1..], &config) {
+                fail("profile", e, 1);
+            }
+            return;
+        }
+        Some("template") if options.words.get(1).map(String::as_str) != Some("apply") => {
+            match options.words.get(1).map(String::as_str) {
+                None | Some("list") => {
+                    let mut names: Vec<String> = templates::load().into_keys().collect();
+                    names.sort();
+                    if term::json() {
+                        term::print_json(&serde_json::json!(names));
+                    } else {
+                        for name in names {
+                            println!("{}", name);
+                        }
+                    }
+                }
+                Some(other) => fail("usage", format!("unknown template command: {}", other), 2),
+            }
+            return;
+        }
+        Some("config") => {
+            if let Err(e) = config_command(&options.words[1..]) {
+                fail("config", e, 1);
+            }
+            return;
+        }
+        Some("restore") => {
+            if let Err(e) = restore_command(&options.words[1..], &config) {
+                fail("restore", e, 1);
+            }
+            return;
+        }
+        Some("metrics") => {
+            metrics::report();
+            return;
+        }
+        Some("queue") if options.words.get(1).map(String::as_str) != Some("retry") => {
+            if let Err(e) = queue_command(&options.words[1..], &config, options.dry_run) {
+                fail("queue", e, 1);
+            }
+            return;
+        }
+        Some("audit") => {
+            if !audit::show() {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some("daemon") if options.words.len() > 1 => {
+            let result = match options.words[1].as_str() {
+                "install" => {
+                    daemon::install(&config).map(|path| format!("Installed {}", path.display()))
+                }
+                "uninstall" => {
+                    daemon::uninstall().map(|path| format!("Removed {}", path.display()))
+                }
+                other => fail("usage", format!("unknown daemon command: {}", other), 2),
+            };
+            match result {
+                Ok(message) if term::json() => {
+                    term::print_json(&serde_json::json!({ "message": message }))
+                }
+                Ok(message) => println!("{}", message),
+                Err(e) => fail("daemon", e, 1),
+            }
+            return;
         }
+        Some("shell-init") => {
+            let shell = options.words.get(1).map(String::as_str).unwrap_or("zsh");
+            match profile::shell_init(shell) {
+                Some(script) => print!("{}", script),
+                None => fail(
+                    "usage",
+                    format!("unsupported shell {} (use bash, zsh or fish)", shell),
+                    2,
+                ),
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    let active_profile = profile::active();
+    let profile_config = config.profiles.get(active_profile).cloned();
+    let make_directories = || {
+        if let Err(e) = ensure_directories() {
+            fail("storage", format!("Failed to create directories: {}", e), 1);
+        }
+    };
+    // A profile only the keyring knows gets its directory once the keyring
+    // has been asked, so a mistyped name never leaves one behind
+    let listed = active_profile == profile::DEFAULT || profile_config.is_some() || mock::enabled();
+    if listed {
+        make_directories();
+    }
+
+    let base_url = if mock::enabled() {
+        match mock::start() {
+            Ok(url) => url,
+            Err(e) => fail("mock", format!("Failed to start the mock API: {}", e), 1),
+        }
+    } else {
+        options
+            .api_url
+            .clone()
+            .or_else(|| env::var("GODSPEED_API_URL").ok())
+            .filter(|url| !url.is_empty())
+            .or_else(|| profile_config.as_ref().and_then(|p| p.base_url.clone()))
+            .or_else(|| config.api.base_url.clone())
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+    };
+    let base_url = match parse_base_url(&base_url) {
+        Ok(url) => url,
+        Err(e) => fail("config", e, 1),
+    };
+    let proxy = match config.api.proxy.as_deref().map(parse_proxy) {
+        Some(Ok(proxy)) => Some(proxy),
+        Some(Err(e)) => fail("config", e, 1),
+        None => None,
+    };
+    let tls = match tls::Tls::from_config(&config.api) {
+        Ok(tls) => tls,
+        Err(e) => fail("config", e, 1),
+    };
+    let timeout = match options.timeout {
+        Some(timeout) => timeout,
+        None => match env::var("GODSPEED_TIMEOUT").ok().filter(|v| !v.is_empty()) {
+            Some(secs) => match secs.trim().parse::<u64>() {
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => fail("config", format!("invalid GODSPEED_TIMEOUT {}", secs), 1),
+            },
+            None => Duration::from_secs(config.api.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)),
+        },
+    };
+    let connect =
+        |key: String| Api::new(key, base_url.clone(), timeout, proxy.clone(), tls.clone());
+
+    // These set up the key, so they run without one
+    match options.words.first().map(String::as_str) {
+        Some("init") => {
+            if let Err(e) = init::run(connect) {
+                fail("init", e, 1);
+            }
+            return;
+        }
+        Some("auth") => {
+            let done = match options.words.get(1).map(String::as_str) {
+                Some("login") => auth::login(connect),
+                Some("logout") => auth::logout(),
+                _ => fail("usage", "Usage: godspeed-cli auth login|logout", 2),
+            };
+            if let Err(e) = done {
+                fail("auth", e, 1);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    // Only the default profile falls back to GODSPEED_API, so a typo in a
+    // work profile can never file tasks into the personal account
+    let env_key = env::var("GODSPEED_API").ok().filter(|key| !key.is_empty());
+    // Unneeded when the environment has the key, so `api_key_cmd` isn't run
+    let env_wins = mock::enabled() || (active_profile == profile::DEFAULT && env_key.is_some());
+    let config_key = match profile_config.as_ref() {
+        Some(profile) if !env_wins => match profile.api_key() {
+            Ok(key) => key,
+            Err(e) => {
+                notify::failure("Couldn't get the API key");
+                fail("no_api_key", e, 1);
+            }
+        },
+        _ => None,
+    };
+    // Asking the keyring is slower and may prompt, so it's the last resort
+    let keyring_key = if env_wins || config_key.is_some() {
+        None
+    } else {
+        credentials::lookup(active_profile)
+    };
+
+    if active_profile != profile::DEFAULT
+        && profile_config.is_none()
+        && keyring_key.is_none()
+        && !mock::enabled()
+    {
+        notify::failure(&format!("Unknown profile: {}", active_profile));
+        fail(
+            "unknown_profile",
+            format!(
+                "unknown profile {} (add [profiles.{}] to {})",
+                active_profile,
+                active_profile,
+                config::get_config_path().display()
+            ),
+            1,
+        );
+    }
+    if !listed {
+        make_directories();
+    }
+
+    let api_key = match config_key.or(keyring_key) {
+        _ if mock::enabled() => "mock".to_string(),
+        // As with every setting, the environment wins over the config
+        _ if active_profile == profile::DEFAULT && env_key.is_some() => env_key.unwrap_or_default(),
+        Some(key) => key,
+        None if active_profile != profile::DEFAULT => {
+            notify::failure(&format!("No API key for profile {}", active_profile));
+            fail(
+                "no_api_key",
+                format!(
+                    "no API key for profile {} (run auth login, or set api_key, api_key_cmd, or api_key_env to a variable that is set)",
+                    active_profile
+                ),
+                1,
+            );
+        }
+        None => {
+            notify::failure("GODSPEED_API environment variable not set");
+            fail("no_api_key", "GODSPEED_API environment variable not set", 1);
+        }
+    };
+
+    let api = connect(api_key);
+
+    backup::weekly(&config.backups);
+
+    if options.words.first().map(String::as_str) == Some("daemon") {
+        if safe_mode() {
+            fail("safe_mode", "safe mode is on; cached tasks are not sent", 1);
+        }
+        cancel::install_handler();
+        // The config is read again each time, so edits apply without a restart
+        daemon::run(&config.daemon, || {
+            let mut resolver = Resolver::new(config::load());
+            let (sent, tried) = flush_queue(&api, &mut resolver, false);
+            if tried > 0 && !term::quiet() {
+                eprintln!("Sent {} of {} cached tasks", sent, tried);
+            }
+        });
+        return;
+    }
+
+    config.strict |= options.strict;
+    config.create_labels |= options.create_labels;
+    if options.create_lists {
+        config.create_lists = CreateLists::Always;
+    }
+    if options.pick {
+        config.pick_list = PickList::Always;
+    }
+    config.queue.allow_duplicates |= options.allow_duplicates;
+    config.urls.move_to_notes |= options.url_notes;
+    config.urls.fetch_title |= options.fetch_title;
+    let updates = std::mem::take(&mut config.updates);
+    let mut resolver = Resolver::new(config);
+    resolver.dry_run = options.dry_run;
+    // Without a queue to fall back on, let the request itself fail
+    resolver.no_queue = options.no_queue;
+    resolver.offline = options.offline
+        || (!options.no_queue
+            && !mock::enabled()
+            && resolver.queue.detect_offline
+            && !online::is_local(&base_url)
+            && !online::has_network());
+    match &options.receipt {
+        Some(path) if options.append_receipt => resolver.receipts.append_to_file(path.clone()),
+        Some(path) => resolver.receipts.set_file(path.clone()),
+        None => {}
+    }
+
+    if options.flush_only {
+        if !safe_mode() {
+            cancel::install_handler();
+            flush_queue(&api, &mut resolver, false);
+        }
+        return;
+    }
+
+    // Commands
+    let retry = options.words.first().map(String::as_str) == Some("queue")
+        && options.words.get(1).map(String::as_str) == Some("retry");
+    if options.words.first().map(String::as_str) == Some("sync") || retry {
+        if options.dry_run {
+            let mut changes = Vec::new();
+            for entry in queue::load() {
+                match plan_task(&entry.input, &api, &mut resolver) {
+                    Ok(planned) => changes.extend(planned),
+                    Err(e) => fail("sync", e.to_string(), 1),
+                }
+            }
+            plan::render(&changes);
+            return;
+        }
+        if safe_mode() {
+            fail("safe_mode", "safe mode is on; cached tasks are not sent", 1);
+        }
+        cancel::install_handler();
+        let (sent, queued) = flush_queue(&api, &mut resolver, true);
+        if term::json() {
+            term::print_json(&serde_json::json!({
+                "sent": sent,
+                "queued": queued,
+                "created": resolver.created,
+            }));
+        } else if !term::quiet() {
+            println!("Sent {} of {} cached tasks", sent, queued);
+        }
+        if cancel::is_cancelled() {
+            std::process::exit(cancel::EXIT_CODE);
+        }
+        return;
+    }
+
+    if options.words.first().map(String::as_str) == Some("watch") {
+        cancel::install_handler();
+        if let Err(e) = watch_command(&options.words[1..], &api, &mut resolver) {
+            fail("watch", e, 1);
+        }
+        return;
+    }
+
+    if options.words.first().map(String::as_str) == Some("refresh") {
+        cancel::install_handler();
+        if let Err(e) = refresh_command(&api, &mut resolver) {
+            fail(
+                error_code(e.as_ref()),
+                format!("Failed to refresh: {}", e),
+                exit_code(e.as_ref()),
+            );
+        }
+        return;
+    }
+
+    if let Some(kind @ ("lists" | "labels" | "teammates")) =
+        options.words.first().map(String::as_str)
+    {
+        cancel::install_handler();
+        let cache = match kind {
+            "lists" => &mut resolver.lists,
+            "labels" => &mut resolver.labels,
+            _ => &mut resolver.members,
+        };
+        if let Err(e) = names_command(cache, &api) {
+            fail(error_code(e.as_ref()), &e, exit_code(e.as_ref()));
+        }
+        return;
+    }
+
+    if options.words.first().map(String::as_str) == Some("whoami") {
+        cancel::install_handler();
+        if let Err(e) = whoami_command(&api) {
+            fail(error_code(e.as_ref()), &e, exit_code(e.as_ref()));
+        }
+        return;
+    }
+
+    if options.words.first().map(String::as_str) == Some("template") {
+        if let Err(e) = apply_template(&options, &api, &mut resolver) {
+            fail("template", e, 1);
+        }
+        if cancel::is_cancelled() {
+            std::process::exit(cancel::EXIT_CODE);
+        }
+        return;
+    }
+
+    // Structured tasks from another program skip templates and paste modes
+    let tasks = if options.json_input {
+        let input = ingest::read_stdin().unwrap_or_default();
+        match task_json::read(&input) {
+            Ok(tasks) => tasks,
+            Err(e) => fail("invalid_input", e, 2),
+        }
+    } else if let Some(path) = task_file_arg(&options.words) {
+        match task_file::read(Path::new(path)) {
+            Ok(tasks) => tasks,
+            Err(e) => fail("invalid_input", e, 2),
+        }
+    } else {
+        // Get input from flags, a dialog, args or stdin
+        let input = {
+            if !options.fields.is_empty() {
+                match options.fields.to_task_text() {
+                    Ok(text) => text,
+                    Err(e) => fail("usage", e, 2),
+                }
+            } else if options.words.first().map(String::as_str) == Some("dialog") {
+                match ask_dialog() {
+                    Some(text) => text,
+                    None => return,
+                }
+            } else if !options.words.is_empty() {
+                // Join all arguments with spaces to handle multi-word input
+                options.words.join(" ")
+            } else {
+                // Read from stdin
+                ingest::read_stdin()
+                    .unwrap_or_default()
+                    .trim_end()
+                    .to_string()
+            }
+        };
+
+        let input = match templates::expand(&input, &templates::load()) {
+            Ok(input) => input,
+            Err(e) => {
+                notify::failure(&e);
+                fail("template", e, 1);
+            }
+        };
+
+        // Decide how multi-line input is split into tasks
+        let line_count = input.lines().filter(|l| !l.trim().is_empty()).count();
+        let mode = if line_count <= 1 || !options.fields.is_empty() {
+            PasteMode::TitleNotes
+        } else if options.stdin_lines {
+            PasteMode::PerLine
+        } else {
+            ask_paste_mode(line_count).unwrap_or(PasteMode::TitleNotes)
+        };
+
+        split_input(&input, mode)
+    };
+
+    // Preview what would be created, without touching the queue or the API's data
+    if options.dry_run {
+        let mut changes = Vec::new();
+        for task_str in &tasks {
+            let task_str = resolver.pick_missing(task_str, &api);
+            let task_str = resolver.prepare_capture(&task_str);
+            match plan_task(&task_str, &api, &mut resolver) {
+                Ok(task_changes) => changes.extend(task_changes),
+                Err(e) => fail(
+                    error_code(e.as_ref()),
+                    format!("Failed to plan task: {}", e),
+                    exit_code(e.as_ref()),
+                ),
+            }
+        }
+        plan::render(&changes);
+        return;
+    }
+
+    // Queue everything, leaving the account untouched
+    if safe_mode() || resolver.offline {
+        let mut queued = Vec::new();
+        for task_str in &tasks {
+            if task_str.is_empty() {
+                continue;
+            }
+            let task_str = resolver.pick_missing(task_str, &api);
+            let task_str = resolver.prepare_capture(&task_str);
+            match queue::push(&task_str, &resolver.queue) {
+                Ok(true) => queued.push(task_str),
+                Ok(false) if !term::json() && !term::quiet() => eprintln!(
+                    "Already queued: {}",
+                    task_str.lines().next().unwrap_or_default()
+                ),
+                Ok(false) => {}
+                Err(e) => eprintln!("Failed to queue task: {}", e),
+            }
+        }
+        if term::json() {
+            term::print_json(&serde_json::json!({ "queued": queued }));
+        } else if term::quiet() {
+            // Nothing to say; the tasks are safely queued
+        } else if safe_mode() {
+            println!(
+                "Safe mode: queued {} tasks without sending; run `godspeed-cli sync` with safe mode off to send them",
+                queued.len()
+            );
+        } else {
+            println!(
+                "Offline: queued {} tasks; they'll be sent by the next capture or `godspeed-cli sync` once you're back online",
+                queued.len()
+            );
+        }
+        return;
+    }
+
+    // From here on Ctrl-C cancels network calls instead of killing the process
+    cancel::install_handler();
+    let pages = api.clone();
+    let version_check = version::start(&updates, move || pages.page_client());
+
+    // Process current input first, so a capture never waits on the backlog
+    let mut all_sent = true;
+    let mut status = 0;
+    let mut queued = Vec::new();
+    let mut errors = Vec::new();
+    let mut inputs = Vec::new();
+    for task_str in &tasks {
+        if task_str.is_empty() {
+            continue;
+        }
+        let task_str = resolver.pick_missing(task_str, &api);
+        inputs.push(Capture::new(&resolver.prepare_capture(&task_str)));
+    }
+    // Several tasks, such as a pasted list, are sent together; `metrics`
+    // only times single captures
+    let results = if inputs.len() > 1 {
+        process_tasks(&inputs, &api, &mut resolver)
+    } else {
+        let mut results = Vec::new();
+        for capture in &inputs {
+            if cancel::is_cancelled() {
+                results.push(Err("Cancelled".into()));
+                continue;
+            }
+            resolver.timings = metrics::Timings::default();
+            let started = Instant::now();
+            let result = process_task(capture, &api, &mut resolver);
+            metrics::record(&resolver.timings, started.elapsed(), result.is_ok());
+            results.push(result);
+        }
+        results
+    };
+    for (
+        Capture {
+            input: task_str,
+            key,
+            ..
+        },
+        result,
+    ) in inputs.into_iter().zip(results)
+    {
+        if result.is_err() && cancel::is_cancelled() {
+            // Keep the capture so Ctrl-C never loses what was typed; the
+            // request may have reached the API, so it keeps its key
+            if !resolver.no_queue && requeue(queue::Entry::attempted(&task_str, &key), &resolver) {
+                queued.push(task_str);
+            }
+            continue;
+        }
+        // The id and link on stdout, for scripts that act on the new task
+        if let (Ok(Some(id)), false) = (&result, term::json() || term::quiet()) {
+            let url = task_url(id);
+            println!("{} {}", term::hyperlink(id, &url), url);
+        }
+        if let Err(e) = result {
+            tracing::error!(
+                input = task_str.lines().next().unwrap_or_default(),
+                error = %e,
+                "task not sent"
+            );
+            let mut error = task_error_json(e.as_ref());
+            error["input"] = serde_json::json!(task_str);
+            errors.push(error);
+            if is_permanent(e.as_ref()) || resolver.no_queue {
+                let kept = !resolver.no_queue && dead_letter_rejected(&task_str, e.as_ref());
+                if !term::json() {
+                    eprintln!("{} {}", term::paint_err(Style::Error, "Error:"), e);
+                    if kept {
+                        eprintln!(
+                            "The task was saved in {}",
+                            queue::get_dead_letter_path().display()
+                        );
+                    }
+                }
+                notify::failure(&e.to_string());
+                status = exit_code(e.as_ref());
+                all_sent &= is_permanent(e.as_ref());
+                continue;
+            }
+            all_sent = false;
+            if !term::json() && !cancel::is_cancelled() {
+                eprintln!("Failed to send task: {}", e);
+            }
+            if requeue(queue::Entry::attempted(&task_str, &key), &resolver) {
+                queued.push(task_str);
+            }
+            if cancel::is_cancelled() {
+                continue;
+            }
+            // The server's explanation helps; a network error's text doesn't
+            if e.downcast_ref::<GodspeedError>()
+                .is_some_and(|e| !matches!(e, GodspeedError::Network(_)))
+            {
+                notify::failure(&format!("Failed to send task: {}", e));
+            } else {
+                notify::failure("Failed to send task");
+            }
+        }
+    }
+    let created = std::mem::take(&mut resolver.created);
+
+    // The tasks are sent, so stale caches can be refreshed without holding
+    // up the capture; this overlaps with sending the queue
+    let refreshing = if all_sent {
+        resolver.refresh_stale(&api)
+    } else {
+        Vec::new()
+    };
+
+    // Then retry cached tasks, but only if the API just proved reachable
+    let synced = if !all_sent || queue::is_empty() {
+        Some(0)
+    } else if resolver.queue.background_flush && spawn_flush(&options) {
+        None
+    } else {
+        Some(flush_queue(&api, &mut resolver, false).0)
     };
 
-    // Process cached tasks first
-    let cached_tasks = get_cached_tasks();
-    for cached_task in cached_tasks {
-        if let Ok(_) = process_task(&cached_task, &api_key) {
-            let _ = remove_from_cache(&cached_task);
+    if term::json() {
+        term::print_json(&serde_json::json!({
+            "created": created,
+            "queued": queued,
+            "errors": errors,
+            "synced": synced,
+        }));
+    }
+    // The output is out; finish writing the refreshed caches before exiting
+    for handle in refreshing {
+        let _ = handle.join();
+    }
+    if let Some(check) = version_check {
+        version::finish(check);
+    }
+    if cancel::is_cancelled() {
+        if !term::json() && resolver.no_queue {
+            eprintln!("Cancelled; unsent tasks were not cached");
+        } else if !term::json() {
+            eprintln!("Cancelled; unsent tasks were cached for retry");
+        }
+        std::process::exit(cancel::EXIT_CODE);
+    }
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn unknown_options_are_refused() {
+        for line in ["--dryrun buy milk", "-vvv", "buy milk --verison", "-x"] {
+            let error = parse_args(args(line).into_iter()).err();
+            assert!(
+                error.is_some_and(|e| e.starts_with("Unknown option")),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn dashes_are_still_task_text_where_they_are_not_flags() {
+        let options = parse_args(args("- 5 -5 -- --version").into_iter()).unwrap();
+        assert_eq!(options.words, args("- 5 -5 -- --version"));
+        let quoted = ["-call mum back".to_string()];
+        assert_eq!(
+            parse_args(quoted.into_iter()).unwrap().words,
+            ["-call mum back"]
+        );
+        let restore = parse_args(args("restore --from-snapshot x").into_iter()).unwrap();
+        assert_eq!(restore.words, args("restore --from-snapshot x"));
+    }
+
+    #[test]
+    fn help_and_version_are_options() {
+        assert!(parse_args(args("--version").into_iter()).unwrap().version);
+        assert!(parse_args(args("-V").into_iter()).unwrap().version);
+        assert!(parse_args(args("--help").into_iter()).unwrap().help);
+        assert!(parse_args(args("-h").into_iter()).unwrap().help);
+    }
+
+    #[test]
+    fn dry_run_refused_where_it_cannot_preview() {
+        for line in [
+            "queue edit",
+            "queue edit 2",
+            "restore before-clear",
+            "refresh",
+            "watch ~/Inbox",
+            "init",
+            "config set api.timeout_secs 5",
+            "config unset api",
+            "config edit",
+            "profile use work",
+            "auth login",
+            "auth logout",
+            "daemon install",
+        ] {
+            assert!(dry_run_unsupported(&args(line)).is_some(), "{}", line);
         }
+        assert_eq!(
+            dry_run_unsupported(&args("queue edit 2")).as_deref(),
+            Some("queue edit")
+        );
+    }
+
+    #[test]
+    fn dry_run_allowed_where_it_previews_or_changes_nothing() {
+        for line in [
+            "",
+            "Buy milk @Errands",
+            "sync",
+            "queue retry",
+            "queue remove 1",
+            "queue clear",
+            "queue list",
+            "template apply weekly",
+            "config get api",
+            "profile list",
+            "lists",
+            "daemon",
+        ] {
+            assert_eq!(dry_run_unsupported(&args(line)), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn queue_removals_name_each_entry() {
+        let entries = [
+            queue::Entry::new("Buy milk\nnotes"),
+            queue::Entry::new("Call Sam"),
+        ];
+        let inputs: Vec<String> = removals(&entries)
+            .into_iter()
+            .map(|change| match change {
+                plan::Change::RemoveQueued { input } => input,
+                _ => panic!("not a removal"),
+            })
+            .collect();
+        assert_eq!(inputs, ["Buy milk\nnotes", "Call Sam"]);
+    }
+
+    fn names(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(name, id)| (name.to_string(), id.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn name_changes_none() {
+        let cache = names(&[("work", "1"), ("home", "2")]);
+        let changes = NameChanges::between(&cache, &cache);
+        assert!(changes.added.is_empty());
+        assert!(changes.removed.is_empty());
+        assert!(changes.renamed.is_empty());
+        assert_eq!(changes.total, 2);
+    }
+
+    #[test]
+    fn name_changes_added_and_removed() {
+        let before = names(&[("work", "1"), ("home", "2")]);
+        let after = names(&[("work", "1"), ("garden", "4"), ("errands", "3")]);
+        let changes = NameChanges::between(&before, &after);
+        assert_eq!(changes.added, ["errands", "garden"]);
+        assert_eq!(changes.removed, ["home"]);
+        assert!(changes.renamed.is_empty());
+        assert_eq!(changes.total, 3);
+    }
+
+    #[test]
+    fn name_changes_renamed() {
+        let before = names(&[("work", "1"), ("home", "2")]);
+        let after = names(&[("office", "1"), ("home", "2")]);
+        let changes = NameChanges::between(&before, &after);
+        assert!(changes.added.is_empty());
+        assert!(changes.removed.is_empty());
+        assert_eq!(
+            changes.renamed,
+            [("work".to_string(), "office".to_string())]
+        );
+    }
+
+    #[test]
+    fn name_changes_reused_name_is_not_a_rename() {
+        // "work" now belongs to a new list, and the old one is gone
+        let before = names(&[("work", "1")]);
+        let after = names(&[("work", "5")]);
+        let changes = NameChanges::between(&before, &after);
+        assert_eq!(changes.added, ["work"]);
+        assert_eq!(changes.removed, ["work"]);
+        assert!(changes.renamed.is_empty());
+    }
+
+    #[test]
+    fn name_changes_with_several_names_for_an_id() {
+        let before = names(&[("café", "1"), ("cafe", "1")]);
+        let after = names(&[("cafe", "1"), ("café", "1")]);
+        assert!(NameChanges::between(&before, &after).renamed.is_empty());
+    }
+
+    /// Parse what `to_task_text` wrote, as a capture would
+    fn round_trip(flags: &TaskFlags) -> (TaskRequest, TaskRefs) {
+        let text = flags.to_task_text().unwrap();
+        let (parent, subtasks) = split_subtasks(&text);
+        assert!(subtasks.is_empty(), "{:?} split into subtasks", text);
+        parse_task(&parent)
+    }
+
+    #[test]
+    fn task_flags_round_trip() {
+        let flags = TaskFlags {
+            title: Some("Write report".into()),
+            list: Some("Work".into()),
+            labels: vec!["urgent".into()],
+            minutes: Some(45),
+            notes: Some("Use the new template".into()),
+            due: Some("2024-06-07".into()),
+        };
+        let (task, refs) = round_trip(&flags);
+        assert_eq!(task.title, "Write report");
+        assert_eq!(refs.lists, ["Work"]);
+        assert_eq!(refs.labels, ["urgent"]);
+        assert_eq!(task.duration_minutes, Some(45));
+        assert_eq!(task.notes, "Use the new template");
+        assert_eq!(task.timeless_due_at.as_deref(), Some("2024-06-07"));
+    }
+
+    #[test]
+    fn task_flags_with_spaces_and_quotes_round_trip() {
+        let flags = TaskFlags {
+            title: Some("Fix  the \"shelf\"".into()),
+            list: Some("Home Projects".into()),
+            labels: vec!["on hold".into(), "say \"hi\"".into(), "C:\\temp".into()],
+            ..Default::default()
+        };
+        let (task, refs) = round_trip(&flags);
+        assert_eq!(task.title, "Fix the \"shelf\"");
+        assert_eq!(refs.lists, ["Home Projects"]);
+        assert_eq!(refs.labels, ["on hold", "say \"hi\"", "C:\\temp"]);
+    }
+
+    #[test]
+    fn task_flags_with_sigils_round_trip() {
+        let flags = TaskFlags {
+            title: Some("Email @sam about .files ^fri :30 *daily !1 > later n: soon".into()),
+            list: Some("a -- b".into()),
+            labels: vec![
+                "x n: y".into(),
+                "p > q".into(),
+                ".dotted".into(),
+                "@at".into(),
+            ],
+            ..Default::default()
+        };
+        let (task, refs) = round_trip(&flags);
+        assert_eq!(
+            task.title,
+            "Email @sam about .files ^fri :30 *daily !1 > later n: soon"
+        );
+        assert_eq!(refs.lists, ["a -- b"]);
+        assert_eq!(refs.labels, ["x n: y", "p > q", ".dotted", "@at"]);
+        assert_eq!(task.timeless_due_at, None);
+        assert_eq!(task.duration_minutes, None);
+        assert!(task.notes.is_empty());
+    }
+
+    #[test]
+    fn task_flags_notes_are_not_subtasks() {
+        let flags = TaskFlags {
+            title: Some("Pack".into()),
+            notes: Some("Checklist:\n  - charger\n\t- passport".into()),
+            ..Default::default()
+        };
+        let (task, _) = round_trip(&flags);
+        assert_eq!(task.notes, "Checklist:\n- charger\n- passport");
+    }
+
+    #[test]
+    fn task_flags_need_a_title() {
+        let flags = TaskFlags {
+            title: Some("   ".into()),
+            list: Some("Work".into()),
+            ..Default::default()
+        };
+        assert!(flags.to_task_text().is_err());
+    }
+
+    #[test]
+    fn next_link_among_several() {
+        let header = "<https://api.example.com/lists?page=1>; rel=\"prev\", \
+                      <https://api.example.com/lists?page=3>; rel=\"next\", \
+                      <https://api.example.com/lists?page=9>; rel=\"last\"";
+        assert_eq!(
+            next_link(header).as_deref(),
+            Some("https://api.example.com/lists?page=3")
+        );
+    }
+
+    #[test]
+    fn next_link_with_several_relations() {
+        let header = "</lists?page=1>; rel=\"first prev\", </lists?page=3>; rel=\"next last\"";
+        assert_eq!(next_link(header).as_deref(), Some("/lists?page=3"));
+    }
+
+    #[test]
+    fn next_link_unquoted_and_any_case() {
+        assert_eq!(next_link("</p2>;rel=next").as_deref(), Some("/p2"));
+        assert_eq!(next_link("</p2> ; REL = \"Next\"").as_deref(), Some("/p2"));
     }
 
-    // Process current input
-    if !input.is_empty() {
-        if let Err(e) = process_task(&input, &api_key) {
-            eprintln!("Failed to send task: {}", e);
-            let _ = add_to_cache(&input);
-            send_notification("Failed to send task");
+    #[test]
+    fn next_link_with_quoted_params() {
+        let header = "</p1>; title=\"a, b; rel=next\"; rel=\"prev\", \
+                      </p3>; title=\"next, please\"; rel=\"next\"";
+        assert_eq!(next_link(header).as_deref(), Some("/p3"));
+    }
+
+    #[test]
+    fn next_link_missing() {
+        assert_eq!(next_link("</p1>; rel=\"prev\", </p9>; rel=\"last\""), None);
+        assert_eq!(next_link("</p2>; rel=\"nextish\""), None);
+        assert_eq!(next_link("</p2>"), None);
+        assert_eq!(next_link(""), None);
+    }
+
+    /// Each word's text, with a `\\` in front of literal ones
+    fn words(input: &str) -> Vec<String> {
+        tokenize(input)
+            .into_iter()
+            .map(|word| match word.literal {
+                true => format!("\\{}", word.text),
+                false => word.text,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_splits_on_whitespace() {
+        assert_eq!(words("  Buy\tmilk  @Errands "), ["Buy", "milk", "@Errands"]);
+        assert!(words("").is_empty());
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn tokenize_quoted_names() {
+        assert_eq!(
+            words("@\"Home Projects\" Fix shelf"),
+            ["@Home Projects", "Fix", "shelf"]
+        );
+        assert_eq!(words(".\"to read\""), [".to read"]);
+        assert_eq!(words("+\"Sam Lee\""), ["+Sam Lee"]);
+    }
+
+    #[test]
+    fn tokenize_quotes_only_after_a_sigil() {
+        assert_eq!(words("Read \"Dune\""), ["Read", "\"Dune\""]);
+        assert_eq!(words("@Home\"s"), ["@Home\"s"]);
+    }
+
+    #[test]
+    fn tokenize_unclosed_quote_takes_the_rest() {
+        assert_eq!(words("@\"Home Projects"), ["@Home Projects"]);
+    }
+
+    #[test]
+    fn tokenize_escaped_whitespace_and_quotes() {
+        assert_eq!(words("@Home\\ Projects done"), ["@Home Projects", "done"]);
+        assert_eq!(words("say \\\"hi\\\""), ["say", "\"hi\""]);
+        assert_eq!(words("C:\\\\temp"), ["C:\\temp"]);
+    }
+
+    #[test]
+    fn tokenize_escaped_sigils_are_literal() {
+        assert_eq!(words("Email \\@sam"), ["Email", "\\@sam"]);
+        assert_eq!(words("\\.profile"), ["\\.profile"]);
+        // Only at the start of a word
+        assert_eq!(words("a\\@b"), ["a\\@b"]);
+    }
+
+    #[test]
+    fn tokenize_tokens_at_the_start_and_end() {
+        assert_eq!(words("@Work report ^fri"), ["@Work", "report", "^fri"]);
+        assert_eq!(words("\\@Work"), ["\\@Work"]);
+        // A lone backslash at the end has nothing to escape
+        assert_eq!(words("report \\"), ["report", "\\"]);
+    }
+
+    #[test]
+    fn sigil_token_round_trips() {
+        for name in [
+            "Work",
+            "Home Projects",
+            "Say \"hi\"",
+            "C:\\temp",
+            "tab\tname",
+        ] {
+            let token = sigil_token('@', name);
+            let parsed = tokenize(&token);
+            assert_eq!(parsed.len(), 1, "{}", token);
+            assert_eq!(parsed[0].text, format!("@{}", name));
+            assert!(!parsed[0].literal);
         }
+        assert_eq!(sigil_token('.', "to read"), ".to\\ read");
+    }
+
+    #[test]
+    fn escaped_sigil_stays_in_the_title() {
+        let (task, refs) = parse_task("Email \\@sam about \\.files @Work");
+        assert_eq!(task.title, "Email @sam about .files");
+        assert_eq!(refs.lists, ["Work"]);
+    }
+
+    #[test]
+    fn raw_marker_keeps_the_rest_as_typed() {
+        let (task, refs) = parse_task("Post @Work -- about @home and ^fri");
+        assert_eq!(task.title, "Post about @home and ^fri");
+        assert_eq!(refs.lists, ["Work"]);
+        assert_eq!(task.timeless_due_at, None);
+    }
+
+    #[test]
+    fn raw_marker_only_counts_on_its_own() {
+        let (task, _) = parse_task("Fix --verbose flag");
+        assert_eq!(task.title, "Fix --verbose flag");
+        assert_eq!(find_raw_marker("a --b"), None);
+        assert_eq!(find_raw_marker("-- a"), Some(0));
+        assert_eq!(find_raw_marker("a --"), Some(2));
+    }
+
+    #[test]
+    fn duration_in_minutes() {
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("45m"), Some(45));
+    }
+
+    #[test]
+    fn duration_in_hours() {
+        assert_eq!(parse_duration("1h"), Some(60));
+        assert_eq!(parse_duration("1.5h"), Some(90));
+        assert_eq!(parse_duration("2H"), Some(120));
+    }
+
+    #[test]
+    fn duration_in_hours_and_minutes() {
+        assert_eq!(parse_duration("1h30m"), Some(90));
+        assert_eq!(parse_duration("1h30"), Some(90));
+    }
+
+    #[test]
+    fn duration_bare_number_only_follows_hours() {
+        assert_eq!(parse_duration("30m15"), None);
+    }
+
+    #[test]
+    fn duration_must_be_positive() {
+        assert_eq!(parse_duration("-5"), None);
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("0h"), None);
+        assert_eq!(parse_duration("0h0m"), None);
+    }
+
+    #[test]
+    fn duration_rejects_other_text() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("1.5"), None);
+        assert_eq!(parse_duration("1d"), None);
+        assert_eq!(parse_duration("h"), None);
+    }
+
+    #[test]
+    fn failed_subtask_is_queued_with_its_parent() {
+        let parent = TaskRequest {
+            title: "Plan trip".into(),
+            list_id: Some("list-travel".into()),
+            ..Default::default()
+        };
+        let entry = unsent_subtask("Book flights", "key-1", Some("task-1"), &parent);
+        // As it's written to and read back from the queue file
+        let line = serde_json::to_string(&entry).unwrap();
+        let queued: queue::Entry = serde_json::from_str(&line).unwrap();
+        assert_eq!(queued.idempotency_key(), "key-1");
+
+        let capture = Capture::queued(&queued, true);
+        let mut subtask = TaskRequest {
+            title: "Book flights".into(),
+            ..Default::default()
+        };
+        capture.prepare(&mut subtask);
+        assert_eq!(subtask.parent_id.as_deref(), Some("task-1"));
+        assert_eq!(subtask.list_id.as_deref(), Some("list-travel"));
+    }
+
+    #[test]
+    fn queued_subtask_keeps_its_own_list() {
+        let parent = TaskRequest {
+            list_id: Some("list-travel".into()),
+            ..Default::default()
+        };
+        let entry = unsent_subtask("Renew passport", "key-2", Some("task-1"), &parent);
+        let mut subtask = TaskRequest {
+            list_id: Some("list-errands".into()),
+            ..Default::default()
+        };
+        Capture::queued(&entry, true).prepare(&mut subtask);
+        assert_eq!(subtask.parent_id.as_deref(), Some("task-1"));
+        assert_eq!(subtask.list_id.as_deref(), Some("list-errands"));
+    }
+
+    #[test]
+    fn subtask_without_parent_id_is_queued_alone() {
+        let entry = unsent_subtask("Book flights", "key-3", None, &TaskRequest::default());
+        assert_eq!(entry.parent, None);
+    }
+
+    #[test]
+    fn single_task_is_not_split_subtasks() {
+        assert_eq!(
+            split_subtasks("Buy milk @Errands"),
+            ("Buy milk @Errands".into(), vec![])
+        );
+    }
+
+    #[test]
+    fn segments_after_the_parent_are_subtasks() {
+        assert_eq!(
+            split_subtasks("Plan trip @Travel > Book flights > Renew passport ^fri"),
+            (
+                "Plan trip @Travel".into(),
+                vec!["Book flights".into(), "Renew passport ^fri".into()]
+            )
+        );
+    }
+
+    #[test]
+    fn empty_segments_are_dropped() {
+        assert_eq!(
+            split_subtasks("Plan trip >  > Book flights > "),
+            ("Plan trip".into(), vec!["Book flights".into()])
+        );
+    }
+
+    #[test]
+    fn greater_than_needs_spaces_around_it() {
+        assert_eq!(
+            split_subtasks("Check 5>3 and a->b"),
+            ("Check 5>3 and a->b".into(), vec![])
+        );
+    }
+
+    #[test]
+    fn marker_after_raw_title_is_kept() {
+        assert_eq!(
+            split_subtasks("Write post -- on a > b"),
+            ("Write post -- on a > b".into(), vec![])
+        );
+        assert_eq!(
+            split_subtasks("Plan > Write post -- on a > b"),
+            ("Plan".into(), vec!["Write post -- on a > b".into()])
+        );
+    }
+
+    #[test]
+    fn indented_lines_are_subtasks() {
+        assert_eq!(
+            split_subtasks("Plan trip\n  - Book flights\n  * Pack\n    Renew passport"),
+            (
+                "Plan trip".into(),
+                vec![
+                    "Book flights".into(),
+                    "Pack".into(),
+                    "Renew passport".into()
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn mixed_indentation_and_notes() {
+        assert_eq!(
+            split_subtasks(
+                "Plan trip > Book flights\n\t- Pack\nRemember the charger\n  Renew passport"
+            ),
+            (
+                "Plan trip\nRemember the charger".into(),
+                vec![
+                    "Book flights".into(),
+                    "Pack".into(),
+                    "Renew passport".into()
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn empty_parent_is_not_split_subtasks() {
+        assert_eq!(
+            split_subtasks(" > Book flights"),
+            (" > Book flights".into(), vec![])
+        );
+        assert_eq!(split_subtasks("\n  - Pack"), ("\n  - Pack".into(), vec![]));
+    }
+
+    #[test]
+    fn json_task_is_not_split_subtasks() {
+        let json = r#"{"title": "Plan > Book"}"#;
+        assert_eq!(split_subtasks(json), (json.into(), vec![]));
     }
 }
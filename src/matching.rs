@@ -0,0 +1,493 @@
+use icu_normalizer::DecomposingNormalizerBorrowed;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// One step of the name matching pipeline, tried in the configured order
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// User-defined shortcut from the `[aliases]` table
+    Alias,
+    /// Exact name, ignoring case and accents
+    Exact,
+    /// Name starting with the search text
+    Prefix,
+    /// Name containing the search text's characters in order (`hsp` → "house projects")
+    Fuzzy,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct PrefixConfig {
+    /// Shortest search text that may match by prefix
+    pub min_length: usize,
+}
+
+impl Default for PrefixConfig {
+    fn default() -> Self {
+        PrefixConfig { min_length: 1 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FuzzyConfig {
+    /// Lowest score, from 0 to 1, that counts as a match
+    pub threshold: f64,
+}
+
+impl Default for FuzzyConfig {
+    fn default() -> Self {
+        FuzzyConfig { threshold: 0.6 }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MatchingConfig {
+    pub stages: Vec<Stage>,
+    pub prefix: PrefixConfig,
+    pub fuzzy: FuzzyConfig,
+    /// Lowercased shortcut → name, filled from the top-level `[aliases]` table
+    #[serde(skip)]
+    pub aliases: HashMap<String, String>,
+}
+
+impl Default for MatchingConfig {
+    fn default() -> Self {
+        MatchingConfig {
+            stages: vec![Stage::Alias, Stage::Exact, Stage::Prefix, Stage::Fuzzy],
+            prefix: PrefixConfig::default(),
+            fuzzy: FuzzyConfig::default(),
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+/// The outcome of looking a name up
+#[derive(Debug, PartialEq)]
+pub enum Lookup {
+    Found(String),
+    /// Several names match equally well; holds them, sorted
+    Ambiguous(Vec<String>),
+    Missing,
+}
+
+/// Fold a name for comparison: compatibility-decompose it (NFKD), drop the
+/// accents that leaves behind and lowercase it, so `Café`, `cafe` and `ｃａｆｅ`
+/// all compare equal
+pub fn fold(name: &str) -> String {
+    let decomposed = DecomposingNormalizerBorrowed::new_nfkd().normalize(name);
+    decomposed
+        .chars()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c == 'ς' { 'σ' } else { c })
+        .collect::<String>()
+        .replace('ß', "ss")
+}
+
+/// Combining diacritical marks, as left separate by NFKD
+fn is_combining_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}')
+}
+
+/// A cached name alongside its folded form
+struct Entry<'a> {
+    folded: String,
+    name: &'a String,
+    id: &'a String,
+}
+
+impl MatchingConfig {
+    /// Resolve a list or label name to its id using the enabled stages
+    pub fn find(&self, cache: &HashMap<String, String>, search: &str) -> Lookup {
+        let entries: Vec<Entry> = cache
+            .iter()
+            .map(|(name, id)| Entry {
+                folded: fold(name),
+                name,
+                id,
+            })
+            .collect();
+        self.find_with(cache, &entries, search, true)
+    }
+
+    fn find_with(
+        &self,
+        cache: &HashMap<String, String>,
+        entries: &[Entry],
+        search: &str,
+        use_aliases: bool,
+    ) -> Lookup {
+        let search_folded = fold(search);
+
+        for stage in &self.stages {
+            let found = match stage {
+                // The alias target goes through the other stages, but never
+                // through aliases again, so aliases can't loop
+                Stage::Alias if use_aliases => match self.aliases.get(&search_folded) {
+                    Some(target) => self.find_with(cache, entries, target, false),
+                    None => Lookup::Missing,
+                },
+                Stage::Alias => Lookup::Missing,
+                // The name as typed wins over others that only fold the same
+                Stage::Exact => match cache.get(&search.to_lowercase()) {
+                    Some(id) => Lookup::Found(id.clone()),
+                    None => unique(
+                        entries
+                            .iter()
+                            .filter(|entry| entry.folded == search_folded)
+                            .collect(),
+                    ),
+                },
+                Stage::Prefix => self.find_prefix(entries, &search_folded),
+                Stage::Fuzzy => self.find_fuzzy(entries, &search_folded),
+            };
+            if found != Lookup::Missing {
+                return found;
+            }
+        }
+
+        Lookup::Missing
+    }
+
+    /// The name starting with `search`, if exactly one does
+    fn find_prefix(&self, entries: &[Entry], search: &str) -> Lookup {
+        if search.chars().count() < self.prefix.min_length {
+            return Lookup::Missing;
+        }
+        unique(
+            entries
+                .iter()
+                .filter(|entry| entry.folded.starts_with(search))
+                .collect(),
+        )
+    }
+
+    /// The best-scoring name containing `search` as a subsequence
+    fn find_fuzzy(&self, entries: &[Entry], search: &str) -> Lookup {
+        // A single character would match almost anything
+        if search.chars().count() < 2 {
+            return Lookup::Missing;
+        }
+        let scored: Vec<(f64, &Entry)> = entries
+            .iter()
+            .filter_map(|entry| fuzzy_score(search, &entry.folded).map(|score| (score, entry)))
+            .filter(|(score, _)| *score >= self.fuzzy.threshold)
+            .collect();
+        let best = scored
+            .iter()
+            .map(|(score, _)| *score)
+            .fold(f64::NEG_INFINITY, f64::max);
+        unique(
+            scored
+                .into_iter()
+                .filter(|(score, _)| *score == best)
+                .map(|(_, entry)| entry)
+                .collect(),
+        )
+    }
+}
+
+/// The id of the matching entries, if they all share one, otherwise their
+/// names, sorted
+fn unique(mut matches: Vec<&Entry>) -> Lookup {
+    matches.sort_by(|a, b| a.name.cmp(b.name));
+    match matches.as_slice() {
+        [] => Lookup::Missing,
+        [first, rest @ ..] if rest.iter().all(|other| other.id == first.id) => {
+            Lookup::Found(first.id.clone())
+        }
+        _ => Lookup::Ambiguous(
+            matches
+                .into_iter()
+                .map(|entry| entry.name.clone())
+                .collect(),
+        ),
+    }
+}
+
+/// Score how well `search` matches `text` as a subsequence, from 0 to 1, or
+/// None if it isn't one. Each character scores 1, plus 2 at the start of a
+/// word and 1 when it directly follows the previous match.
+fn fuzzy_score(search: &str, text: &str) -> Option<f64> {
+    let search: Vec<char> = search.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (n, m) = (search.len(), text.len());
+
+    // best[i][j][c]: best score for search[i..] within text[j..], where c
+    // says whether text[j - 1] matched search[i - 1]
+    const NONE: i32 = i32::MIN / 2;
+    let mut best = vec![vec![[NONE; 2]; m + 1]; n + 1];
+    for row in best[n].iter_mut() {
+        *row = [0, 0];
+    }
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            for consecutive in 0..2 {
+                let skip = best[i][j + 1][0];
+                let take = if search[i] == text[j] {
+                    let word_start = j == 0 || !text[j - 1].is_alphanumeric();
+                    let bonus = if word_start {
+                        2
+                    } else {
+                        i32::from(consecutive == 1 && i > 0)
+                    };
+                    1 + bonus + best[i + 1][j + 1][1]
+                } else {
+                    NONE
+                };
+                best[i][j][consecutive] = skip.max(take);
+            }
+        }
+    }
+
+    let score = best[0][0][0];
+    (score >= 0).then(|| score as f64 / (3 * n) as f64)
+}
+
+/// Edit distance between two strings in characters, counting a swap of two
+/// neighbouring characters (`wrok` → `work`) as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Cached names close to `search`, nearest first, for "did you mean" hints
+pub fn suggest(cache: &HashMap<String, String>, search: &str) -> Vec<String> {
+    let search = fold(search);
+    // Allow roughly one typo per three characters
+    let limit = (search.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &String)> = cache
+        .keys()
+        .map(|name| (edit_distance(&search, &fold(name)), name))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(3)
+        .map(|(_, name)| name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A name cache as the CLI keeps it, keyed by lowercased name
+    fn cache(names: &[(&str, &str)]) -> HashMap<String, String> {
+        names
+            .iter()
+            .map(|(name, id)| (name.to_string(), id.to_string()))
+            .collect()
+    }
+
+    fn only(stages: &[Stage]) -> MatchingConfig {
+        MatchingConfig {
+            stages: stages.to_vec(),
+            ..MatchingConfig::default()
+        }
+    }
+
+    fn found(id: &str) -> Lookup {
+        Lookup::Found(id.to_string())
+    }
+
+    fn ambiguous(names: &[&str]) -> Lookup {
+        Lookup::Ambiguous(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    #[test]
+    fn fold_drops_case_and_accents() {
+        assert_eq!(fold("Café"), "cafe");
+        assert_eq!(fold("CRÈME BRÛLÉE"), "creme brulee");
+        assert_eq!(fold("Ångström"), "angstrom");
+    }
+
+    #[test]
+    fn fold_uses_compatibility_forms() {
+        assert_eq!(fold("ｃａｆｅ"), "cafe");
+        assert_eq!(fold("ﬁling"), "filing");
+        assert_eq!(fold("Straße"), "strasse");
+        assert_eq!(fold("ΟΔΟΣ"), fold("οδος"));
+    }
+
+    #[test]
+    fn exact_name_beats_prefix() {
+        let names = cache(&[("work", "1"), ("workshop", "2")]);
+        let config = MatchingConfig::default();
+        assert_eq!(config.find(&names, "Work"), found("1"));
+        assert_eq!(config.find(&names, "works"), found("2"));
+    }
+
+    #[test]
+    fn exact_name_as_typed_beats_folded() {
+        let names = cache(&[("café", "1"), ("cafe", "2")]);
+        let config = MatchingConfig::default();
+        assert_eq!(config.find(&names, "Café"), found("1"));
+        assert_eq!(config.find(&names, "cafe"), found("2"));
+        assert_eq!(config.find(&names, "cafè"), ambiguous(&["cafe", "café"]));
+    }
+
+    #[test]
+    fn folded_name_matches_exactly() {
+        let names = cache(&[("café", "1"), ("cafeteria", "2")]);
+        assert_eq!(MatchingConfig::default().find(&names, "CAFE"), found("1"));
+    }
+
+    #[test]
+    fn prefix_beats_fuzzy() {
+        let names = cache(&[("groceries", "1"), ("garage repairs", "2")]);
+        let config = MatchingConfig::default();
+        assert_eq!(config.find(&names, "gr"), found("1"));
+        assert_eq!(config.find(&names, "grp"), found("2"));
+    }
+
+    #[test]
+    fn shared_prefix_is_ambiguous() {
+        let names = cache(&[("home", "1"), ("house", "2"), ("work", "3")]);
+        assert_eq!(
+            MatchingConfig::default().find(&names, "ho"),
+            ambiguous(&["home", "house"])
+        );
+    }
+
+    #[test]
+    fn names_sharing_an_id_are_not_ambiguous() {
+        let names = cache(&[("café", "1"), ("cafe", "1")]);
+        assert_eq!(MatchingConfig::default().find(&names, "caf"), found("1"));
+    }
+
+    #[test]
+    fn prefix_respects_min_length() {
+        let names = cache(&[("groceries", "1")]);
+        let mut config = only(&[Stage::Exact, Stage::Prefix]);
+        config.prefix.min_length = 3;
+        assert_eq!(config.find(&names, "gr"), Lookup::Missing);
+        assert_eq!(config.find(&names, "gro"), found("1"));
+    }
+
+    #[test]
+    fn fuzzy_matches_word_starts() {
+        let names = cache(&[("house projects", "1"), ("holiday", "2")]);
+        assert_eq!(MatchingConfig::default().find(&names, "hsp"), found("1"));
+    }
+
+    #[test]
+    fn fuzzy_tie_is_ambiguous() {
+        let names = cache(&[("home", "1"), ("house", "2")]);
+        assert_eq!(
+            only(&[Stage::Fuzzy]).find(&names, "ho"),
+            ambiguous(&["home", "house"])
+        );
+    }
+
+    #[test]
+    fn fuzzy_ignores_single_characters() {
+        let names = cache(&[("house", "1")]);
+        assert_eq!(only(&[Stage::Fuzzy]).find(&names, "h"), Lookup::Missing);
+    }
+
+    #[test]
+    fn fuzzy_respects_threshold() {
+        let names = cache(&[("house projects", "1")]);
+        let mut config = only(&[Stage::Fuzzy]);
+        config.fuzzy.threshold = 0.9;
+        assert_eq!(config.find(&names, "hsp"), Lookup::Missing);
+    }
+
+    #[test]
+    fn disabled_stages_are_skipped() {
+        let names = cache(&[("groceries", "1")]);
+        assert_eq!(only(&[Stage::Exact]).find(&names, "gro"), Lookup::Missing);
+    }
+
+    #[test]
+    fn alias_resolves_through_other_stages() {
+        let names = cache(&[("groceries", "1")]);
+        let mut config = MatchingConfig::default();
+        config.aliases.insert("food".into(), "Groc".into());
+        assert_eq!(config.find(&names, "Food"), found("1"));
+    }
+
+    #[test]
+    fn aliases_do_not_loop() {
+        let names = cache(&[("work", "1")]);
+        let mut config = MatchingConfig::default();
+        config.aliases.insert("a".into(), "b".into());
+        config.aliases.insert("b".into(), "a".into());
+        assert_eq!(config.find(&names, "a"), Lookup::Missing);
+    }
+
+    #[test]
+    fn fuzzy_score_rewards_word_starts_and_runs() {
+        assert_eq!(fuzzy_score("ab", "a b"), Some(1.0));
+        // h and p start words, s is on its own
+        assert_eq!(fuzzy_score("hsp", "house projects"), Some(7.0 / 9.0));
+        // h starts the word, o follows it
+        assert_eq!(fuzzy_score("ho", "home"), Some(5.0 / 6.0));
+    }
+
+    #[test]
+    fn fuzzy_score_needs_a_subsequence() {
+        assert_eq!(fuzzy_score("oh", "home"), None);
+        assert_eq!(fuzzy_score("homes", "home"), None);
+    }
+
+    #[test]
+    fn edit_distance_counts_swaps_as_one_edit() {
+        assert_eq!(edit_distance("work", "work"), 0);
+        assert_eq!(edit_distance("wrok", "work"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "home"), 4);
+    }
+
+    #[test]
+    fn suggest_allows_one_typo_per_three_characters() {
+        let names = cache(&[("work", "1"), ("walk", "2"), ("home", "3")]);
+        assert_eq!(suggest(&names, "wrk"), vec!["work"]);
+        assert_eq!(suggest(&names, "xyz"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn suggest_orders_nearest_first() {
+        let names = cache(&[("grocery", "1"), ("groceries", "2"), ("garage", "3")]);
+        assert_eq!(suggest(&names, "groceris"), vec!["groceries", "grocery"]);
+    }
+
+    #[test]
+    fn suggest_keeps_at_most_three() {
+        let names = cache(&[("ab", "1"), ("ac", "2"), ("ad", "3"), ("ae", "4")]);
+        assert_eq!(suggest(&names, "aa"), vec!["ab", "ac", "ad"]);
+    }
+
+    #[test]
+    fn suggest_folds_accents() {
+        let names = cache(&[("café", "1")]);
+        assert_eq!(suggest(&names, "CAFF"), vec!["café"]);
+    }
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::dates;
+use crate::term;
+use crate::{get_data_dir, write_atomic};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Samples older than this are dropped when the file is compacted
+const KEEP_DAYS: i64 = 30;
+
+/// Compact the file once it grows past this many bytes
+const COMPACT_SIZE: u64 = 256 * 1024;
+
+/// Time spent in each phase of one capture
+#[derive(Default)]
+pub struct Timings {
+    pub parse: Duration,
+    /// Resolving names, including `parse` and any fetches of uncached names
+    pub resolve: Duration,
+    /// Sending the task and its subtasks
+    pub network: Duration,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sample {
+    time: i64,
+    parse_ms: f64,
+    resolve_ms: f64,
+    network_ms: f64,
+    total_ms: f64,
+    ok: bool,
+}
+
+impl Sample {
+    fn phase(&self, name: &str) -> f64 {
+        match name {
+            "parse" => self.parse_ms,
+            "resolve" => self.resolve_ms,
+            "network" => self.network_ms,
+            _ => self.total_ms,
+        }
+    }
+}
+
+fn get_metrics_path() -> PathBuf {
+    get_data_dir().join("metrics.jsonl")
+}
+
+/// Milliseconds, rounded to the microsecond
+fn ms(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+fn load() -> Vec<Sample> {
+    fs::read_to_string(get_metrics_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn append(sample: &Sample) -> io::Result<()> {
+    let path = get_metrics_path();
+    if fs::metadata(&path).is_ok_and(|m| m.len() > COMPACT_SIZE) {
+        let cutoff = sample.time - KEEP_DAYS * DAY;
+        let kept: String = load()
+            .iter()
+            .filter(|s| s.time >= cutoff)
+            .filter_map(|s| serde_json::to_string(s).ok())
+            .map(|line| line + "\n")
+            .collect();
+        write_atomic(&path, kept)?;
+    }
+
+    let line = serde_json::to_string(sample).map_err(io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Record how long a capture took. Metrics are best effort and never fail a capture.
+pub fn record(timings: &Timings, total: Duration, ok: bool) {
+    let sample = Sample {
+        time: dates::now_unix(),
+        parse_ms: ms(timings.parse),
+        resolve_ms: ms(timings.resolve.saturating_sub(timings.parse)),
+        network_ms: ms(timings.network),
+        total_ms: ms(total),
+        ok,
+    };
+    let _ = append(&sample);
+}
+
+fn percentile(values: &mut [f64], p: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = ((values.len() - 1) as f64 * p).round() as usize;
+    values[rank]
+}
+
+fn format_ms(value: f64) -> String {
+    if value >= 1000.0 {
+        format!("{:.2}s", value / 1000.0)
+    } else if value >= 10.0 {
+        format!("{:.0}ms", value)
+    } else {
+        format!("{:.1}ms", value)
+    }
+}
+
+/// Print p50/p95 per phase over the last week, and warn if the last day was much slower
+pub fn report() {
+    let now = dates::now_unix();
+    let week: Vec<Sample> = load()
+        .into_iter()
+        .filter(|s| s.time >= now - 7 * DAY)
+        .collect();
+    let failed = week.iter().filter(|s| !s.ok).count();
+    let phases: Vec<(&str, f64, f64)> = if week.is_empty() {
+        Vec::new()
+    } else {
+        ["parse", "resolve", "network", "total"]
+            .into_iter()
+            .map(|name| {
+                let mut values: Vec<f64> = week.iter().map(|s| s.phase(name)).collect();
+                (
+                    name,
+                    percentile(&mut values, 0.5),
+                    percentile(&mut values, 0.95),
+                )
+            })
+            .collect()
+    };
+    let warnings = warnings(&week, now);
+
+    if term::json() {
+        let phases: serde_json::Map<String, serde_json::Value> = phases
+            .iter()
+            .map(|(name, p50, p95)| {
+                (
+                    name.to_string(),
+                    serde_json::json!({ "p50_ms": p50, "p95_ms": p95 }),
+                )
+            })
+            .collect();
+        term::print_json(&serde_json::json!({
+            "captures": week.len(),
+            "failed": failed,
+            "phases": phases,
+            "warnings": warnings,
+        }));
+        return;
+    }
+
+    if week.is_empty() {
+        println!("No captures recorded in the last 7 days");
+        return;
+    }
+    println!(
+        "Captures in the last 7 days: {} ({} failed)",
+        week.len(),
+        failed
+    );
+    println!("{:<10} {:>8} {:>8}", "", "p50", "p95");
+    for (name, p50, p95) in phases {
+        println!("{:<10} {:>8} {:>8}", name, format_ms(p50), format_ms(p95));
+    }
+    for warning in warnings {
+        println!("Warning: {}", warning);
+    }
+}
+
+/// Signs of trouble in the last day compared with the rest of the week
+fn warnings(week: &[Sample], now: i64) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let (mut recent, mut earlier): (Vec<&Sample>, Vec<&Sample>) =
+        week.iter().partition(|s| s.time >= now - DAY);
+    recent.retain(|s| s.ok);
+    earlier.retain(|s| s.ok);
+    if recent.len() >= 5 && earlier.len() >= 5 {
+        let mut recent_network: Vec<f64> = recent.iter().map(|s| s.network_ms).collect();
+        let mut earlier_network: Vec<f64> = earlier.iter().map(|s| s.network_ms).collect();
+        let now_p50 = percentile(&mut recent_network, 0.5);
+        let before_p50 = percentile(&mut earlier_network, 0.5);
+        if now_p50 > before_p50 * 2.0 && now_p50 - before_p50 > 100.0 {
+            warnings.push(format!(
+                "network time is up from {} to {} (p50) in the last day; the API or your network may be degraded",
+                format_ms(before_p50),
+                format_ms(now_p50)
+            ));
+        }
+    }
+    let recent_all = week.iter().filter(|s| s.time >= now - DAY).count();
+    let recent_failed = week.iter().filter(|s| s.time >= now - DAY && !s.ok).count();
+    if recent_all >= 5 && recent_failed * 5 > recent_all {
+        warnings.push(format!(
+            "{} of {} captures failed in the last day",
+            recent_failed, recent_all
+        ));
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_000_000;
+
+    fn sample(age_hours: i64, network_ms: f64, ok: bool) -> Sample {
+        Sample {
+            time: NOW - age_hours * 60 * 60,
+            parse_ms: 0.1,
+            resolve_ms: 1.0,
+            network_ms,
+            total_ms: network_ms + 1.1,
+            ok,
+        }
+    }
+
+    #[test]
+    fn percentiles_pick_the_nearest_rank() {
+        let mut values = vec![5.0, 1.0, 4.0, 2.0, 3.0];
+        assert_eq!(percentile(&mut values, 0.5), 3.0);
+        assert_eq!(percentile(&mut values, 0.95), 5.0);
+        assert_eq!(percentile(&mut values, 0.0), 1.0);
+        assert_eq!(percentile(&mut [7.0], 0.95), 7.0);
+        assert_eq!(percentile(&mut [], 0.5), 0.0);
+    }
+
+    #[test]
+    fn steady_week_has_no_warnings() {
+        let week: Vec<Sample> = (0..20).map(|i| sample(i * 8, 200.0, true)).collect();
+        assert!(warnings(&week, NOW).is_empty());
+    }
+
+    #[test]
+    fn slower_last_day_is_a_warning() {
+        let mut week: Vec<Sample> = (0..5).map(|i| sample(i, 900.0, true)).collect();
+        week.extend((0..5).map(|i| sample(48 + i, 200.0, true)));
+        assert_eq!(
+            warnings(&week, NOW),
+            ["network time is up from 200ms to 900ms (p50) in the last day; the API or your network may be degraded"]
+        );
+    }
+
+    #[test]
+    fn slowdowns_under_100ms_or_without_enough_samples_are_ignored() {
+        let mut week: Vec<Sample> = (0..5).map(|i| sample(i, 90.0, true)).collect();
+        week.extend((0..5).map(|i| sample(48 + i, 30.0, true)));
+        assert!(warnings(&week, NOW).is_empty());
+        let few: Vec<Sample> = (0..4)
+            .map(|i| sample(i, 900.0, true))
+            .chain((0..5).map(|i| sample(48 + i, 200.0, true)))
+            .collect();
+        assert!(warnings(&few, NOW).is_empty());
+    }
+
+    #[test]
+    fn many_failures_in_the_last_day_are_a_warning() {
+        let mut week: Vec<Sample> = (0..3).map(|i| sample(i, 200.0, true)).collect();
+        week.extend((0..2).map(|i| sample(i, 0.0, false)));
+        assert_eq!(
+            warnings(&week, NOW),
+            ["2 of 5 captures failed in the last day"]
+        );
+        week[3].ok = true;
+        assert!(warnings(&week, NOW).is_empty());
+    }
+}
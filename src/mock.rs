@@ -0,0 +1,370 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::dates;
+use crate::get_data_dir;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Whether requests go to the built-in mock instead of the API
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Every request the mock answered, one JSON object per line:
+/// `GODSPEED_MOCK_LOG`, or `mock-requests.jsonl` in the mock's data directory
+pub fn get_log_path() -> PathBuf {
+    env::var("GODSPEED_MOCK_LOG")
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_data_dir().join("mock-requests.jsonl"))
+}
+
+/// What the mock account holds. It starts the same every run; lists, labels
+/// and tasks created are kept until the run ends.
+struct Account {
+    lists: Vec<(String, String)>,
+    labels: Vec<(String, String)>,
+    members: Vec<(String, String)>,
+    tasks: usize,
+    /// Tasks created so far by idempotency key, so a retry gets the same task
+    keys: HashMap<String, Value>,
+}
+
+impl Account {
+    fn new() -> Self {
+        let named = |kind: &str, names: &[&str]| {
+            names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (format!("{}-{}", kind, i + 1), name.to_string()))
+                .collect()
+        };
+        Account {
+            lists: named("list", &["Inbox", "Work", "Personal"]),
+            labels: named("label", &["urgent", "waiting", "errand"]),
+            members: named("member", &["Sam"]),
+            tasks: 0,
+            keys: HashMap::new(),
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Start the mock on a free local port, returning its base URL. It answers
+/// until the process exits.
+pub fn start() -> io::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let account = Arc::new(Mutex::new(Account::new()));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let account = account.clone();
+            // Several tasks are sent at once, each on its own connection
+            thread::spawn(move || serve(stream, &account));
+        }
+    });
+    Ok(url)
+}
+
+/// Answer requests on one kept-alive connection until the client closes it
+fn serve(stream: TcpStream, account: &Mutex<Account>) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    while let Ok(Some(request)) = read_request(&mut reader) {
+        let (status, body) = {
+            let mut account = account.lock().unwrap_or_else(|e| e.into_inner());
+            let answer = respond(&request, &mut account);
+            record(&request, answer.0);
+            answer
+        };
+        let body = body.to_string();
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// The next request on the connection, or None once it's closed
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    let method = method.to_string();
+    // Query strings, such as a page cursor, don't change the answer
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    let length = headers
+        .get("content-length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(Request {
+        method,
+        path,
+        headers,
+        body,
+    }))
+}
+
+/// The status line and JSON body the API would answer `request` with
+fn respond(request: &Request, account: &mut Account) -> (&'static str, Value) {
+    if !request
+        .headers
+        .get("authorization")
+        .is_some_and(|auth| auth.starts_with("Bearer "))
+    {
+        return ("401 Unauthorized", json!({ "message": "Missing API key" }));
+    }
+    let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+    let names = |items: &[(String, String)]| -> Value {
+        items
+            .iter()
+            .map(|(id, name)| json!({ "id": id, "name": name }))
+            .collect()
+    };
+    match (request.method.as_str(), request.path.trim_end_matches('/')) {
+        ("GET", "/lists") => ("200 OK", json!({ "lists": names(&account.lists) })),
+        ("GET", "/labels") => ("200 OK", json!({ "labels": names(&account.labels) })),
+        ("GET", "/members") => ("200 OK", json!({ "members": names(&account.members) })),
+        ("GET", "/user") => (
+            "200 OK",
+            json!({
+                "user": { "id": "member-1", "name": "Sam", "email": "sam@example.com" },
+                "workspace": { "id": "workspace-1", "name": "Mock" },
+            }),
+        ),
+        ("POST", "/tasks") => create_task(request, body, account),
+        ("POST", path @ ("/lists" | "/labels")) => {
+            let Some(name) = body.get("name").and_then(Value::as_str) else {
+                return unprocessable("name", "can't be blank");
+            };
+            let (kind, items) = match path {
+                "/lists" => ("list", &mut account.lists),
+                _ => ("label", &mut account.labels),
+            };
+            let id = format!("{}-{}", kind, items.len() + 1);
+            items.push((id.clone(), name.to_string()));
+            ("200 OK", json!({ kind: { "id": id, "name": name } }))
+        }
+        _ => ("404 Not Found", json!({ "message": "Not found" })),
+    }
+}
+
+fn create_task(request: &Request, mut task: Value, account: &mut Account) -> (&'static str, Value) {
+    let key = request.headers.get("idempotency-key");
+    if let Some(task) = key.and_then(|key| account.keys.get(key)) {
+        return ("200 OK", json!({ "task": task }));
+    }
+    if task
+        .get("title")
+        .and_then(Value::as_str)
+        .is_none_or(|title| title.trim().is_empty())
+    {
+        return unprocessable("title", "can't be blank");
+    }
+    let known = |field: &str, items: &[(String, String)]| {
+        task.get(field)
+            .and_then(Value::as_str)
+            .is_none_or(|id| items.iter().any(|(known, _)| known == id))
+    };
+    if !known("list_id", &account.lists) {
+        return unprocessable("list_id", "not found");
+    }
+    if !known("assignee_id", &account.members) {
+        return unprocessable("assignee_id", "not found");
+    }
+    let unknown_label = task
+        .get("label_ids")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .any(|id| !account.labels.iter().any(|(known, _)| known == id));
+    if unknown_label {
+        return unprocessable("label_ids", "not found");
+    }
+
+    account.tasks += 1;
+    task["id"] = json!(format!("task-{}", account.tasks));
+    if let Some(key) = key {
+        account.keys.insert(key.clone(), task.clone());
+    }
+    ("200 OK", json!({ "task": task }))
+}
+
+fn unprocessable(field: &str, problem: &str) -> (&'static str, Value) {
+    (
+        "422 Unprocessable Entity",
+        json!({ "message": "Validation failed", "errors": { field: [problem] } }),
+    )
+}
+
+/// Add a request to the log. The API key is left out.
+fn record(request: &Request, status: &str) {
+    let body = serde_json::from_slice::<Value>(&request.body).unwrap_or(Value::Null);
+    let mut entry = json!({
+        "at": dates::format_utc(dates::now_unix()),
+        "method": request.method,
+        "path": request.path,
+        "status": status.split(' ').next().and_then(|code| code.parse::<u16>().ok()),
+        "body": body,
+    });
+    if let Some(key) = request.headers.get("idempotency-key") {
+        entry["idempotency_key"] = json!(key);
+    }
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(get_log_path())
+        .and_then(|mut file| writeln!(file, "{}", entry));
+    if let Err(e) = written {
+        eprintln!("Warning: failed to record the mock request: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn request(method: &str, path: &str, body: Value) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: HashMap::from([("authorization".to_string(), "Bearer key".to_string())]),
+            body: body.to_string().into_bytes(),
+        }
+    }
+
+    #[test]
+    fn requests_are_read_off_a_kept_alive_connection() {
+        let raw = "POST /tasks?x=1 HTTP/1.1\r\nContent-Length: 2\r\nIdempotency-Key: k\r\n\r\n{}GET /lists HTTP/1.1\r\n\r\n";
+        let mut reader = Cursor::new(raw);
+        let first = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            (first.method.as_str(), first.path.as_str()),
+            ("POST", "/tasks")
+        );
+        assert_eq!(first.headers["idempotency-key"], "k");
+        assert_eq!(first.body, b"{}");
+        let second = read_request(&mut reader).unwrap().unwrap();
+        assert_eq!(
+            (second.method.as_str(), second.path.as_str()),
+            ("GET", "/lists")
+        );
+        assert!(read_request(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn requests_need_a_key() {
+        let mut request = request("GET", "/lists", Value::Null);
+        request.headers.clear();
+        assert_eq!(respond(&request, &mut Account::new()).0, "401 Unauthorized");
+    }
+
+    #[test]
+    fn names_are_listed_and_created() {
+        let mut account = Account::new();
+        let (status, body) = respond(&request("GET", "/lists/", Value::Null), &mut account);
+        assert_eq!(status, "200 OK");
+        assert_eq!(body["lists"][1], json!({ "id": "list-2", "name": "Work" }));
+        let created = respond(
+            &request("POST", "/labels", json!({ "name": "home" })),
+            &mut account,
+        );
+        assert_eq!(created.1["label"]["id"], "label-4");
+        let (_, labels) = respond(&request("GET", "/labels", Value::Null), &mut account);
+        assert_eq!(labels["labels"][3]["name"], "home");
+    }
+
+    #[test]
+    fn tasks_are_created_once_per_key() {
+        let mut account = Account::new();
+        let mut create = request(
+            "POST",
+            "/tasks",
+            json!({ "title": "Buy milk", "list_id": "list-1" }),
+        );
+        create
+            .headers
+            .insert("idempotency-key".to_string(), "k".to_string());
+        let (status, first) = respond(&create, &mut account);
+        assert_eq!(status, "200 OK");
+        assert_eq!(first["task"]["id"], "task-1");
+        assert_eq!(respond(&create, &mut account).1, first);
+        let other = request("POST", "/tasks", json!({ "title": "Call Sam" }));
+        assert_eq!(respond(&other, &mut account).1["task"]["id"], "task-2");
+    }
+
+    #[test]
+    fn invalid_tasks_are_refused_like_the_api() {
+        let mut account = Account::new();
+        for (task, field) in [
+            (json!({ "title": " " }), "title"),
+            (json!({ "title": "x", "list_id": "list-9" }), "list_id"),
+            (
+                json!({ "title": "x", "assignee_id": "member-9" }),
+                "assignee_id",
+            ),
+            (
+                json!({ "title": "x", "label_ids": ["label-1", "label-9"] }),
+                "label_ids",
+            ),
+        ] {
+            let (status, body) = respond(&request("POST", "/tasks", task), &mut account);
+            assert_eq!(status, "422 Unprocessable Entity");
+            assert!(body["errors"].get(field).is_some(), "{}", field);
+        }
+        assert_eq!(account.tasks, 0);
+        let (status, _) = respond(&request("DELETE", "/tasks/1", Value::Null), &mut account);
+        assert_eq!(status, "404 Not Found");
+    }
+}
@@ -0,0 +1,308 @@
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+
+use crate::on_path;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Desktop notifications at all; `GODSPEED_NOTIFY` and `--no-notify`
+    /// take precedence
+    pub enabled: bool,
+    pub backend: Backend,
+    /// A task couldn't be sent, or the run couldn't start
+    pub on_failure: bool,
+    /// A task was added
+    pub on_success: bool,
+    /// Cached tasks were sent
+    pub on_flush: bool,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        NotificationConfig {
+            enabled: true,
+            backend: Backend::Auto,
+            on_failure: true,
+            on_success: false,
+            on_flush: false,
+        }
+    }
+}
+
+/// `[notifications]`, or `notifications = false` for short
+pub fn enabled_or_table<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NotificationConfig, D::Error> {
+    struct EnabledOrTable;
+
+    impl<'de> Visitor<'de> for EnabledOrTable {
+        type Value = NotificationConfig;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("true, false or a table")
+        }
+
+        fn visit_bool<E: de::Error>(self, enabled: bool) -> Result<Self::Value, E> {
+            Ok(NotificationConfig {
+                enabled,
+                ..NotificationConfig::default()
+            })
+        }
+
+        fn visit_map<M: MapAccess<'de>>(self, map: M) -> Result<Self::Value, M::Error> {
+            NotificationConfig::deserialize(de::value::MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(EnabledOrTable)
+}
+
+/// How notifications are shown (`[notifications] backend`)
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// `terminal-notifier` on macOS when it's installed, since its
+    /// notifications can be clicked, otherwise `osascript`; a toast on
+    /// Windows; and `notify-send` or `gdbus` elsewhere, whichever is installed
+    #[default]
+    Auto,
+    Osascript,
+    TerminalNotifier,
+    NotifySend,
+    Gdbus,
+    Toast,
+    /// A line on stderr, for when there's no desktop
+    Stderr,
+    None,
+}
+
+impl Backend {
+    /// As written in the config
+    fn name(self) -> &'static str {
+        match self {
+            Backend::Auto => "auto",
+            Backend::Osascript => "osascript",
+            Backend::TerminalNotifier => "terminal-notifier",
+            Backend::NotifySend => "notify-send",
+            Backend::Gdbus => "gdbus",
+            Backend::Toast => "toast",
+            Backend::Stderr => "stderr",
+            Backend::None => "none",
+        }
+    }
+
+    fn notifier(self) -> &'static dyn Notifier {
+        match self {
+            Backend::Auto if cfg!(target_os = "macos") && on_path("terminal-notifier") => {
+                &TerminalNotifier
+            }
+            Backend::Auto if cfg!(target_os = "macos") => &Osascript,
+            Backend::Auto if cfg!(windows) => &Toast,
+            Backend::Auto if on_path("notify-send") => &NotifySend,
+            Backend::Auto if on_path("gdbus") => &Gdbus,
+            Backend::Auto | Backend::None => &Silent,
+            Backend::Osascript => &Osascript,
+            Backend::TerminalNotifier => &TerminalNotifier,
+            Backend::NotifySend => &NotifySend,
+            Backend::Gdbus => &Gdbus,
+            Backend::Toast => &Toast,
+            Backend::Stderr => &Stderr,
+        }
+    }
+}
+
+/// Something that shows a notification. `link` is opened when it's clicked,
+/// by the backends that can do that.
+trait Notifier: Sync {
+    fn show(&self, message: &str, link: Option<&str>) -> io::Result<()>;
+}
+
+const TITLE: &str = "Godspeed CLI";
+
+/// Start a notification tool without waiting for it, since some take a
+/// noticeable moment; only failing to start it is an error. It's left to
+/// finish after the run exits, and a thread reaps it while the run lasts.
+fn run(mut command: Command) -> io::Result<()> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+struct Osascript;
+
+impl Notifier for Osascript {
+    fn show(&self, message: &str, _link: Option<&str>) -> io::Result<()> {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!(
+            r#"display notification "{}" with title "{}""#,
+            message.replace('\\', "\\\\").replace('"', "\\\""),
+            TITLE
+        ));
+        run(command)
+    }
+}
+
+/// terminal-notifier (`brew install terminal-notifier`), whose
+/// notifications are grouped, shown under its own icon and open a link when
+/// clicked
+struct TerminalNotifier;
+
+impl Notifier for TerminalNotifier {
+    fn show(&self, message: &str, link: Option<&str>) -> io::Result<()> {
+        let mut command = Command::new("terminal-notifier");
+        command.args([
+            "-title",
+            TITLE,
+            "-group",
+            "godspeed-cli",
+            "-message",
+            message,
+        ]);
+        if let Some(link) = link {
+            command.args(["-open", link]);
+        }
+        run(command)
+    }
+}
+
+/// libnotify's `notify-send`
+struct NotifySend;
+
+impl Notifier for NotifySend {
+    fn show(&self, message: &str, _link: Option<&str>) -> io::Result<()> {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=godspeed-cli", "--", TITLE, message]);
+        run(command)
+    }
+}
+
+/// The freedesktop notification service over D-Bus, through GLib's `gdbus`,
+/// for desktops without `notify-send`
+struct Gdbus;
+
+impl Notifier for Gdbus {
+    fn show(&self, message: &str, _link: Option<&str>) -> io::Result<()> {
+        let mut command = Command::new("gdbus");
+        command
+            .args([
+                "call",
+                "--session",
+                "--dest=org.freedesktop.Notifications",
+                "--object-path=/org/freedesktop/Notifications",
+                "--method=org.freedesktop.Notifications.Notify",
+                "godspeed-cli",
+                "0",
+                "",
+                TITLE,
+            ])
+            .arg(gvariant_string(message))
+            .args(["[]", "{}", "-1"]);
+        run(command)
+    }
+}
+
+/// A string in GVariant text format, which `gdbus` parses its arguments as
+fn gvariant_string(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// A toast through PowerShell's Windows Runtime access, shown under
+/// PowerShell's app id since an unpackaged program can't have its own. The
+/// message is passed in the environment so it needs no quoting.
+const WINDOWS_TOAST: &str = r#"
+[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null
+$template = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02)
+$text = $template.GetElementsByTagName('text')
+$text.Item(0).AppendChild($template.CreateTextNode('Godspeed CLI')) > $null
+$text.Item(1).AppendChild($template.CreateTextNode($env:GODSPEED_NOTIFICATION)) > $null
+$toast = [Windows.UI.Notifications.ToastNotification]::new($template)
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe').Show($toast)
+"#;
+
+struct Toast;
+
+impl Notifier for Toast {
+    fn show(&self, message: &str, _link: Option<&str>) -> io::Result<()> {
+        let mut command = Command::new("powershell");
+        command
+            .args(["-NoProfile", "-NonInteractive", "-Command", WINDOWS_TOAST])
+            .env("GODSPEED_NOTIFICATION", message);
+        run(command)
+    }
+}
+
+struct Stderr;
+
+impl Notifier for Stderr {
+    fn show(&self, message: &str, _link: Option<&str>) -> io::Result<()> {
+        eprintln!("{}: {}", TITLE, message);
+        Ok(())
+    }
+}
+
+struct Silent;
+
+impl Notifier for Silent {
+    fn show(&self, _message: &str, _link: Option<&str>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The config, with `enabled` decided by the flag and the environment too
+static SETTINGS: OnceLock<NotificationConfig> = OnceLock::new();
+
+/// Apply the config, with `enabled` already decided from `--no-notify`,
+/// `GODSPEED_NOTIFY` and the config. Call it once, before anything is sent.
+pub fn configure(config: &NotificationConfig, enabled: bool) {
+    let _ = SETTINGS.set(NotificationConfig {
+        enabled,
+        ..config.clone()
+    });
+}
+
+fn settings() -> &'static NotificationConfig {
+    SETTINGS.get_or_init(NotificationConfig::default)
+}
+
+fn send(wanted: bool, message: &str, link: Option<&str>) {
+    let settings = settings();
+    if !settings.enabled || !wanted {
+        return;
+    }
+    if let Err(e) = settings.backend.notifier().show(message, link) {
+        // Only worth saying when the backend was chosen, not guessed
+        if settings.backend != Backend::Auto {
+            eprintln!(
+                "Warning: failed to show a notification with {}: {}",
+                settings.backend.name(),
+                e
+            );
+        }
+    }
+}
+
+/// Something went wrong that the user should know about
+pub fn failure(message: &str) {
+    send(settings().on_failure, message, None);
+}
+
+/// A task was added; clicking the notification opens `link`, where that's
+/// supported
+pub fn success(message: &str, link: Option<&str>) {
+    send(settings().on_success, message, link);
+}
+
+/// Cached tasks were sent
+pub fn flushed(message: &str) {
+    send(settings().on_flush, message, None);
+}
@@ -0,0 +1,132 @@
+use std::net::IpAddr;
+
+/// Whether any network interface other than loopback is up with a routable
+/// address. It's a cheap local check, so it catches Wi-Fi being off or
+/// airplane mode without waiting for a request to time out; a network that's
+/// connected but has no internet still counts as online.
+#[cfg(unix)]
+pub fn has_network() -> bool {
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        // Can't tell, so let the request find out
+        return true;
+    }
+
+    let mut found = false;
+    let mut current = addrs;
+    while !current.is_null() {
+        let ifa = unsafe { &*current };
+        current = ifa.ifa_next;
+
+        let flags = ifa.ifa_flags;
+        let up = (flags & libc::IFF_UP as libc::c_uint) != 0
+            && (flags & libc::IFF_RUNNING as libc::c_uint) != 0;
+        if !up || (flags & libc::IFF_LOOPBACK as libc::c_uint) != 0 || ifa.ifa_addr.is_null() {
+            continue;
+        }
+        if unsafe { is_routable(ifa.ifa_addr) } {
+            found = true;
+            break;
+        }
+    }
+    unsafe { libc::freeifaddrs(addrs) };
+    found
+}
+
+#[cfg(not(unix))]
+pub fn has_network() -> bool {
+    true
+}
+
+/// Link-local addresses show up on interfaces that never got a real address,
+/// so they don't count
+#[cfg(unix)]
+unsafe fn is_routable(addr: *const libc::sockaddr) -> bool {
+    match (*addr).sa_family as libc::c_int {
+        libc::AF_INET => {
+            let addr = &*(addr as *const libc::sockaddr_in);
+            let octets = u32::from_be(addr.sin_addr.s_addr).to_be_bytes();
+            octets[..2] != [169, 254]
+        }
+        libc::AF_INET6 => {
+            let addr = &*(addr as *const libc::sockaddr_in6);
+            let octets = addr.sin6_addr.s6_addr;
+            !(octets[0] == 0xfe && (octets[1] & 0xc0) == 0x80)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `url` points at this machine or the local network, such as a
+/// self-hosted or mock API. Those don't need the internet, so they're tried
+/// even when no interface looks online.
+pub fn is_local(url: &str) -> bool {
+    let Some(host) = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+    else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+        Ok(IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || ip
+                    .to_ipv4_mapped()
+                    .is_some_and(|ip| ip.is_loopback() || ip.is_private())
+        }
+        Err(_) => {
+            host == "localhost"
+                || host.ends_with(".localhost")
+                || host.ends_with(".local")
+                || !host.contains('.')
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loopback_and_private_hosts_are_local() {
+        for url in [
+            "http://localhost:8080",
+            "http://api.localhost",
+            "http://127.0.0.1:9",
+            "http://127.8.0.1",
+            "http://[::1]:3000/v1",
+            "http://10.0.0.5",
+            "http://172.16.0.1",
+            "http://172.31.255.254",
+            "http://192.168.1.20",
+            "http://169.254.1.1",
+            "http://[fd12:3456::1]",
+            "http://[fe80::1]",
+            "http://[::ffff:192.168.0.1]",
+            "http://nas.local",
+            "http://godspeed:8000",
+        ] {
+            assert!(is_local(url), "{}", url);
+        }
+    }
+
+    #[test]
+    fn public_hosts_are_not_local() {
+        for url in [
+            "https://api.godspeedapp.com",
+            "http://8.8.8.8",
+            "http://172.32.0.1",
+            "http://192.169.0.1",
+            "http://[2606:4700::1111]",
+            "http://localhost.example.com",
+            "not a url",
+        ] {
+            assert!(!is_local(url), "{}", url);
+        }
+    }
+}
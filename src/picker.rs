@@ -0,0 +1,76 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use crate::term;
+
+/// Let the user choose from `names`, through fzf when it's installed and a
+/// numbered menu on the terminal otherwise. Returns None when there's no
+/// terminal or the picker was dismissed; an empty choice is `Some(vec![])`.
+pub fn pick(kind: &str, names: &[String], multi: bool) -> Option<Vec<String>> {
+    if names.is_empty() {
+        return None;
+    }
+    match pick_fzf(kind, names, multi) {
+        Some(result) => result,
+        None => pick_menu(kind, names, multi),
+    }
+}
+
+/// None when fzf couldn't be started, so the menu can be used instead
+fn pick_fzf(kind: &str, names: &[String], multi: bool) -> Option<Option<Vec<String>>> {
+    let prompt = format!("{}> ", kind);
+    let mut command = Command::new("fzf");
+    command
+        .args(["--height", "40%", "--reverse", "--prompt", &prompt])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    if multi {
+        command.arg("--multi");
+    }
+    let mut child = command.spawn().ok()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(names.join("\n").as_bytes());
+    }
+    let output = child.wait_with_output().ok()?;
+
+    // fzf exits with 1 when nothing matched and 130 when dismissed
+    Some(match output.status.code() {
+        Some(0) => Some(
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect(),
+        ),
+        Some(1) => Some(Vec::new()),
+        _ => None,
+    })
+}
+
+fn pick_menu(kind: &str, names: &[String], multi: bool) -> Option<Vec<String>> {
+    let (tty, mut writer) = term::open_tty()?;
+    writeln!(writer, "Pick a {}:", kind).ok()?;
+    for (i, name) in names.iter().enumerate() {
+        writeln!(writer, "  [{}] {}", i + 1, name).ok()?;
+    }
+    if multi {
+        write!(writer, "Choices, separated by spaces (blank for none): ").ok()?;
+    } else {
+        write!(writer, "Choice (blank for none): ").ok()?;
+    }
+    writer.flush().ok()?;
+
+    let mut answer = String::new();
+    BufReader::new(tty).read_line(&mut answer).ok()?;
+    let choices: Vec<&str> = answer.split([' ', ',']).filter(|s| !s.is_empty()).collect();
+    if !multi && choices.len() > 1 {
+        return None;
+    }
+    choices
+        .into_iter()
+        .map(|choice| {
+            let index = choice.trim().parse::<usize>().ok()?.checked_sub(1)?;
+            names.get(index).cloned()
+        })
+        .collect()
+}
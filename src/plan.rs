@@ -0,0 +1,108 @@
+use serde::Serialize;
+
+use crate::term::{self, Style};
+use crate::TaskRequest;
+
+/// A change a command would make, shown instead of sending it with `--dry-run`
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Change {
+    Create {
+        task: Box<TaskRequest>,
+        /// Title of the parent task, for subtasks
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parent: Option<String>,
+    },
+    /// A label or list that doesn't exist yet, created before the tasks that use it
+    CreateLabel {
+        name: String,
+    },
+    CreateList {
+        name: String,
+    },
+    /// A queued capture dropped without being sent
+    RemoveQueued {
+        input: String,
+    },
+}
+
+fn describe(task: &TaskRequest) -> String {
+    let mut fields = Vec::new();
+    if let Ok(serde_json::Value::Object(map)) = serde_json::to_value(task) {
+        for (key, value) in map {
+            if key != "title" {
+                fields.push(format!("{}={}", key, value));
+            }
+        }
+    }
+    fields.join(" ")
+}
+
+/// Print planned changes as a diff-style preview, or as a JSON array
+pub fn render(changes: &[Change]) {
+    if crate::term::json() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(changes).unwrap_or_default()
+        );
+        return;
+    }
+
+    for change in changes {
+        println!("{}", line(change));
+    }
+}
+
+fn line(change: &Change) -> String {
+    let marker = term::paint(Style::Success, "+");
+    match change {
+        Change::Create { task, parent } => {
+            let mut line = format!("{} create {:?}", marker, task.title);
+            if let Some(parent) = parent {
+                line.push_str(&format!(" (subtask of {:?})", parent));
+            }
+            let fields = describe(task);
+            if !fields.is_empty() {
+                line.push(' ');
+                line.push_str(&fields);
+            }
+            line
+        }
+        Change::CreateLabel { name } => format!("{} create label {:?}", marker, name),
+        Change::CreateList { name } => format!("{} create list {:?}", marker, name),
+        Change::RemoveQueued { input } => format!(
+            "{} remove queued {:?}",
+            term::paint(Style::Error, "-"),
+            input.lines().next().unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_line_lists_fields() {
+        let change = Change::Create {
+            task: Box::new(TaskRequest {
+                title: "Book flights".into(),
+                list_id: Some("list-2".into()),
+                ..Default::default()
+            }),
+            parent: Some("Plan trip".into()),
+        };
+        assert_eq!(
+            line(&change),
+            "+ create \"Book flights\" (subtask of \"Plan trip\") list_id=\"list-2\""
+        );
+    }
+
+    #[test]
+    fn removal_line_shows_the_first_line() {
+        let change = Change::RemoveQueued {
+            input: "Buy milk\nfrom the corner shop".into(),
+        };
+        assert_eq!(line(&change), "- remove queued \"Buy milk\"");
+    }
+}
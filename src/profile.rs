@@ -0,0 +1,239 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::{get_data_home, write_atomic};
+
+/// The profile used when none is selected; it keeps the original data layout
+pub const DEFAULT: &str = "default";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// API key for this profile
+    pub api_key: Option<String>,
+    /// Environment variable holding the API key, instead of storing it in the config
+    pub api_key_env: Option<String>,
+    /// Command that prints the API key, such as `op read op://Personal/godspeed/token`
+    pub api_key_cmd: Option<String>,
+    /// API base URL for this profile, over `[api] base_url`
+    pub base_url: Option<String>,
+}
+
+impl ProfileConfig {
+    /// The key from `api_key`, then `api_key_env`, then `api_key_cmd`. Only a
+    /// command that fails is an error; no key at all is None.
+    pub fn api_key(&self) -> Result<Option<String>, String> {
+        let key = self
+            .api_key
+            .clone()
+            .or_else(|| self.api_key_env.as_ref().and_then(|var| env::var(var).ok()));
+        match (key, &self.api_key_cmd) {
+            (Some(key), _) => Ok(Some(key)),
+            (None, Some(command)) => run_key_command(command).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Run `api_key_cmd` through the shell and read the key from the first line
+/// it prints. Its stderr is shown, so a secret manager can say why it failed.
+fn run_key_command(command: &str) -> Result<String, String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    // Not given stdin, which may hold the tasks being captured
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("Can't run api_key_cmd {}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "api_key_cmd {} failed ({})",
+            command, output.status
+        ));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let key = stdout.lines().next().unwrap_or_default().trim();
+    if key.is_empty() {
+        return Err(format!("api_key_cmd {} printed no key", command));
+    }
+    Ok(key.to_string())
+}
+
+/// Data shared by all profiles, including the default profile's caches
+pub fn get_base_dir() -> PathBuf {
+    get_data_home().join("godspeed-cli")
+}
+
+fn get_state_path() -> PathBuf {
+    get_base_dir().join("profile")
+}
+
+/// Names become directories under the data dir, so they can't hold a path
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty()
+        || name == "."
+        || name.contains("..")
+        || name.contains(['/', '\\'])
+        || name.chars().any(char::is_control)
+    {
+        return Err(format!(
+            "Invalid profile name: {:?} (use letters, digits, - or _)",
+            name
+        ));
+    }
+    Ok(())
+}
+
+static SELECTED: OnceLock<String> = OnceLock::new();
+
+/// Use `name` for this run (`--profile`), over `GODSPEED_PROFILE`. Call it
+/// before anything asks for the active profile.
+pub fn select(name: &str) {
+    let _ = SELECTED.set(name.trim().to_string());
+}
+
+/// The profile picked for this run with `--profile`, or for this terminal
+/// with `GODSPEED_PROFILE`, if either was
+pub fn selected() -> Option<String> {
+    SELECTED
+        .get()
+        .cloned()
+        .or_else(|| env::var("GODSPEED_PROFILE").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The active profile: `--profile` for this run, `GODSPEED_PROFILE` for
+/// this terminal, then the one chosen with `profile use`, then the default
+pub fn active() -> &'static str {
+    static ACTIVE: OnceLock<String> = OnceLock::new();
+    ACTIVE.get_or_init(|| {
+        selected()
+            .or_else(|| fs::read_to_string(get_state_path()).ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| DEFAULT.to_string())
+    })
+}
+
+pub fn set_default(name: &str) -> io::Result<()> {
+    check_name(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    fs::create_dir_all(get_base_dir())?;
+    if name == DEFAULT {
+        match fs::remove_file(get_state_path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    } else {
+        write_atomic(&get_state_path(), format!("{}\n", name))
+    }
+}
+
+/// Shell code that scopes `profile use` to the current terminal and shows the
+/// active profile in the prompt
+pub fn shell_init(shell: &str) -> Option<String> {
+    let state_path = get_state_path()
+        .display()
+        .to_string()
+        .replace('\'', "'\\''");
+
+    let posix_wrapper = format!(
+        r#"godspeed-cli() {{
+  if [ "$1" = "profile" ] && [ "$2" = "use" ] && [ -n "$3" ]; then
+    command godspeed-cli profile check "$3" || return $?
+    export GODSPEED_PROFILE="$3"
+    echo "Using profile $3 in this terminal"
+  else
+    command godspeed-cli "$@"
+  fi
+}}
+__godspeed_prompt() {{
+  local profile="${{GODSPEED_PROFILE:-$(cat '{}' 2>/dev/null)}}"
+  if [ -n "$profile" ] && [ "$profile" != "{}" ]; then
+    printf '[gs:%s] ' "$profile"
+  fi
+}}
+"#,
+        state_path, DEFAULT
+    );
+
+    match shell {
+        "bash" => Some(format!(
+            "{}case \"$PS1\" in *__godspeed_prompt*) ;; *) PS1='$(__godspeed_prompt)'\"$PS1\" ;; esac\n",
+            posix_wrapper
+        )),
+        "zsh" => Some(format!(
+            "{}setopt PROMPT_SUBST\ncase \"$PROMPT\" in *__godspeed_prompt*) ;; *) PROMPT='$(__godspeed_prompt)'\"$PROMPT\" ;; esac\n",
+            posix_wrapper
+        )),
+        "fish" => Some(format!(
+            r#"function godspeed-cli
+  if test (count $argv) -ge 3; and test "$argv[1]" = profile; and test "$argv[2]" = use
+    command godspeed-cli profile check $argv[3]; or return $status
+    set -gx GODSPEED_PROFILE $argv[3]
+    echo "Using profile $argv[3] in this terminal"
+  else
+    command godspeed-cli $argv
+  end
+end
+if not functions -q __godspeed_original_prompt
+  functions -c fish_prompt __godspeed_original_prompt
+  function fish_prompt
+    set -l profile $GODSPEED_PROFILE
+    if test -z "$profile"; and test -f '{}'
+      set profile (cat '{}')
+    end
+    if test -n "$profile"; and test "$profile" != {}
+      printf '[gs:%s] ' $profile
+    end
+    __godspeed_original_prompt
+  end
+end
+"#,
+            state_path, state_path, DEFAULT
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_that_are_paths_are_refused() {
+        for name in [
+            "",
+            ".",
+            "..",
+            "../../escaped",
+            "a/b",
+            "a\\b",
+            "work..old",
+            "a\nb",
+        ] {
+            assert!(check_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn ordinary_names_are_accepted() {
+        for name in [DEFAULT, "work", "side-project_2", "v1.2", "café"] {
+            assert!(check_name(name).is_ok(), "{:?}", name);
+        }
+    }
+}
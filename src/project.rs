@@ -0,0 +1,32 @@
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+pub const FILE_NAME: &str = ".godspeed";
+
+/// Per-directory settings from the nearest `.godspeed` file
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// List used when a task has no `@list`
+    pub list: Option<String>,
+}
+
+/// Find the `.godspeed` file in the current directory or its nearest parent
+pub fn find() -> Option<(PathBuf, ProjectConfig)> {
+    let cwd = env::current_dir().ok()?;
+    let path = cwd
+        .ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())?;
+
+    let content = fs::read_to_string(&path).ok()?;
+    match toml::from_str(&content) {
+        Ok(config) => Some((path, config)),
+        Err(e) => {
+            eprintln!("Warning: ignoring invalid {}: {}", path.display(), e);
+            None
+        }
+    }
+}
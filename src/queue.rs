@@ -0,0 +1,725 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dates;
+use crate::lock::{self, FileLock};
+use crate::{get_data_dir, write_atomic};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Add the original capture time to the notes of tasks sent late
+    pub capture_note: bool,
+    /// Failed sends before a task is moved to the dead-letter file
+    pub max_attempts: u32,
+    /// Wait after the first failure; it doubles with each one after that
+    pub backoff_secs: i64,
+    /// Send the queue from a separate process after a capture, so the
+    /// capture returns as soon as its own task is sent
+    pub background_flush: bool,
+    /// Queue a capture even when the same input is already waiting
+    pub allow_duplicates: bool,
+    /// Queue captures straight away when no network interface is up
+    pub detect_offline: bool,
+    /// Most tasks kept waiting; the oldest are pruned past this
+    pub max_entries: usize,
+    /// Tasks waiting longer than this are pruned; 0 keeps them forever
+    pub max_age_days: i64,
+    pub prune: Prune,
+    /// Warn each time the queue grows by this many tasks; 0 never warns
+    pub warn_every: usize,
+}
+
+/// What happens to tasks pruned from the queue
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Prune {
+    /// Move them to the dead-letter file, where they can still be recovered
+    #[default]
+    DeadLetter,
+    Delete,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig {
+            capture_note: true,
+            max_attempts: 10,
+            backoff_secs: 60,
+            background_flush: true,
+            allow_duplicates: false,
+            detect_offline: true,
+            max_entries: 1000,
+            max_age_days: 30,
+            prune: Prune::DeadLetter,
+            warn_every: 50,
+        }
+    }
+}
+
+/// The longest wait between automatic retries
+const MAX_BACKOFF_SECS: i64 = 24 * 60 * 60;
+
+/// Tasks sent sooner than this after capture don't get a capture note
+const NOTE_AFTER_SECS: i64 = 10 * 60;
+
+/// A capture waiting to be sent, one JSON object per line of the queue file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The task text (or JSON task) as it will be sent
+    pub input: String,
+    pub captured_at: String,
+    /// Failed sends so far
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// No automatic retry before this time, so a failing task backs off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<String>,
+    /// Sent with every attempt, so one whose answer was lost doesn't create
+    /// the task a second time when it's retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// For a subtask whose parent was created when it wasn't, the parent
+    /// to create it under when it's retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Parent>,
+}
+
+/// A task that already exists, which a queued subtask belongs to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Parent {
+    pub id: String,
+    /// The parent's list, which the subtask goes to unless it names its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<String>,
+}
+
+/// How sending a queued task went
+pub enum Outcome {
+    Sent,
+    Failed(String),
+    /// Retrying can't help, such as for a list that doesn't exist
+    Permanent(String),
+}
+
+impl Entry {
+    pub fn new(input: &str) -> Self {
+        Entry {
+            input: input.to_string(),
+            captured_at: dates::format_utc(dates::now_unix()),
+            attempts: 0,
+            last_error: None,
+            retry_after: None,
+            idempotency_key: Some(new_idempotency_key(input)),
+            parent: None,
+        }
+    }
+
+    /// A capture whose send failed, keeping the idempotency key it was sent
+    /// with in case the API did create it
+    pub fn attempted(input: &str, key: &str) -> Self {
+        Entry {
+            idempotency_key: Some(key.to_string()),
+            ..Entry::new(input)
+        }
+    }
+
+    /// The key sent with this capture. Entries queued before keys were kept
+    /// get one from their input and capture time, the same on every retry.
+    pub fn idempotency_key(&self) -> String {
+        self.idempotency_key.clone().unwrap_or_else(|| {
+            crate::sha256::hex(format!("{}\n{}", self.input, self.captured_at).as_bytes())
+        })
+    }
+
+    /// Whether the backoff after the last failure has passed
+    pub fn is_due(&self, now: i64) -> bool {
+        self.retry_after
+            .as_deref()
+            .and_then(dates::parse_utc)
+            .is_none_or(|after| now >= after)
+    }
+
+    /// A line for the notes saying when this was captured, if that was long
+    /// enough ago for it to differ noticeably from the time it's sent
+    pub fn capture_note(&self) -> Option<String> {
+        let captured = dates::parse_utc(&self.captured_at)?;
+        (dates::now_unix() - captured >= NOTE_AFTER_SECS)
+            .then(|| format!("Captured {}", dates::format_local(captured)))
+    }
+
+    /// Whether `other` is this same capture, possibly after more attempts or
+    /// an edit. The key tells captures apart even within the same second.
+    pub fn same_capture(&self, other: &Entry) -> bool {
+        self.idempotency_key() == other.idempotency_key()
+    }
+}
+
+/// A key for a new capture of `input`: its hash with the time, so the same
+/// text captured twice is still two tasks
+pub fn new_idempotency_key(input: &str) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    crate::sha256::hex(format!("{}\n{}", input, now).as_bytes())
+}
+
+pub fn get_queue_path() -> PathBuf {
+    get_data_dir().join("queue.jsonl")
+}
+
+/// Tasks that are no longer retried, kept with their last error
+pub fn get_dead_letter_path() -> PathBuf {
+    get_data_dir().join("dead-letter.jsonl")
+}
+
+/// The `---`-separated file used before the queue was JSON lines
+fn get_legacy_path() -> PathBuf {
+    get_data_dir().join("cache")
+}
+
+/// Held while the queue or dead-letter file is read or rewritten
+pub fn lock() -> io::Result<FileLock> {
+    lock::exclusive(&get_data_dir().join("queue.lock"))
+}
+
+/// Held while queued tasks are being sent, so two runs never send the same
+/// one. None if another run is sending and `wait` is false.
+pub fn flush_lock(wait: bool) -> io::Result<Option<FileLock>> {
+    let path = get_data_dir().join("flush.lock");
+    if wait {
+        lock::exclusive(&path).map(Some)
+    } else {
+        lock::try_exclusive(&path)
+    }
+}
+
+/// When the API last asked us to slow down, the time to wait until
+fn get_rate_limit_path() -> PathBuf {
+    get_data_dir().join("rate-limited-until")
+}
+
+/// The time the API's `Retry-After` asked for, if it hasn't passed yet
+pub fn rate_limited_until() -> Option<i64> {
+    let until = fs::read_to_string(get_rate_limit_path()).ok()?;
+    dates::parse_utc(until.trim()).filter(|until| *until > dates::now_unix())
+}
+
+/// Remember that the API asked for no more requests before `until`
+pub fn set_rate_limited_until(until: i64) -> io::Result<()> {
+    write_atomic(&get_rate_limit_path(), dates::format_utc(until) + "\n")
+}
+
+/// Cheap check for the common case of nothing queued
+pub fn is_empty() -> bool {
+    let has_data = |path: PathBuf| fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false);
+    !has_data(get_queue_path()) && !has_data(get_legacy_path())
+}
+
+/// Every queued capture, oldest first
+pub fn load() -> Vec<Entry> {
+    let Ok(_lock) = lock() else {
+        return Vec::new();
+    };
+    migrate_legacy();
+    read(&get_queue_path())
+}
+
+fn read(path: &Path) -> Vec<Entry> {
+    parse(&fs::read_to_string(path).unwrap_or_default())
+}
+
+fn parse(content: &str) -> Vec<Entry> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        // A line that isn't an entry is kept as task text rather than lost,
+        // with a key from the line so it's the same capture on every read
+        .map(|line| {
+            serde_json::from_str(line).unwrap_or_else(|_| Entry {
+                idempotency_key: Some(crate::sha256::hex(line.as_bytes())),
+                ..Entry::new(line)
+            })
+        })
+        .collect()
+}
+
+/// Queue a capture for a later retry. Input that's already waiting isn't
+/// queued again unless duplicates are allowed, so a hotkey that fires twice
+/// or a capture retried by hand doesn't turn into several tasks later.
+/// Returns false if it was already queued.
+pub fn push(input: &str, config: &QueueConfig) -> io::Result<bool> {
+    push_entry(Entry::new(input), config)
+}
+
+/// Queue an entry made by the caller, such as a subtask with its parent
+pub fn push_entry(entry: Entry, config: &QueueConfig) -> io::Result<bool> {
+    let _lock = lock()?;
+    migrate_legacy();
+    let queued = read(&get_queue_path());
+    if !config.allow_duplicates && queued.iter().any(|queued| queued.input == entry.input) {
+        tracing::info!(input = first_line(&entry.input), "already queued");
+        return Ok(false);
+    }
+    append(&entry)?;
+    tracing::info!(input = first_line(&entry.input), "queued");
+
+    let mut entries = queued;
+    entries.push(entry);
+    let pruned = prune_entries(&mut entries, config)?;
+    if config.warn_every > 0 && pruned == 0 && entries.len().is_multiple_of(config.warn_every) {
+        let message = format!(
+            "{} tasks are waiting in the offline queue; check that the API key works",
+            entries.len()
+        );
+        eprintln!("Warning: {}", message);
+        crate::notify::failure(&message);
+    }
+    Ok(true)
+}
+
+/// Apply the size and age limits to the queue, returning how many tasks were
+/// pruned
+pub fn prune(config: &QueueConfig) -> io::Result<usize> {
+    let _lock = lock()?;
+    migrate_legacy();
+    let mut entries = read(&get_queue_path());
+    prune_entries(&mut entries, config)
+}
+
+/// The limits for `entries`, which must be the whole queue; saves it if
+/// anything was pruned
+fn prune_entries(entries: &mut Vec<Entry>, config: &QueueConfig) -> io::Result<usize> {
+    let mut pruned = over_limits(entries, config, dates::now_unix());
+    if pruned.is_empty() {
+        return Ok(0);
+    }
+
+    if config.prune == Prune::DeadLetter {
+        for entry in &mut pruned {
+            entry.retry_after = None;
+            entry.last_error = Some(format!(
+                "pruned from the queue; {}",
+                entry.last_error.as_deref().unwrap_or("never tried")
+            ));
+            append_to(&get_dead_letter_path(), entry)?;
+        }
+    }
+    save(entries)?;
+    tracing::warn!(pruned = pruned.len(), "pruned the queue");
+    eprintln!(
+        "Warning: pruned {} old tasks from the offline queue (limits are under [queue] in the config)",
+        pruned.len()
+    );
+    Ok(pruned.len())
+}
+
+/// Take the entries past the age and size limits at time `now` out of
+/// `entries`, returning them
+fn over_limits(entries: &mut Vec<Entry>, config: &QueueConfig, now: i64) -> Vec<Entry> {
+    let cutoff = now - config.max_age_days * 24 * 60 * 60;
+    let expired = |entry: &Entry| {
+        config.max_age_days > 0
+            && dates::parse_utc(&entry.captured_at).is_some_and(|at| at < cutoff)
+    };
+    let (mut pruned, kept): (Vec<Entry>, Vec<Entry>) = entries.drain(..).partition(expired);
+    *entries = kept;
+    if config.max_entries > 0 && entries.len() > config.max_entries {
+        let excess = entries.len() - config.max_entries;
+        pruned.extend(entries.drain(..excess));
+    }
+    pruned
+}
+
+fn append(entry: &Entry) -> io::Result<()> {
+    append_to(&get_queue_path(), entry)
+}
+
+fn append_to(path: &Path, entry: &Entry) -> io::Result<()> {
+    let line = serde_json::to_string(entry).map_err(io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)
+}
+
+/// Keep a capture that can never be sent, such as one the API rejected,
+/// in the dead-letter file with the reason so it can be fixed by hand
+pub fn dead_letter(input: &str, error: &str) -> io::Result<()> {
+    let _lock = lock()?;
+    let mut entry = Entry::new(input);
+    entry.attempts = 1;
+    entry.last_error = Some(error.to_string());
+    tracing::error!(input = first_line(input), error, "dead-lettered");
+    append_to(&get_dead_letter_path(), &entry)
+}
+
+/// How many tasks are in the dead-letter file
+pub fn dead_letter_count() -> usize {
+    fs::read_to_string(get_dead_letter_path())
+        .map(|content| content.lines().filter(|l| !l.trim().is_empty()).count())
+        .unwrap_or(0)
+}
+
+/// Change the queue in one step, so captures queued by another run at the
+/// same time are neither lost nor overwritten
+pub fn modify<T>(change: impl FnOnce(&mut Vec<Entry>) -> T) -> io::Result<T> {
+    let _lock = lock()?;
+    migrate_legacy();
+    let mut entries = read(&get_queue_path());
+    let result = change(&mut entries);
+    save(&entries)?;
+    tracing::info!(entries = entries.len(), "queue rewritten");
+    Ok(result)
+}
+
+/// Rewrite the queue from `content` as edited by hand, keeping any entry
+/// queued since `before` was read
+pub fn replace_edited(before: &[Entry], content: &str) -> io::Result<()> {
+    let edited = parse(content);
+    modify(|entries| *entries = merge_edited(before, edited, std::mem::take(entries)))
+}
+
+/// The edited entries, then those in `current` that weren't in `before`
+fn merge_edited(before: &[Entry], mut edited: Vec<Entry>, current: Vec<Entry>) -> Vec<Entry> {
+    // A task edited by hand is a different request, so it needs its own key
+    for entry in &mut edited {
+        let unchanged = before
+            .iter()
+            .any(|old| old.input == entry.input && old.idempotency_key == entry.idempotency_key);
+        if !unchanged {
+            entry.idempotency_key = Some(new_idempotency_key(&entry.input));
+        }
+    }
+    let added = current
+        .into_iter()
+        .filter(|entry| !before.iter().any(|old| old.same_capture(entry)));
+    edited.extend(added);
+    edited
+}
+
+fn save(entries: &[Entry]) -> io::Result<()> {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&serde_json::to_string(entry).map_err(io::Error::other)?);
+        content.push('\n');
+    }
+    write_atomic(&get_queue_path(), content)
+}
+
+/// Record the outcome of sending `entry`: drop it once sent, otherwise count
+/// the attempt and back off, moving it to the dead-letter file once retrying
+/// is hopeless. The file is re-read first so captures queued meanwhile stay.
+/// Returns true if the entry was dead-lettered.
+pub fn update(entry: &Entry, outcome: Outcome, config: &QueueConfig) -> io::Result<bool> {
+    let _lock = lock()?;
+    let mut entries = read(&get_queue_path());
+    let Some(pos) = entries.iter().position(|e| e.same_capture(entry)) else {
+        return Ok(false);
+    };
+    let (error, permanent) = match outcome {
+        Outcome::Sent => {
+            entries.remove(pos);
+            save(&entries)?;
+            tracing::info!(input = first_line(&entry.input), "sent from the queue");
+            return Ok(false);
+        }
+        Outcome::Failed(error) => (error, false),
+        Outcome::Permanent(error) => (error, true),
+    };
+
+    let failed = &mut entries[pos];
+    let dead = record_failure(failed, error, permanent, config, dates::now_unix());
+    tracing::warn!(
+        input = first_line(&failed.input),
+        attempts = failed.attempts,
+        error = failed.last_error.as_deref(),
+        dead,
+        "queued task failed"
+    );
+    if dead {
+        append_to(&get_dead_letter_path(), failed)?;
+        entries.remove(pos);
+    }
+    save(&entries)?;
+    Ok(dead)
+}
+
+/// Count a failed attempt at `entry` at time `now`, setting when it may be
+/// retried. Returns true if it shouldn't be, and belongs in the dead-letter
+/// file.
+fn record_failure(
+    entry: &mut Entry,
+    error: String,
+    permanent: bool,
+    config: &QueueConfig,
+    now: i64,
+) -> bool {
+    entry.attempts += 1;
+    entry.last_error = Some(error);
+    let dead = permanent || entry.attempts >= config.max_attempts;
+    entry.retry_after = (!dead).then(|| dates::format_utc(now + backoff(config, entry.attempts)));
+    dead
+}
+
+/// The wait after the `attempts`th failure in a row
+fn backoff(config: &QueueConfig, attempts: u32) -> i64 {
+    config
+        .backoff_secs
+        .max(1)
+        .saturating_mul(1 << attempts.saturating_sub(1).min(20))
+        .min(MAX_BACKOFF_SECS)
+}
+
+/// What the log records of a capture
+fn first_line(input: &str) -> &str {
+    input.lines().next().unwrap_or_default()
+}
+
+/// Move entries from the old `---`-separated cache file into the queue
+fn migrate_legacy() {
+    let legacy = get_legacy_path();
+    let Ok(content) = fs::read_to_string(&legacy) else {
+        return;
+    };
+    let moved = content
+        .split("---\n")
+        .map(str::trim)
+        .filter(|task| !task.is_empty())
+        .try_for_each(|task| append(&Entry::new(task)));
+    match moved.and_then(|()| fs::remove_file(&legacy)) {
+        Ok(()) => {}
+        Err(e) => eprintln!("Warning: failed to migrate the offline cache: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_in_the_same_second_are_different() {
+        let first = Entry::new("Buy milk");
+        let second = Entry {
+            captured_at: first.captured_at.clone(),
+            ..Entry::new("Buy milk")
+        };
+        assert!(!first.same_capture(&second));
+    }
+
+    #[test]
+    fn capture_is_the_same_after_attempts() {
+        let entry = Entry::attempted("Buy milk", "key-1");
+        let retried = Entry {
+            attempts: 2,
+            last_error: Some("timed out".into()),
+            ..entry.clone()
+        };
+        assert!(entry.same_capture(&retried));
+    }
+
+    #[test]
+    fn line_that_is_not_an_entry_is_the_same_on_every_read() {
+        let first = parse("Buy milk\n");
+        let second = parse("Buy milk\n");
+        assert_eq!(first[0].input, "Buy milk");
+        assert!(first[0].same_capture(&second[0]));
+    }
+
+    const NOW: i64 = 1_717_407_000;
+
+    fn captured(input: &str, days_ago: i64) -> Entry {
+        Entry {
+            captured_at: dates::format_utc(NOW - days_ago * 24 * 60 * 60),
+            ..Entry::new(input)
+        }
+    }
+
+    fn inputs(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.input.as_str()).collect()
+    }
+
+    #[test]
+    fn backoff_doubles_with_each_failure() {
+        let config = QueueConfig::default();
+        assert_eq!(backoff(&config, 1), 60);
+        assert_eq!(backoff(&config, 2), 120);
+        assert_eq!(backoff(&config, 3), 240);
+        assert_eq!(backoff(&config, 8), 60 * 128);
+    }
+
+    #[test]
+    fn backoff_is_capped_at_a_day() {
+        let config = QueueConfig::default();
+        assert_eq!(backoff(&config, 12), MAX_BACKOFF_SECS);
+        assert_eq!(backoff(&config, 100), MAX_BACKOFF_SECS);
+        let config = QueueConfig {
+            backoff_secs: i64::MAX,
+            ..QueueConfig::default()
+        };
+        assert_eq!(backoff(&config, 30), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn backoff_waits_at_least_a_second() {
+        let config = QueueConfig {
+            backoff_secs: 0,
+            ..QueueConfig::default()
+        };
+        assert_eq!(backoff(&config, 1), 1);
+        assert_eq!(backoff(&config, 3), 4);
+    }
+
+    #[test]
+    fn failure_sets_the_next_retry() {
+        let config = QueueConfig::default();
+        let mut entry = Entry::new("Buy milk");
+        assert!(!record_failure(
+            &mut entry,
+            "timed out".into(),
+            false,
+            &config,
+            NOW
+        ));
+        assert!(!record_failure(
+            &mut entry,
+            "timed out".into(),
+            false,
+            &config,
+            NOW
+        ));
+        assert_eq!(entry.attempts, 2);
+        assert_eq!(entry.last_error.as_deref(), Some("timed out"));
+        assert_eq!(entry.retry_after, Some(dates::format_utc(NOW + 120)));
+        assert!(!entry.is_due(NOW + 119));
+        assert!(entry.is_due(NOW + 120));
+    }
+
+    #[test]
+    fn dead_letters_after_max_attempts() {
+        let config = QueueConfig {
+            max_attempts: 3,
+            ..QueueConfig::default()
+        };
+        let mut entry = Entry::new("Buy milk");
+        assert!(!record_failure(
+            &mut entry,
+            "timed out".into(),
+            false,
+            &config,
+            NOW
+        ));
+        assert!(!record_failure(
+            &mut entry,
+            "timed out".into(),
+            false,
+            &config,
+            NOW
+        ));
+        assert!(record_failure(
+            &mut entry,
+            "refused".into(),
+            false,
+            &config,
+            NOW
+        ));
+        assert_eq!(entry.attempts, 3);
+        assert_eq!(entry.last_error.as_deref(), Some("refused"));
+        assert_eq!(entry.retry_after, None);
+    }
+
+    #[test]
+    fn permanent_failure_dead_letters_at_once() {
+        let config = QueueConfig::default();
+        let mut entry = Entry::new("Buy milk @Nowhere");
+        assert!(record_failure(
+            &mut entry,
+            "no such list".into(),
+            true,
+            &config,
+            NOW
+        ));
+        assert_eq!(entry.attempts, 1);
+        assert_eq!(entry.retry_after, None);
+    }
+
+    #[test]
+    fn prunes_by_age() {
+        let config = QueueConfig::default();
+        let mut entries = vec![
+            captured("old", 31),
+            captured("recent", 29),
+            captured("new", 0),
+        ];
+        let pruned = over_limits(&mut entries, &config, NOW);
+        assert_eq!(inputs(&pruned), ["old"]);
+        assert_eq!(inputs(&entries), ["recent", "new"]);
+    }
+
+    #[test]
+    fn max_age_of_zero_keeps_everything() {
+        let config = QueueConfig {
+            max_age_days: 0,
+            ..QueueConfig::default()
+        };
+        let mut entries = vec![captured("ancient", 3650)];
+        assert!(over_limits(&mut entries, &config, NOW).is_empty());
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn prunes_the_oldest_past_max_entries() {
+        let config = QueueConfig {
+            max_entries: 2,
+            ..QueueConfig::default()
+        };
+        let mut entries = vec![captured("a", 3), captured("b", 2), captured("c", 1)];
+        let pruned = over_limits(&mut entries, &config, NOW);
+        assert_eq!(inputs(&pruned), ["a"]);
+        assert_eq!(inputs(&entries), ["b", "c"]);
+    }
+
+    #[test]
+    fn unreadable_capture_time_is_not_pruned_by_age() {
+        let config = QueueConfig::default();
+        let mut entries = vec![Entry {
+            captured_at: "yesterday".into(),
+            ..Entry::new("Buy milk")
+        }];
+        assert!(over_limits(&mut entries, &config, NOW).is_empty());
+    }
+
+    #[test]
+    fn edit_keeps_captures_queued_meanwhile() {
+        let before = vec![Entry::new("a"), Entry::new("b")];
+        let edited = before.clone();
+        let mut current = before.clone();
+        current.push(Entry::new("c"));
+        let merged = merge_edited(&before, edited, current);
+        assert_eq!(inputs(&merged), ["a", "b", "c"]);
+        assert_eq!(merged[0].idempotency_key, before[0].idempotency_key);
+    }
+
+    #[test]
+    fn edit_gives_changed_entries_a_new_key() {
+        let before = vec![Entry::new("a"), Entry::new("b")];
+        let mut edited = before.clone();
+        edited[1].input = "b, fixed".into();
+        let merged = merge_edited(&before, edited, before.clone());
+        assert_eq!(inputs(&merged), ["a", "b, fixed"]);
+        assert_eq!(merged[0].idempotency_key, before[0].idempotency_key);
+        assert_ne!(merged[1].idempotency_key, before[1].idempotency_key);
+    }
+
+    #[test]
+    fn edit_removes_deleted_entries() {
+        let before = vec![Entry::new("a"), Entry::new("b")];
+        let edited = vec![before[0].clone()];
+        let merged = merge_edited(&before, edited, before.clone());
+        assert_eq!(inputs(&merged), ["a"]);
+    }
+}
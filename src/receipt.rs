@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::dates;
+use crate::{expand_home, write_atomic, TaskRequest};
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ReceiptConfig {
+    /// Directory that gets one receipt file per created task
+    pub dir: Option<String>,
+}
+
+/// What a script needs to know about a task the CLI created
+#[derive(Serialize)]
+struct Receipt {
+    id: Option<String>,
+    title: String,
+    list_id: Option<String>,
+    url: Option<String>,
+    parent_id: Option<String>,
+    created_at: String,
+}
+
+pub struct Receipts {
+    /// `--receipt <file>`: rewritten after each create with every receipt from this run
+    file: Option<PathBuf>,
+    /// What the file held before this run, with `--append-receipt`
+    earlier: Vec<Value>,
+    dir: Option<PathBuf>,
+    written: Vec<Receipt>,
+}
+
+impl Receipts {
+    pub fn new(config: ReceiptConfig) -> Self {
+        Receipts {
+            file: None,
+            earlier: Vec::new(),
+            dir: config.dir.as_deref().map(expand_home),
+            written: Vec::new(),
+        }
+    }
+
+    /// Write receipts to `path`, starting with an empty list so a run that
+    /// creates nothing never leaves a previous run's receipts behind
+    pub fn set_file(&mut self, path: PathBuf) {
+        if let Err(e) = write_atomic(&path, "[]\n") {
+            eprintln!("Warning: failed to write receipt: {}", e);
+        }
+        self.file = Some(path);
+    }
+
+    /// Add receipts to the list already in `path`, such as the one the
+    /// capture that started a background flush wrote
+    pub fn append_to_file(&mut self, path: PathBuf) {
+        self.earlier = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+            Err(_) => Vec::new(),
+        };
+        self.file = Some(path);
+    }
+
+    /// Record a created task. Failing to write a receipt is reported but
+    /// doesn't fail the capture, since the task already exists.
+    pub fn record(&mut self, task: &TaskRequest, id: Option<&str>) {
+        if self.file.is_none() && self.dir.is_none() {
+            return;
+        }
+        let now = dates::now_unix();
+        let receipt = Receipt {
+            id: id.map(str::to_string),
+            title: task.title.clone(),
+            list_id: task.list_id.clone(),
+            url: id.map(crate::task_url),
+            parent_id: task.parent_id.clone(),
+            created_at: dates::format_utc(now),
+        };
+        if let Err(e) = self.write(receipt, now) {
+            eprintln!("Warning: failed to write receipt: {}", e);
+        }
+    }
+
+    fn write(&mut self, receipt: Receipt, now: i64) -> io::Result<()> {
+        if let Some(dir) = &self.dir {
+            fs::create_dir_all(dir)?;
+            let name = file_name(&receipt, now, self.written.len() + 1);
+            let json = serde_json::to_string_pretty(&receipt).map_err(io::Error::other)?;
+            write_atomic(&dir.join(name), json + "\n")?;
+        }
+
+        self.written.push(receipt);
+        if let Some(file) = &self.file {
+            write_atomic(file, self.file_contents()?)?;
+        }
+        Ok(())
+    }
+
+    /// The `--receipt` file: what it held before, then this run's receipts
+    fn file_contents(&self) -> io::Result<String> {
+        let mut all = self.earlier.clone();
+        for receipt in &self.written {
+            all.push(serde_json::to_value(receipt).map_err(io::Error::other)?);
+        }
+        let json = serde_json::to_string_pretty(&all).map_err(io::Error::other)?;
+        Ok(json + "\n")
+    }
+}
+
+/// A receipt's name in the receipts directory, numbered by its place in the
+/// run when the API gave no id
+fn file_name(receipt: &Receipt, now: i64, number: usize) -> String {
+    let id = receipt.id.clone().unwrap_or_else(|| number.to_string());
+    format!("{}-{}.json", now, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn receipt(id: Option<&str>) -> Receipt {
+        Receipt {
+            id: id.map(str::to_string),
+            title: "Buy milk".to_string(),
+            list_id: Some("list-1".to_string()),
+            url: id.map(crate::task_url),
+            parent_id: None,
+            created_at: "2026-10-14T12:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn file_lists_earlier_receipts_then_this_runs() {
+        let mut receipts = Receipts::new(ReceiptConfig::default());
+        receipts.earlier = vec![json!({ "id": "old" })];
+        receipts.written.push(receipt(Some("task-1")));
+        let all: Value = serde_json::from_str(&receipts.file_contents().unwrap()).unwrap();
+        assert_eq!(all[0], json!({ "id": "old" }));
+        assert_eq!(all[1]["id"], "task-1");
+        assert_eq!(all[1]["title"], "Buy milk");
+        assert_eq!(all[1]["list_id"], "list-1");
+        assert_eq!(all[1]["parent_id"], Value::Null);
+        assert_eq!(all.as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn empty_run_is_an_empty_list() {
+        let receipts = Receipts::new(ReceiptConfig::default());
+        assert_eq!(receipts.file_contents().unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn names_use_the_id_or_the_place_in_the_run() {
+        assert_eq!(
+            file_name(&receipt(Some("task-1")), 100, 3),
+            "100-task-1.json"
+        );
+        assert_eq!(file_name(&receipt(None), 100, 3), "100-3.json");
+    }
+
+    #[test]
+    fn nothing_is_kept_without_a_file_or_directory() {
+        let mut receipts = Receipts::new(ReceiptConfig::default());
+        receipts.record(&TaskRequest::default(), Some("task-1"));
+        assert!(receipts.written.is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::map_words;
+
+/// Characters the parser already gives a meaning to at the start of a word
+const BUILT_IN: &[char] = &['.', '@', '+', ':', '!', '^', '*', '\\', '-', '"'];
+
+/// Expand words that start with a custom sigil from the `[sigils]` config
+/// table, such as `"~" = ".{}"` turning `~energy:low` into the label
+/// `energy:low`. `{}` stands for the rest of the word.
+pub fn expand(input: &str, sigils: &HashMap<String, String>) -> String {
+    if sigils.is_empty() {
+        return input.to_string();
+    }
+
+    // Longest sigil first, so `$$` wins over `$`
+    let mut ordered: Vec<(&String, &String)> =
+        sigils.iter().filter(|(sigil, _)| valid(sigil)).collect();
+    ordered.sort_by_key(|(sigil, _)| std::cmp::Reverse(sigil.len()));
+
+    map_words(input, |word| {
+        let expansion = ordered.iter().find_map(|(sigil, template)| {
+            let rest = word.strip_prefix(sigil.as_str())?;
+            (!rest.is_empty()).then(|| template.replace("{}", rest))
+        });
+        Ok(expansion)
+    })
+    .unwrap_or_else(|_| input.to_string())
+}
+
+/// A sigil must not start like a built-in token or an ordinary word
+fn valid(sigil: &str) -> bool {
+    sigil
+        .chars()
+        .next()
+        .is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace() && !BUILT_IN.contains(&c))
+}
+
+/// Sigils that will be ignored, for a warning when the config is loaded
+pub fn invalid(sigils: &HashMap<String, String>) -> Vec<&str> {
+    let mut names: Vec<&str> = sigils
+        .keys()
+        .map(String::as_str)
+        .filter(|sigil| !valid(sigil))
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sigils(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(sigil, template)| (sigil.to_string(), template.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn sigil_words_expand_with_the_rest_of_the_word() {
+        let sigils = sigils(&[("~", ".{}"), ("$", "@Finance .{}")]);
+        assert_eq!(
+            expand("Pay rent ~energy:low $bills", &sigils),
+            "Pay rent .energy:low @Finance .bills"
+        );
+    }
+
+    #[test]
+    fn longest_sigil_wins() {
+        let sigils = sigils(&[("$", ".{}"), ("$$", "@{}")]);
+        assert_eq!(expand("$$Work $home", &sigils), "@Work .home");
+    }
+
+    #[test]
+    fn bare_sigils_and_text_after_the_marker_stay() {
+        let sigils = sigils(&[("~", ".{}")]);
+        assert_eq!(expand("About ~ 5", &sigils), "About ~ 5");
+        assert_eq!(expand("Read -- ~later", &sigils), "Read -- ~later");
+    }
+
+    #[test]
+    fn sigils_that_clash_are_ignored() {
+        let sigils = sigils(&[("@", ".{}"), ("a", ".{}"), ("", ".{}"), ("~", ".{}")]);
+        assert!(!valid("@") && !valid("a") && !valid("") && !valid(" x"));
+        assert!(valid("~") && valid("$$"));
+        assert_eq!(invalid(&sigils), ["", "@", "a"]);
+        assert_eq!(expand("@home amy ~x", &sigils), "@home amy .x");
+    }
+}
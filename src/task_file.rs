@@ -0,0 +1,136 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+use crate::{dates, parse_duration, parse_priority};
+
+/// One task in a file for `add -f`, with friendlier fields than the API's
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct FileTask {
+    title: String,
+    #[serde(default)]
+    notes: String,
+    list: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+    assignee: Option<String>,
+    /// Minutes, or text such as `1h30m`
+    duration: Option<toml::Value>,
+    /// A TOML date, or text such as `fri` or `+3d`
+    due: Option<toml::Value>,
+    starts: Option<toml::Value>,
+    /// 1 to 4, or `high`, `medium`, `low` or `none`
+    priority: Option<toml::Value>,
+    /// Such as `weekdays`, `monthly:15` or `every 2w`
+    repeat: Option<String>,
+}
+
+/// Read a task file: the fields of one task at the top level, or several
+/// under `[[tasks]]`. Each task comes back as a JSON task line, with dates
+/// fixed now so a queued retry keeps them.
+pub fn read(path: &Path) -> Result<Vec<String>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    let mut table: toml::Table = content
+        .parse()
+        .map_err(|e| format!("Invalid task file {}: {}", path.display(), e))?;
+
+    let items = match table.remove("tasks") {
+        Some(toml::Value::Array(items)) if table.is_empty() => items,
+        Some(_) => return Err("`tasks` must be the only key, holding [[tasks]] tables".into()),
+        None => vec![toml::Value::Table(table)],
+    };
+
+    let today = dates::today();
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let task: FileTask = item
+                .try_into()
+                .map_err(|e| format!("Invalid task {}: {}", i + 1, e))?;
+            to_json(task, today).map_err(|e| format!("Invalid task {}: {}", i + 1, e))
+        })
+        .collect()
+}
+
+fn to_json(task: FileTask, today: dates::Date) -> Result<String, String> {
+    if task.title.trim().is_empty() {
+        return Err("title is required".into());
+    }
+
+    let date = |value: &toml::Value, field: &str| -> Result<String, String> {
+        let text = match value {
+            toml::Value::String(text) => text.clone(),
+            toml::Value::Datetime(datetime) => datetime.to_string(),
+            _ => return Err(format!("{} must be a date", field)),
+        };
+        dates::parse_date(&text, today)
+            .map(|date| date.to_string())
+            .ok_or_else(|| format!("invalid {} date: {}", field, text))
+    };
+    let duration = match &task.duration {
+        None => None,
+        Some(toml::Value::Integer(minutes)) => Some(*minutes as i32),
+        Some(toml::Value::String(text)) => {
+            Some(parse_duration(text).ok_or_else(|| format!("invalid duration: {}", text))?)
+        }
+        Some(_) => return Err("duration must be minutes or text such as 1h30m".into()),
+    };
+    let priority = match &task.priority {
+        None => None,
+        Some(toml::Value::Integer(level)) => Some(
+            parse_priority(&level.to_string())
+                .ok_or_else(|| format!("invalid priority: {}", level))?,
+        ),
+        Some(toml::Value::String(text)) => {
+            Some(parse_priority(text).ok_or_else(|| format!("invalid priority: {}", text))?)
+        }
+        Some(_) => return Err("priority must be 1 to 4 or a name such as high".into()),
+    };
+    let recurrence_rule = match &task.repeat {
+        None => None,
+        Some(repeat) => Some(
+            match repeat.strip_prefix("every ") {
+                Some(every) => dates::parse_every(every.trim()),
+                None => dates::parse_recurrence(repeat),
+            }
+            .ok_or_else(|| format!("invalid repeat: {}", repeat))?,
+        ),
+    };
+
+    let mut line = json!({
+        "title": task.title.trim(),
+        "notes": task.notes.trim(),
+        "labels": task.labels,
+    });
+    let fields = [
+        ("list", task.list.map(Value::from)),
+        ("assignee", task.assignee.map(Value::from)),
+        ("duration_minutes", duration.map(Value::from)),
+        (
+            "timeless_due_at",
+            task.due
+                .map(|d| date(&d, "due"))
+                .transpose()?
+                .map(Value::from),
+        ),
+        (
+            "timeless_starts_at",
+            task.starts
+                .map(|d| date(&d, "starts"))
+                .transpose()?
+                .map(Value::from),
+        ),
+        ("priority", priority.map(Value::from)),
+        ("recurrence_rule", recurrence_rule.map(Value::from)),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            line[key] = value;
+        }
+    }
+    Ok(line.to_string())
+}
@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use serde_json::Value;
+
+/// A task given as JSON by another program: the API's task fields, plus
+/// `list`, `labels` and `assignee` as names to resolve like `@`, `.` and `+`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TaskInput {
+    pub title: String,
+    pub list: Option<String>,
+    pub list_id: Option<String>,
+    pub labels: Vec<String>,
+    pub label_ids: Vec<String>,
+    pub assignee: Option<String>,
+    pub assignee_id: Option<String>,
+    pub duration_minutes: Option<i32>,
+    pub notes: String,
+    pub timeless_due_at: Option<String>,
+    pub timeless_starts_at: Option<String>,
+    pub priority: Option<i32>,
+    pub recurrence_rule: Option<String>,
+}
+
+/// The task in a queued or captured entry, if it's JSON rather than task text
+pub fn parse(task_str: &str) -> Option<TaskInput> {
+    if !task_str.starts_with('{') {
+        return None;
+    }
+    serde_json::from_str(task_str).ok()
+}
+
+/// Split `--format json` input, a task or an array of tasks, into one compact
+/// line per task, checking each so mistakes are reported before anything is sent
+pub fn read(input: &str) -> Result<Vec<String>, String> {
+    let value: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let items = match value {
+        Value::Array(items) => items,
+        item => vec![item],
+    };
+
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let line = item.to_string();
+            let task: TaskInput = serde_json::from_value(item)
+                .map_err(|e| format!("Invalid task {}: {}", i + 1, e))?;
+            if task.title.trim().is_empty() {
+                return Err(format!("Invalid task {}: title is required", i + 1));
+            }
+            if task.list.is_some() && task.list_id.is_some() {
+                return Err(format!(
+                    "Invalid task {}: give list or list_id, not both",
+                    i + 1
+                ));
+            }
+            if task.assignee.is_some() && task.assignee_id.is_some() {
+                return Err(format!(
+                    "Invalid task {}: give assignee or assignee_id, not both",
+                    i + 1
+                ));
+            }
+            Ok(line)
+        })
+        .collect()
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config;
+use crate::dates;
+use crate::map_words;
+
+const PREFIX: &str = "tpl:";
+
+pub fn get_templates_path() -> PathBuf {
+    config::get_config_dir().join("templates.toml")
+}
+
+/// Load templates (name → shorthand text), keyed by lowercase name.
+/// A missing file means no templates; an invalid one is reported and ignored.
+pub fn load() -> HashMap<String, String> {
+    let path = get_templates_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return HashMap::new(),
+    };
+
+    match toml::from_str::<HashMap<String, String>>(&content) {
+        Ok(templates) => templates
+            .into_iter()
+            .map(|(name, text)| (name.to_lowercase(), text))
+            .collect(),
+        Err(e) => {
+            eprintln!(
+                "Warning: ignoring invalid templates file {}: {}",
+                path.display(),
+                e
+            );
+            HashMap::new()
+        }
+    }
+}
+
+/// Values for the placeholders every template can use
+fn builtin_vars() -> HashMap<String, String> {
+    let today = dates::today();
+    HashMap::from([
+        ("date".to_string(), today.to_string()),
+        ("weekday".to_string(), today.weekday_name()),
+    ])
+}
+
+/// Split a multi-line template into tasks, one per line. Indented lines
+/// stay with the line above them as its subtasks.
+pub fn render_tasks(
+    name: &str,
+    templates: &HashMap<String, String>,
+    vars: &[(String, String)],
+) -> Result<Vec<String>, String> {
+    let template = templates
+        .get(&name.to_lowercase())
+        .ok_or_else(|| format!("Unknown template: {}", name))?;
+    let mut all_vars = builtin_vars();
+    all_vars.extend(vars.iter().map(|(k, v)| (k.to_lowercase(), v.clone())));
+    let text = substitute(template, &all_vars)
+        .map_err(|e| format!("Template {}: {} (set it with --var)", name, e))?;
+
+    let mut tasks: Vec<String> = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match tasks.last_mut() {
+            Some(task) if line.starts_with(char::is_whitespace) => {
+                task.push('\n');
+                task.push_str(line);
+            }
+            _ => tasks.push(line.trim().to_string()),
+        }
+    }
+    Ok(tasks)
+}
+
+/// Replace `{name}` placeholders with their values. Braces around anything
+/// that isn't a plain name are left alone; an unknown name is an error.
+fn substitute(text: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let name = after.find('}').map(|close| &after[..close]).filter(|name| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        });
+        match name {
+            Some(name) => {
+                let value = vars
+                    .get(&name.to_lowercase())
+                    .ok_or_else(|| format!("No value for {{{}}}", name))?;
+                result.push_str(value);
+                rest = &after[name.len() + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Replace `tpl:name` words with the named template, filling in `{date}` and
+/// `{weekday}`. Nothing after a standalone `--` is expanded.
+pub fn expand(input: &str, templates: &HashMap<String, String>) -> Result<String, String> {
+    let mut vars = None;
+    map_words(input, |word| {
+        let Some(name) = word.strip_prefix(PREFIX).filter(|name| !name.is_empty()) else {
+            return Ok(None);
+        };
+        let template = templates
+            .get(&name.to_lowercase())
+            .ok_or_else(|| format!("Unknown template: {}", name))?;
+        let vars = vars.get_or_insert_with(builtin_vars);
+        substitute(template.trim(), vars)
+            .map(Some)
+            .map_err(|e| format!("Template {}: {}", name, e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, text)| (name.to_string(), text.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let vars = HashMap::from([("who".to_string(), "Sam".to_string())]);
+        assert_eq!(
+            substitute("Call {who} {WHO}", &vars).unwrap(),
+            "Call Sam Sam"
+        );
+    }
+
+    #[test]
+    fn braces_that_are_not_placeholders_stay() {
+        let vars = HashMap::new();
+        for text in ["{}", "{a b}", "json {\"x\": 1}", "open {", "close }"] {
+            assert_eq!(substitute(text, &vars).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn unknown_placeholder_is_an_error() {
+        let error = substitute("Call {who}", &HashMap::new()).unwrap_err();
+        assert_eq!(error, "No value for {who}");
+    }
+
+    #[test]
+    fn tpl_words_expand_with_the_date() {
+        let templates = templates(&[("standup", "Standup notes {date} @Work")]);
+        let expanded = expand("tpl:Standup .Daily", &templates).unwrap();
+        assert_eq!(
+            expanded,
+            format!("Standup notes {} @Work .Daily", dates::today())
+        );
+    }
+
+    #[test]
+    fn tpl_words_after_the_marker_are_left_alone() {
+        let templates = templates(&[("standup", "Standup")]);
+        assert_eq!(
+            expand("Read -- tpl:standup", &templates).unwrap(),
+            "Read -- tpl:standup"
+        );
+        assert_eq!(expand("tpl: alone", &templates).unwrap(), "tpl: alone");
+    }
+
+    #[test]
+    fn checklist_lines_become_tasks_with_their_subtasks() {
+        let templates = templates(&[(
+            "trip",
+            "Pack for {place} @Travel\n  passport\n  charger\n\nBook taxi ^{day}\n",
+        )]);
+        let vars = [
+            ("PLACE".to_string(), "Oslo".to_string()),
+            ("day".to_string(), "fri".to_string()),
+        ];
+        let tasks = render_tasks("Trip", &templates, &vars).unwrap();
+        assert_eq!(
+            tasks,
+            [
+                "Pack for Oslo @Travel\n  passport\n  charger",
+                "Book taxi ^fri"
+            ]
+        );
+    }
+
+    #[test]
+    fn checklist_needs_every_variable() {
+        let templates = templates(&[("trip", "Pack for {place}")]);
+        let error = render_tasks("trip", &templates, &[]).unwrap_err();
+        assert_eq!(
+            error,
+            "Template trip: No value for {place} (set it with --var)"
+        );
+        let error = render_tasks("nope", &templates, &[]).unwrap_err();
+        assert_eq!(error, "Unknown template: nope");
+    }
+
+    #[test]
+    fn unknown_template_is_an_error() {
+        let error = expand("tpl:nope", &HashMap::new()).unwrap_err();
+        assert_eq!(error, "Unknown template: nope");
+    }
+}
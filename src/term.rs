@@ -0,0 +1,234 @@
+use serde::Deserialize;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicI8, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Hyperlinks {
+    /// Only in terminals known to support them
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Color {
+    /// When writing to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct OutputConfig {
+    pub hyperlinks: Hyperlinks,
+    pub color: Color,
+}
+
+/// What a piece of output is, which decides its color
+#[derive(Clone, Copy)]
+pub enum Style {
+    Success,
+    Error,
+    Heading,
+    Detail,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Success => "32",
+            Style::Error => "1;31",
+            Style::Heading => "1",
+            Style::Detail => "2",
+        }
+    }
+}
+
+static HYPERLINKS: OnceLock<bool> = OnceLock::new();
+static COLOR: OnceLock<Color> = OnceLock::new();
+static JSON: AtomicBool = AtomicBool::new(false);
+/// -1 for `-q`, 1 for `-v` and 2 for `-vv`
+static VERBOSITY: AtomicI8 = AtomicI8::new(0);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Make commands print JSON instead of text (`--json`)
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::SeqCst);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// How much to say: below 0 only errors and warnings, above it what the run
+/// is doing
+pub fn set_verbosity(level: i8) {
+    STARTED.get_or_init(Instant::now);
+    VERBOSITY.store(level, Ordering::SeqCst);
+}
+
+/// `-q`: confirmations and progress aren't printed
+pub fn quiet() -> bool {
+    VERBOSITY.load(Ordering::SeqCst) < 0
+}
+
+/// Whether `-v` was given at least `level` times
+pub fn verbose(level: i8) -> bool {
+    VERBOSITY.load(Ordering::SeqCst) >= level
+}
+
+/// A line about what the run is doing, on stderr, with the time since it started
+pub fn debug(message: &str) {
+    let elapsed = STARTED.get_or_init(Instant::now).elapsed();
+    eprintln!(
+        "{} {}",
+        paint_err(Style::Detail, &format!("[{:>7.3}s]", elapsed.as_secs_f64())),
+        message
+    );
+}
+
+/// Print a command's result as pretty JSON on stdout
+pub fn print_json(value: &serde_json::Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    );
+}
+
+/// The terminal, to read answers from and write questions to even when
+/// stdin and stdout are pipes. None when there's no terminal, as in a hotkey.
+#[cfg(not(windows))]
+pub fn open_tty() -> Option<(File, File)> {
+    let tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    Some((tty.try_clone().ok()?, tty))
+}
+
+/// The console's input and output, Windows' equivalent of `/dev/tty`
+#[cfg(windows)]
+pub fn open_tty() -> Option<(File, File)> {
+    let input = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("CONIN$")
+        .ok()?;
+    let output = OpenOptions::new().write(true).open("CONOUT$").ok()?;
+    Some((input, output))
+}
+
+/// Guess from the environment whether stdout is a terminal that renders OSC 8 links
+fn detect_hyperlinks() -> bool {
+    if let Some(force) = env::var_os("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+    if !std::io::stdout().is_terminal() || env::var("TERM").is_ok_and(|t| t == "dumb") {
+        return false;
+    }
+
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+    matches!(
+        term_program.as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper" | "Tabby"
+    ) || ["xterm-kitty", "alacritty", "foot", "wezterm"]
+        .iter()
+        .any(|name| term.contains(name))
+        || env::var_os("WT_SESSION").is_some()
+        || env::var_os("KONSOLE_VERSION").is_some()
+        || env::var("VTE_VERSION")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .is_some_and(|v| v >= 5000)
+}
+
+/// Decide once per run whether to emit hyperlinks and colors, honoring the
+/// config override; `--no-color` turns colors off whatever it says
+pub fn configure(config: &OutputConfig, no_color: bool) {
+    let enabled = match config.hyperlinks {
+        Hyperlinks::Always => true,
+        Hyperlinks::Never => false,
+        Hyperlinks::Auto => detect_hyperlinks(),
+    };
+    let _ = HYPERLINKS.set(enabled);
+    let _ = COLOR.set(if no_color { Color::Never } else { config.color });
+}
+
+fn use_color(terminal: bool) -> bool {
+    match COLOR.get().copied().unwrap_or_default() {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => {
+            terminal
+                && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                && !env::var("TERM").is_ok_and(|t| t == "dumb")
+        }
+    }
+}
+
+fn paint_if(color: bool, style: Style, text: &str) -> String {
+    if color && !json() {
+        format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `text` in the style's color, for stdout
+pub fn paint(style: Style, text: &str) -> String {
+    paint_if(use_color(std::io::stdout().is_terminal()), style, text)
+}
+
+/// `text` in the style's color, for stderr
+pub fn paint_err(style: Style, text: &str) -> String {
+    paint_if(use_color(std::io::stderr().is_terminal()), style, text)
+}
+
+/// Rows with their columns padded to line up, the header first and in bold.
+/// The last column isn't padded, so long titles don't leave trailing spaces.
+pub fn table(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let columns = header.len();
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| -> String {
+        let mut line = String::new();
+        for (i, cell) in cells.iter().enumerate().take(columns) {
+            line.push_str(cell);
+            if i + 1 < columns {
+                let pad = widths[i] - cell.chars().count() + 2;
+                line.push_str(&" ".repeat(pad));
+            }
+        }
+        line.trim_end().to_string()
+    };
+    let mut lines = vec![paint(Style::Heading, &line(header.to_vec()))];
+    for row in rows {
+        lines.push(line(row.iter().map(String::as_str).collect()));
+    }
+    lines
+}
+
+/// `text` as a clickable link to `url`, or unchanged where links aren't
+/// supported and in JSON output
+pub fn hyperlink(text: &str, url: &str) -> String {
+    if *HYPERLINKS.get_or_init(detect_hyperlinks) && !json() {
+        format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,88 @@
+use reqwest::blocking::ClientBuilder;
+use reqwest::tls::{Certificate, Version};
+use std::fs;
+
+use crate::config::ApiConfig;
+use crate::expand_home;
+
+/// Certificates and TLS settings from `[api]`, loaded once per run
+#[derive(Clone, Default)]
+pub struct Tls {
+    /// Trusted alongside the system's roots, for every request
+    extra_roots: Vec<Certificate>,
+    /// The only roots trusted for the API, when set
+    pinned: Vec<Certificate>,
+    min_version: Option<Version>,
+}
+
+impl Tls {
+    pub fn from_config(config: &ApiConfig) -> Result<Self, String> {
+        let min_version = match config.min_tls.as_deref() {
+            None => None,
+            Some("1.2") => Some(Version::TLS_1_2),
+            // The platform's TLS library can't require 1.3, only prefer it
+            Some(other) => {
+                return Err(format!(
+                    "invalid min_tls {} (only \"1.2\" is supported; 1.3 is used when the server offers it)",
+                    other
+                ))
+            }
+        };
+        Ok(Tls {
+            extra_roots: config
+                .ca_file
+                .as_deref()
+                .map(load)
+                .transpose()?
+                .unwrap_or_default(),
+            pinned: config
+                .pin_file
+                .as_deref()
+                .map(load)
+                .transpose()?
+                .unwrap_or_default(),
+            min_version,
+        })
+    }
+
+    /// Settings for requests to pages other than the API
+    pub fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        for cert in &self.extra_roots {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        if let Some(version) = self.min_version {
+            builder = builder.min_tls_version(version);
+        }
+        builder
+    }
+
+    /// Settings for requests to the API: as `apply`, and with a pin only the
+    /// pinned certificates are trusted, so a proxy or a compromised CA can't
+    /// read the API key
+    pub fn apply_pinned(&self, builder: ClientBuilder) -> ClientBuilder {
+        if self.pinned.is_empty() {
+            return self.apply(builder);
+        }
+        let mut builder = builder.tls_built_in_root_certs(false);
+        if let Some(version) = self.min_version {
+            builder = builder.min_tls_version(version);
+        }
+        for cert in &self.pinned {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        builder
+    }
+}
+
+/// Read every certificate in a PEM file
+fn load(path: &str) -> Result<Vec<Certificate>, String> {
+    let full_path = expand_home(path);
+    let pem =
+        fs::read(&full_path).map_err(|e| format!("Can't read {}: {}", full_path.display(), e))?;
+    let certs = Certificate::from_pem_bundle(&pem)
+        .map_err(|e| format!("Can't read certificates in {}: {}", full_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates in {}", full_path.display()));
+    }
+    Ok(certs)
+}
@@ -0,0 +1,59 @@
+use crate::cancel;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct UrlConfig {
+    /// Move URLs out of the title and into the notes
+    pub move_to_notes: bool,
+    /// When the input is only a URL, use the page's `<title>` as the task title
+    pub fetch_title: bool,
+}
+
+fn is_url(word: &str) -> bool {
+    word.starts_with("http://") || word.starts_with("https://")
+}
+
+/// Split URLs out of a title, returning the remaining title and the URLs
+pub fn extract_urls(title: &str) -> (String, Vec<String>) {
+    let (urls, words): (Vec<&str>, Vec<&str>) = title.split_whitespace().partition(|w| is_url(w));
+    (
+        words.join(" "),
+        urls.into_iter().map(|u| u.to_string()).collect(),
+    )
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+fn find_title(html: &str) -> Option<String> {
+    let lower = html.to_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = decode_entities(&html[start..end]);
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+/// Fetch a page and return its `<title>`, or None if it can't be read
+pub fn fetch_page_title(url: &str, client: reqwest::blocking::Client) -> Option<String> {
+    let url = url.to_string();
+    let html = cancel::run(move || {
+        let response = client.get(&url).send()?.error_for_status()?;
+        Ok(response.text()?)
+    })
+    .ok()?;
+    find_title(&html)
+}
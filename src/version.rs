@@ -0,0 +1,178 @@
+use reqwest::blocking::{Client, Response};
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+
+use crate::dates;
+use crate::{get_data_dir, write_atomic};
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct UpdatesConfig {
+    /// Check once a day for a newer release and for deprecated endpoints
+    pub check: bool,
+    /// Where the latest release is read from: a GitHub-style release feed
+    /// whose JSON has a `tag_name` such as `v0.2.0`
+    pub feed: String,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        UpdatesConfig {
+            check: true,
+            feed: "https://api.github.com/repos/quintrino/godspeed-cli/releases/latest".to_string(),
+        }
+    }
+}
+
+const CURRENT: &str = env!("CARGO_PKG_VERSION");
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Endpoints the API said are deprecated during this run, with when they
+/// stop working if it said
+static DEPRECATED: Mutex<Vec<(String, Option<String>)>> = Mutex::new(Vec::new());
+
+/// Holds the time of the last check, so it runs once a day
+fn get_state_path() -> PathBuf {
+    get_data_dir().join("version-checked")
+}
+
+/// Remember an API response's `Deprecation` header (and `Sunset`, when the
+/// endpoint has a last day), to be reported by the next daily check
+pub fn note_deprecation(response: &Response) {
+    if !response.headers().contains_key("deprecation") {
+        return;
+    }
+    let endpoint = response.url().path().to_string();
+    let sunset = response
+        .headers()
+        .get("sunset")
+        .and_then(|value| value.to_str().ok())
+        .map(|sunset| {
+            dates::parse_http_date(sunset).map_or_else(
+                || sunset.to_string(),
+                |at| format!("on {}", dates::format_local(at)),
+            )
+        });
+    let mut deprecated = DEPRECATED.lock().unwrap_or_else(|e| e.into_inner());
+    if !deprecated.iter().any(|(seen, _)| *seen == endpoint) {
+        deprecated.push((endpoint, sunset));
+    }
+}
+
+/// A day after the last check, start looking up the latest release in the
+/// background, with the client `client` returns. Pass the handle to `finish`
+/// once the run's work is done.
+pub fn start(
+    config: &UpdatesConfig,
+    client: impl FnOnce() -> reqwest::Result<Client> + Send + 'static,
+) -> Option<JoinHandle<Option<String>>> {
+    if !config.check || crate::mock::enabled() {
+        return None;
+    }
+    let now = dates::now_unix();
+    let last = fs::read_to_string(get_state_path())
+        .ok()
+        .and_then(|checked| dates::parse_utc(checked.trim()));
+    if last.is_some_and(|last| now - last < DAY_SECS && last <= now) {
+        return None;
+    }
+    // Saved before checking, so being offline doesn't mean a try every run
+    if let Err(e) = write_atomic(&get_state_path(), dates::format_utc(now) + "\n") {
+        eprintln!("Warning: failed to save the version check time: {}", e);
+        return None;
+    }
+    let feed = config.feed.clone();
+    Some(thread::spawn(move || latest_release(&feed, client().ok()?)))
+}
+
+/// Wait for the check and warn if this version is a minor release or more
+/// behind, or the API deprecated an endpoint it used
+pub fn finish(check: JoinHandle<Option<String>>) {
+    if let Some(latest) = check.join().ok().flatten() {
+        if is_outdated(CURRENT, &latest) {
+            eprintln!(
+                "Warning: godspeed-cli {} is out of date; {} is available (brew upgrade godspeed-cli)",
+                CURRENT,
+                latest.trim_start_matches('v')
+            );
+        }
+    }
+    let deprecated = DEPRECATED.lock().unwrap_or_else(|e| e.into_inner());
+    for (endpoint, sunset) in deprecated.iter() {
+        match sunset {
+            Some(sunset) => eprintln!(
+                "Warning: the API has deprecated {}, which godspeed-cli uses; it stops working {}. Check for an update.",
+                endpoint, sunset
+            ),
+            None => eprintln!(
+                "Warning: the API has deprecated {}, which godspeed-cli uses; check for an update",
+                endpoint
+            ),
+        }
+    }
+}
+
+/// The latest release's tag, or None if the feed can't be read
+fn latest_release(feed: &str, client: Client) -> Option<String> {
+    #[derive(Deserialize)]
+    struct Release {
+        tag_name: String,
+    }
+    let response = client
+        .get(feed)
+        .header("User-Agent", format!("godspeed-cli/{}", CURRENT))
+        .header("Accept", "application/json")
+        .send()
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response
+        .json::<Release>()
+        .ok()
+        .map(|release| release.tag_name)
+}
+
+/// Whether `latest` is a newer major or minor version than `current`; a
+/// newer patch release alone isn't worth a warning
+fn is_outdated(current: &str, latest: &str) -> bool {
+    let parse = |version: &str| -> Option<(u64, u64)> {
+        let mut parts = version.trim().trim_start_matches('v').split('.');
+        Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+    };
+    match (parse(current), parse(latest)) {
+        (Some(current), Some(latest)) => latest > current,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_minor_or_major_is_outdated() {
+        assert!(is_outdated("0.1.0", "v0.2.0"));
+        assert!(is_outdated("0.9.3", "1.0.0"));
+        assert!(is_outdated("1.9.0", "1.10.0"));
+    }
+
+    #[test]
+    fn patch_releases_and_older_versions_are_not() {
+        assert!(!is_outdated("0.1.0", "v0.1.7"));
+        assert!(!is_outdated("0.2.0", "0.1.9"));
+        assert!(!is_outdated("1.0.0", " v1.0.0\n"));
+    }
+
+    #[test]
+    fn tags_that_are_not_versions_are_ignored() {
+        assert!(!is_outdated("0.1.0", "nightly"));
+        assert!(!is_outdated("0.1.0", "v1"));
+        assert!(!is_outdated("0.1.0", ""));
+        assert!(!is_outdated("dev", "9.0.0"));
+    }
+}
@@ -0,0 +1,269 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::cancel;
+use crate::{expand_home, write_atomic};
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// Drop folder for `watch` without a path, such as `~/Dropbox/inbox`
+    pub folder: Option<String>,
+    /// Where captured files are moved; `archive/` inside the folder by default
+    pub archive: Option<String>,
+    pub interval_secs: u64,
+    /// Lines of a watched file that start with this are tasks
+    pub pattern: String,
+    /// Replaces the pattern on lines that were captured
+    pub mark: String,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            folder: None,
+            archive: None,
+            interval_secs: 5,
+            pattern: "- [ ] ".to_string(),
+            mark: "- [>] ".to_string(),
+        }
+    }
+}
+
+/// A file modified more recently than this may still be being written
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Check `folder` every interval, handing each text file dropped there to
+/// `capture` as task text and then moving it to the archive. Stops after one
+/// pass with `once`, otherwise on Ctrl-C.
+pub fn folder(
+    folder: &Path,
+    config: &WatchConfig,
+    once: bool,
+    mut capture: impl FnMut(&str),
+) -> Result<(), String> {
+    if !folder.is_dir() {
+        return Err(format!("{} is not a folder", folder.display()));
+    }
+    let archive = match &config.archive {
+        Some(archive) => expand_home(archive),
+        None => folder.join("archive"),
+    };
+    fs::create_dir_all(&archive)
+        .map_err(|e| format!("Can't create {}: {}", archive.display(), e))?;
+
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    // Files that aren't text are reported once and then left where they are
+    let mut skipped = HashSet::new();
+    while !cancel::is_cancelled() {
+        for path in dropped_files(folder) {
+            if cancel::is_cancelled() {
+                break;
+            }
+            if skipped.contains(&path) {
+                continue;
+            }
+            let Some(text) = task_text(&path) else {
+                skipped.insert(path);
+                continue;
+            };
+            capture(&text);
+            if let Err(e) = archive_file(&path, &archive) {
+                // Stop rather than capture the same file again next time
+                return Err(format!("Can't archive {}: {}", path.display(), e));
+            }
+        }
+        if once {
+            break;
+        }
+        thread::sleep(interval);
+    }
+    Ok(())
+}
+
+/// Check the file at `path` every interval, handing each line that starts
+/// with the pattern to `capture` and then marking it, so it's only captured
+/// once. Stops after one pass with `once`, otherwise on Ctrl-C.
+pub fn file(
+    path: &Path,
+    config: &WatchConfig,
+    once: bool,
+    mut capture: impl FnMut(&str),
+) -> Result<(), String> {
+    if config.pattern.trim().is_empty() || config.mark.trim() == config.pattern.trim() {
+        return Err("[watch] pattern can't be empty or the same as mark".into());
+    }
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let mut last_seen = None;
+    while !cancel::is_cancelled() {
+        let metadata =
+            fs::metadata(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        let seen = (metadata.modified().ok(), metadata.len());
+        if last_seen != Some(seen) {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+            let captured: Vec<&str> = content
+                .lines()
+                .filter(|line| task_line(line, &config.pattern).is_some())
+                .collect();
+            for line in &captured {
+                if cancel::is_cancelled() {
+                    break;
+                }
+                capture(task_line(line, &config.pattern).unwrap_or_default());
+                mark_line(path, line, config)
+                    .map_err(|e| format!("Can't mark the line in {}: {}", path.display(), e))?;
+            }
+            let metadata = fs::metadata(path).map_err(|e| e.to_string())?;
+            last_seen = Some((metadata.modified().ok(), metadata.len()));
+        }
+        if once {
+            break;
+        }
+        thread::sleep(interval);
+    }
+    Ok(())
+}
+
+/// The task on a line that starts with `pattern`, after any indentation
+fn task_line<'a>(line: &'a str, pattern: &str) -> Option<&'a str> {
+    let task = line.trim_start().strip_prefix(pattern)?.trim();
+    (!task.is_empty()).then_some(task)
+}
+
+/// Swap the pattern for the mark on the first line still reading `line`.
+/// The file is read again first, so edits made meanwhile are kept.
+fn mark_line(path: &Path, line: &str, config: &WatchConfig) -> std::io::Result<()> {
+    let content = fs::read_to_string(path)?;
+    if let Some(marked) = marked(&content, line, config) {
+        write_atomic(path, marked)?;
+    }
+    Ok(())
+}
+
+/// `content` with `line` marked, or None if no line reads that any more
+fn marked(content: &str, line: &str, config: &WatchConfig) -> Option<String> {
+    let mut marked = String::with_capacity(content.len() + config.mark.len());
+    let mut done = false;
+    for current in content.split_inclusive('\n') {
+        if !done && current.trim_end_matches(['\r', '\n']) == line {
+            marked.push_str(&current.replacen(&config.pattern, &config.mark, 1));
+            done = true;
+        } else {
+            marked.push_str(current);
+        }
+    }
+    done.then_some(marked)
+}
+
+/// Files in `folder` that are done being written, oldest first. Hidden
+/// files, such as a sync client's partial downloads, are left alone.
+fn dropped_files(folder: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return Vec::new();
+    };
+    let now = SystemTime::now();
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok()?;
+            let settled = now.duration_since(modified).is_ok_and(|age| age >= SETTLE);
+            (metadata.is_file() && settled).then(|| (modified, entry.path()))
+        })
+        .collect();
+    files.sort();
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+/// The task for a dropped file: its name, without the extension, is the
+/// title and its contents are the notes. None for files that aren't text.
+fn task_text(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let Ok(contents) = String::from_utf8(bytes) else {
+        eprintln!("Skipping {}: not a text file", path.display());
+        return None;
+    };
+    Some(text_for(&path.file_stem()?.to_string_lossy(), &contents))
+}
+
+fn text_for(name: &str, contents: &str) -> String {
+    let mut text = name.trim().to_string();
+    // Indented lines would be read as subtasks
+    for line in contents.trim().lines() {
+        text.push('\n');
+        text.push_str(line.trim_start());
+    }
+    text
+}
+
+/// Move a captured file into the archive, numbering it if the name is taken
+fn archive_file(path: &Path, archive: &Path) -> std::io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut target = archive.join(&*name);
+    let mut n = 1;
+    while target.exists() {
+        n += 1;
+        target = archive.join(format!("{} {}", n, name));
+    }
+    fs::rename(path, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchecked_lines_are_tasks() {
+        let pattern = &WatchConfig::default().pattern;
+        assert_eq!(
+            task_line("- [ ] Buy milk @errands", pattern),
+            Some("Buy milk @errands")
+        );
+        assert_eq!(
+            task_line("    - [ ]  Indented  ", pattern),
+            Some("Indented")
+        );
+        assert_eq!(task_line("- [ ] ", pattern), None);
+        assert_eq!(task_line("- [x] Done", pattern), None);
+        assert_eq!(task_line("- [>] Captured", pattern), None);
+        assert_eq!(task_line("Notes - [ ] inline", pattern), None);
+    }
+
+    #[test]
+    fn only_the_first_matching_line_is_marked() {
+        let config = WatchConfig::default();
+        let content = "# Inbox\r\n- [ ] Call Sam\r\n- [ ] Call Sam\n  - [ ] Nested\n";
+        assert_eq!(
+            marked(content, "- [ ] Call Sam", &config).unwrap(),
+            "# Inbox\r\n- [>] Call Sam\r\n- [ ] Call Sam\n  - [ ] Nested\n"
+        );
+        assert_eq!(
+            marked(content, "  - [ ] Nested", &config).unwrap(),
+            "# Inbox\r\n- [ ] Call Sam\r\n- [ ] Call Sam\n  - [>] Nested\n"
+        );
+    }
+
+    #[test]
+    fn file_name_is_the_title_and_contents_the_notes() {
+        assert_eq!(
+            text_for(" Buy milk @errands ", "\n  two litres\n\tsemi-skimmed\n\n"),
+            "Buy milk @errands\ntwo litres\nsemi-skimmed"
+        );
+        assert_eq!(text_for("Call Sam", ""), "Call Sam");
+    }
+
+    #[test]
+    fn a_line_edited_meanwhile_is_not_marked() {
+        let config = WatchConfig::default();
+        assert_eq!(
+            marked("- [ ] Call Sam later\n", "- [ ] Call Sam", &config),
+            None
+        );
+    }
+}